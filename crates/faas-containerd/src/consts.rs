@@ -1,13 +1,35 @@
 #[allow(unused)]
 pub const DEFAULT_FUNCTION_NAMESPACE: &str = "faasrs-default";
 
-#[allow(unused)]
+/// 未配置 `SNAPSHOTTER` 时优先使用的 snapshotter
 pub const DEFAULT_SNAPSHOTTER: &str = "overlayfs";
 
+/// 镜像缓存所在的命名空间，镜像先拉取到这里，再导入各个函数命名空间
+pub const CACHE_NAMESPACE: &str = "faasrs-cache";
+
 pub const DEFAULT_CTRD_SOCK: &str = "/run/containerd/containerd.sock";
 
 pub const DEFAULT_FAASDRS_DATA_DIR: &str = "/var/lib/faasdrs";
 
+/// 为各函数生成的 resolv.conf 等文件所在的目录
+pub const FUNCTION_RUN_DIR: &str = "/run/faasdrs/functions";
+
+/// 函数既没有端口注解、镜像也没有唯一暴露的 TCP 端口时使用的端口
+pub const DEFAULT_UPSTREAM_PORT: u16 = 8080;
+
+/// 容器标签：用户部署时指定的镜像引用
+pub const LABEL_IMAGE: &str = "faasrs.io/image";
+/// 容器标签：部署时解析得到的镜像 digest
+pub const LABEL_IMAGE_DIGEST: &str = "faasrs.io/image-digest";
+/// 容器标签：JSON 格式的就绪探测配置，没有该标签的函数不做探测
+pub const LABEL_READINESS_PROBE: &str = "faasrs.io/readiness-probe";
+/// 容器标签：JSON 格式的进程限制，即打开文件数、进程数与 /dev/shm 大小
+pub const LABEL_PROCESS_LIMITS: &str = "faasrs.io/process-limits";
+/// 容器标签：部署时选择的运行时类
+pub const LABEL_RUNTIME_CLASS: &str = "faasrs.io/runtime-class";
+/// 镜像标签：由 faasrs 管理的镜像，只有它们会被回收
+pub const LABEL_MANAGED: &str = "faasrs.io/managed";
+
 // 定义版本的常量
 pub const VERSION_MAJOR: u32 = 1;
 pub const VERSION_MINOR: u32 = 1;
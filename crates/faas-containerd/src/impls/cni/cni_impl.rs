@@ -1,12 +1,17 @@
 type Err = Box<dyn std::error::Error>;
 
-use derive_more::{Display, Error};
+use derive_more::Display;
 use netns_rs::NetNs;
 use scopeguard::{ScopeGuard, guard};
-use serde_json::Value;
-use std::{fmt::Error, net::IpAddr, path::Path, sync::LazyLock};
+use serde_json::{Map, Value};
+use std::{net::IpAddr, path::Path, sync::LazyLock};
 
-use super::{Endpoint, command as cmd, util};
+use super::{
+    Endpoint,
+    config::NetworkConfig,
+    exec::{self, CniError, CniExec, RuntimeArgs},
+    policy, util,
+};
 
 static CNI_CONF_DIR: LazyLock<String> = LazyLock::new(|| {
     std::env::var("CNI_CONF_DIR").unwrap_or_else(|_| "/etc/cni/net.d".to_string())
@@ -14,116 +19,145 @@ static CNI_CONF_DIR: LazyLock<String> = LazyLock::new(|| {
 
 const CNI_DATA_DIR: &str = "/var/run/cni";
 const DEFAULT_CNI_CONF_FILENAME: &str = "10-faasrs.conflist";
-const DEFAULT_NETWORK_NAME: &str = "faasrs-cni-bridge";
-const DEFAULT_BRIDGE_NAME: &str = "faasrs0";
-const DEFAULT_SUBNET: &str = "10.66.0.0/16";
+
+static CNI_EXEC: LazyLock<CniExec> = LazyLock::new(CniExec::from_env);
 
 pub fn init_cni_network() -> Result<(), Err> {
+    let config = NetworkConfig::from_env()?;
+    log::info!(
+        "CNI network {} on bridge {} with subnets {:?}",
+        config.network_name,
+        config.bridge,
+        config.subnets
+    );
     util::init_net_fs(
         Path::new(CNI_CONF_DIR.as_str()),
         DEFAULT_CNI_CONF_FILENAME,
-        DEFAULT_NETWORK_NAME,
-        DEFAULT_BRIDGE_NAME,
-        DEFAULT_SUBNET,
+        config,
+        &CNI_EXEC,
         CNI_DATA_DIR,
-    )
+    )?;
+    policy::init_bridge_netfilter();
+    Ok(())
+}
+
+fn conflist() -> &'static Value {
+    &util::CNI_CONFIG_FILE.get().unwrap().conflist
 }
 
-#[derive(Debug, Display, Error)]
-pub struct NetworkError {
-    pub msg: String,
+/// 函数网络的子网
+pub fn subnets() -> &'static [cidr::IpCidr] {
+    &util::CNI_CONFIG_FILE.get().unwrap().subnets
 }
 
-//TODO: 创建网络和删除网络的错误处理
-pub fn create_cni_network(endpoint: &Endpoint) -> Result<(cidr::IpInet, NetNs), NetworkError> {
+/// 双栈时访问函数使用的地址族，即第一个配置的子网的地址族
+pub fn preferred_family() -> cidr::Family {
+    util::CNI_CONFIG_FILE
+        .get()
+        .and_then(|conf| conf.subnets.first())
+        .map_or(cidr::Family::Ipv4, |subnet| subnet.family())
+}
+
+#[derive(Debug, Display)]
+pub enum NetworkError {
+    #[display("Failed to create netns: {}", _0)]
+    CreateNetns(String),
+    #[display("Failed to get netns: {}", _0)]
+    GetNetns(String),
+    #[display("Failed to remove netns: {}", _0)]
+    RemoveNetns(String),
+    #[display("{}", _0)]
+    Cni(CniError),
+    #[display("Invalid CNI result: {}", _0)]
+    InvalidResult(String),
+}
+
+impl std::error::Error for NetworkError {}
+
+impl From<CniError> for NetworkError {
+    fn from(e: CniError) -> Self {
+        NetworkError::Cni(e)
+    }
+}
+
+fn runtime_args<'a>(
+    container_id: &'a str,
+    netns: &'a Path,
+    capability_args: &'a Map<String, Value>,
+) -> RuntimeArgs<'a> {
+    RuntimeArgs {
+        container_id,
+        netns,
+        ifname: exec::DEFAULT_IFNAME,
+        capability_args,
+    }
+}
+
+/// 网络插件链中是否有插件声明了该 capability
+pub fn supports_capability(capability: &str) -> bool {
+    conflist()["plugins"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .any(|plugin| plugin["capabilities"][capability] == Value::Bool(true))
+}
+
+/// 返回每个配置的地址族分配到的地址，`capability_args` 传给声明了对应 capabilities 的插件
+pub fn create_cni_network(
+    endpoint: &Endpoint,
+    capability_args: &Map<String, Value>,
+) -> Result<(Vec<cidr::IpInet>, NetNs), NetworkError> {
     let net_ns = guard(
-        NetNs::new(endpoint.to_string()).map_err(|e| NetworkError {
-            msg: format!("Failed to create netns: {}", e),
-        })?,
+        NetNs::new(endpoint.to_string()).map_err(|e| NetworkError::CreateNetns(e.to_string()))?,
         |ns| ns.remove().unwrap(),
     );
 
-    let output = cmd::cni_add_bridge(net_ns.path(), DEFAULT_NETWORK_NAME);
-
-    match output {
-        Ok(output) => {
-            if !output.status.success() {
-                return Err(NetworkError {
-                    msg: format!(
-                        "Failed to add CNI bridge: {}",
-                        String::from_utf8_lossy(&output.stderr)
-                    ),
-                });
-            }
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let mut json: Value = match serde_json::from_str(&stdout) {
-                Ok(json) => json,
-                Err(e) => {
-                    log::error!("Failed to parse JSON: {}", e);
-                    return Err(NetworkError {
-                        msg: format!("Failed to parse JSON: {}", e),
-                    });
-                }
-            };
-            log::trace!("CNI add bridge output: {:?}", json);
-            if let serde_json::Value::Array(ips) = json["ips"].take() {
-                let mut ip_list = Vec::new();
-                for mut ip in ips {
-                    if let serde_json::Value::String(ip_str) = ip["address"].take() {
-                        let ipcidr = ip_str.parse::<cidr::IpInet>().map_err(|e| {
-                            log::error!("Failed to parse IP address: {}", e);
-                            NetworkError { msg: e.to_string() }
-                        })?;
-                        ip_list.push(ipcidr);
-                    }
-                }
-                if ip_list.is_empty() {
-                    return Err(NetworkError {
-                        msg: "No IP address found in CNI output".to_string(),
-                    });
-                }
-                if ip_list.len() > 1 {
-                    log::warn!("Multiple IP addresses found in CNI output: {:?}", ip_list);
-                }
-                log::trace!("CNI network created with IP: {:?}", ip_list[0]);
-                Ok((ip_list[0], ScopeGuard::into_inner(net_ns)))
-            } else {
-                log::error!("Invalid JSON format: {:?}", json);
-                Err(NetworkError {
-                    msg: "Invalid JSON format".to_string(),
-                })
-            }
-        }
-        Err(e) => {
-            log::error!("Failed to add CNI bridge: {}", e);
-            Err(NetworkError {
-                msg: format!("Failed to add CNI bridge: {}", e),
+    let container_id = exec::container_id(net_ns.path());
+    let result = CNI_EXEC.add(
+        conflist(),
+        &runtime_args(&container_id, net_ns.path(), capability_args),
+    )?;
+    log::trace!("CNI add result: {:?}", result);
+
+    let ips = result["ips"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|ip| ip["address"].as_str())
+        .map(|addr| {
+            addr.parse::<cidr::IpInet>().map_err(|e| {
+                log::error!("Failed to parse IP address: {}", e);
+                NetworkError::InvalidResult(e.to_string())
             })
-        }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if ips.is_empty() {
+        return Err(NetworkError::InvalidResult(
+            "no IP address found in CNI result".to_string(),
+        ));
     }
+    log::trace!("CNI network created with IP: {:?}", ips);
+    Ok((ips, ScopeGuard::into_inner(net_ns)))
 }
 
 pub fn delete_cni_network(endpoint: Endpoint) -> Result<(), NetworkError> {
-    match NetNs::get(endpoint.to_string()) {
-        Ok(ns) => {
-            let e1 = cmd::cni_del_bridge(ns.path(), DEFAULT_NETWORK_NAME);
-            let e2 = ns.remove();
-            if e1.is_err() || e2.is_err() {
-                let err = format!(
-                    "NetNS exists, but failed to delete CNI network, cni bridge: {:?}, netns: {:?}",
-                    e1, e2
-                );
-                log::error!("{}", err);
-                return Err(NetworkError { msg: err });
-            }
-            Ok(())
-        }
-        Err(e) => {
-            let msg = format!("Failed to get netns {}: {}", endpoint, e);
-            log::warn!("{}", msg);
-            Err(NetworkError { msg })
-        }
-    }
+    let ns = NetNs::get(endpoint.to_string()).map_err(|e| {
+        log::warn!("Failed to get netns {}: {}", endpoint, e);
+        NetworkError::GetNetns(e.to_string())
+    })?;
+    let container_id = exec::container_id(ns.path());
+    let capability_args = Map::new();
+    let del_result = CNI_EXEC.del(
+        conflist(),
+        &runtime_args(&container_id, ns.path(), &capability_args),
+    );
+    // 插件失败时也移除 netns，避免残留
+    let rm_result = ns.remove();
+    del_result.map_err(|e| {
+        log::error!("Failed to delete CNI network of {}: {}", endpoint, e);
+        e
+    })?;
+    rm_result.map_err(|e| NetworkError::RemoveNetns(e.to_string()))
 }
 
 #[inline]
@@ -135,14 +169,3 @@ pub fn check_network_exists(addr: IpAddr) -> bool {
         .join(addr.to_string())
         .exists()
 }
-
-#[allow(unused)]
-fn cni_gateway() -> Result<String, Err> {
-    let ip: IpAddr = DEFAULT_SUBNET.parse().unwrap();
-    if let IpAddr::V4(ip) = ip {
-        let octets = &mut ip.octets();
-        octets[3] = 1;
-        return Ok(ip.to_string());
-    }
-    Err(Box::new(Error))
-}
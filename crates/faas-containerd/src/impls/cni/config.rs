@@ -0,0 +1,218 @@
+use cidr::{Family, IpCidr};
+use serde_json::{Value, json};
+
+const DEFAULT_NETWORK_NAME: &str = "faasrs-cni-bridge";
+const DEFAULT_BRIDGE_NAME: &str = "faasrs0";
+const DEFAULT_SUBNET: &str = "10.66.0.0/16";
+
+/// 网络接口名的最大长度 (IFNAMSIZ - 1)
+const MAX_BRIDGE_NAME_LEN: usize = 15;
+
+/// 函数网络的配置，来自环境变量：
+/// - `CNI_NETWORK_NAME`: 网络名
+/// - `CNI_BRIDGE_NAME`: 网桥名
+/// - `CNI_SUBNETS`: 逗号分隔的子网，如 `10.66.0.0/16,fd66::/64`
+/// - `CNI_EXTRA_PLUGINS`: 追加在 firewall 之后的插件，
+///   可以是 `portmap,bandwidth,tuning` 这样的插件名，也可以是插件配置的 JSON 数组
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkConfig {
+    pub network_name: String,
+    pub bridge: String,
+    pub subnets: Vec<IpCidr>,
+    pub plugins: Vec<Value>,
+}
+
+impl NetworkConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |key: &str, default: &str| std::env::var(key).unwrap_or(default.to_string());
+        let config = NetworkConfig {
+            network_name: var("CNI_NETWORK_NAME", DEFAULT_NETWORK_NAME),
+            bridge: var("CNI_BRIDGE_NAME", DEFAULT_BRIDGE_NAME),
+            subnets: parse_subnets(&var("CNI_SUBNETS", DEFAULT_SUBNET))?,
+            plugins: parse_plugins(&var("CNI_EXTRA_PLUGINS", ""))?,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let name = &self.network_name;
+        if name.is_empty()
+            || !name.starts_with(|c: char| c.is_ascii_alphanumeric())
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c))
+        {
+            return Err(format!("invalid network name '{}'", name));
+        }
+        if self.bridge.is_empty()
+            || self.bridge.len() > MAX_BRIDGE_NAME_LEN
+            || self
+                .bridge
+                .chars()
+                .any(|c| c == '/' || c == ':' || c.is_whitespace() || c.is_control())
+        {
+            return Err(format!(
+                "invalid bridge name '{}', expected at most {} characters without '/', ':' or spaces",
+                self.bridge, MAX_BRIDGE_NAME_LEN
+            ));
+        }
+        if self.subnets.is_empty() {
+            return Err("at least one subnet is required".to_string());
+        }
+        for plugin in &self.plugins {
+            match plugin_type(plugin) {
+                Some("bridge" | "firewall") => {
+                    return Err("bridge and firewall plugins are always configured".to_string());
+                }
+                Some(_) => {}
+                None => return Err(format!("invalid plugin type in {}", plugin)),
+            }
+        }
+        Ok(())
+    }
+
+    /// 生成 conflist，`data_dir` 为 host-local 记录分配地址的目录
+    pub fn conflist(&self, data_dir: &str) -> Value {
+        // host-local 按 ranges 的顺序为每个地址族分配一个地址
+        let ranges: Vec<_> = self
+            .subnets
+            .iter()
+            .map(|subnet| json!([{ "subnet": subnet.to_string() }]))
+            .collect();
+        let routes: Vec<_> = self
+            .subnets
+            .iter()
+            .map(|subnet| match subnet.family() {
+                Family::Ipv4 => json!({ "dst": "0.0.0.0/0" }),
+                Family::Ipv6 => json!({ "dst": "::/0" }),
+            })
+            .collect();
+        let mut plugins = vec![
+            json!({
+                "type": "bridge",
+                "bridge": self.bridge,
+                "isGateway": true,
+                "ipMasq": true,
+                "ipam": {
+                    "type": "host-local",
+                    "ranges": ranges,
+                    "dataDir": data_dir,
+                    "routes": routes
+                }
+            }),
+            json!({ "type": "firewall" }),
+        ];
+        plugins.extend(self.plugins.iter().cloned());
+        json!({
+            "cniVersion": "0.4.0",
+            "name": self.network_name,
+            "plugins": plugins
+        })
+    }
+}
+
+/// 插件类型同时是 CNI_PATH 下的可执行文件名，只允许简单的名字
+pub(super) fn plugin_type(plugin: &Value) -> Option<&str> {
+    plugin["type"].as_str().filter(|ty| {
+        !ty.is_empty()
+            && ty
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    })
+}
+
+/// 逗号分隔的子网，每个地址族最多一个，先写的地址族优先用于访问函数
+pub(super) fn parse_subnets(subnets: &str) -> Result<Vec<IpCidr>, String> {
+    let subnets = subnets
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<IpCidr>()
+                .map_err(|e| format!("invalid subnet '{}': {}", s, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if subnets.is_empty() {
+        return Err("at least one subnet is required".to_string());
+    }
+    if subnets.len() > 2 || (subnets.len() == 2 && subnets[0].family() == subnets[1].family()) {
+        return Err("expected at most one IPv4 and one IPv6 subnet".to_string());
+    }
+    Ok(subnets)
+}
+
+fn parse_plugins(plugins: &str) -> Result<Vec<Value>, String> {
+    let plugins = plugins.trim();
+    if plugins.starts_with('[') {
+        let plugins: Vec<Value> = serde_json::from_str(plugins)
+            .map_err(|e| format!("invalid CNI_EXTRA_PLUGINS: {}", e))?;
+        if let Some(plugin) = plugins.iter().find(|p| !p.is_object()) {
+            return Err(format!("plugin config must be an object, got {}", plugin));
+        }
+        return Ok(plugins);
+    }
+    plugins
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|name| match name {
+            "portmap" => Ok(json!({ "type": "portmap", "capabilities": { "portMappings": true } })),
+            "bandwidth" => {
+                Ok(json!({ "type": "bandwidth", "capabilities": { "bandwidth": true } }))
+            }
+            "tuning" => Ok(json!({ "type": "tuning" })),
+            other => Err(format!(
+                "unknown plugin '{}', use a JSON array to configure it",
+                other
+            )),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subnets() {
+        let subnets = parse_subnets("10.66.0.0/16, fd66::/64").unwrap();
+        assert_eq!(subnets.len(), 2);
+        assert_eq!(subnets[1].family(), Family::Ipv6);
+
+        assert!(parse_subnets("").is_err());
+        assert!(parse_subnets("10.66.0.0/16,10.67.0.0/16").is_err());
+        assert!(parse_subnets("10.66.0.1/16").is_err());
+    }
+
+    #[test]
+    fn test_network_config() {
+        let mut config = NetworkConfig {
+            network_name: "faasrs".to_string(),
+            bridge: "faasrs1".to_string(),
+            subnets: parse_subnets("172.30.0.0/24,fd66::/64").unwrap(),
+            plugins: parse_plugins("portmap, tuning").unwrap(),
+        };
+        assert!(config.validate().is_ok());
+
+        let conf = config.conflist("/var/run/cni");
+        let plugins = conf["plugins"].as_array().unwrap();
+        assert_eq!(plugins.len(), 4);
+        assert_eq!(plugins[0]["bridge"], "faasrs1");
+        assert_eq!(plugins[0]["ipam"]["ranges"][1][0]["subnet"], "fd66::/64");
+        assert_eq!(plugins[0]["ipam"]["routes"][1]["dst"], "::/0");
+        assert_eq!(plugins[2]["type"], "portmap");
+
+        config.plugins = parse_plugins(r#"[{"type": "bandwidth", "ingressRate": 1000}]"#).unwrap();
+        assert!(config.validate().is_ok());
+        config.plugins = parse_plugins(r#"[{"type": "../../bin/sh"}]"#).unwrap();
+        assert!(config.validate().is_err());
+        config.plugins = parse_plugins(r#"[{"type": "bridge"}]"#).unwrap();
+        assert!(config.validate().is_err());
+        assert!(parse_plugins("flannel").is_err());
+
+        config.plugins.clear();
+        config.bridge = "a-very-long-bridge0".to_string();
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! 函数网络内置 DNS 的报文处理，只实现解析单个问题的查询所需的部分 (RFC 1035)
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+pub const DNS_PORT: u16 = 53;
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+const HEADER_LEN: usize = 12;
+/// 名称的最大长度，超过的查询视为无效
+const MAX_NAME_LEN: usize = 255;
+
+pub const RCODE_NOERROR: u8 = 0;
+pub const RCODE_SERVFAIL: u8 = 2;
+pub const RCODE_NXDOMAIN: u8 = 3;
+
+/// 查询报文中的问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Question {
+    /// 小写、不带末尾的点
+    pub name: String,
+    pub qtype: u16,
+    pub qclass: u16,
+    /// 问题在报文中结束的位置，应答时原样复制报文头之后到这里的内容
+    end: usize,
+}
+
+impl Question {
+    /// 本地只回答 IN 类的 A 与 AAAA 查询
+    pub fn is_address_query(&self) -> bool {
+        self.qclass == CLASS_IN && (self.qtype == TYPE_A || self.qtype == TYPE_AAAA)
+    }
+}
+
+/// 解析标准查询，只接受恰好一个问题且问题中没有压缩指针的报文
+pub fn parse_query(packet: &[u8]) -> Option<Question> {
+    let header = packet.get(..HEADER_LEN)?;
+    // QR 必须为 0，OPCODE 必须为标准查询
+    if header[2] & 0xf8 != 0 {
+        return None;
+    }
+    if u16::from_be_bytes([header[4], header[5]]) != 1 {
+        return None;
+    }
+
+    let mut labels = Vec::new();
+    let mut pos = HEADER_LEN;
+    loop {
+        let len = *packet.get(pos)? as usize;
+        pos += 1;
+        if len == 0 {
+            break;
+        }
+        // 0xc0 开头是压缩指针，0x40 与 0x80 是保留的标签类型
+        if len & 0xc0 != 0 {
+            return None;
+        }
+        let label = packet.get(pos..pos + len)?;
+        labels.push(std::str::from_utf8(label).ok()?.to_ascii_lowercase());
+        pos += len;
+        if pos - HEADER_LEN > MAX_NAME_LEN {
+            return None;
+        }
+    }
+    let fixed = packet.get(pos..pos + 4)?;
+    Some(Question {
+        name: labels.join("."),
+        qtype: u16::from_be_bytes([fixed[0], fixed[1]]),
+        qclass: u16::from_be_bytes([fixed[2], fixed[3]]),
+        end: pos + 4,
+    })
+}
+
+/// 构造应答：复制查询的 ID、RD 与问题，附带与问题类型一致的地址记录。
+/// 查询中的附加记录 (如 EDNS) 不会带回
+pub fn response(
+    query: &[u8],
+    question: &Question,
+    rcode: u8,
+    addrs: &[IpAddr],
+    ttl: u32,
+) -> Vec<u8> {
+    let answers: Vec<_> = addrs
+        .iter()
+        .filter(|addr| match question.qtype {
+            TYPE_A => addr.is_ipv4(),
+            TYPE_AAAA => addr.is_ipv6(),
+            _ => false,
+        })
+        .collect();
+
+    let mut packet = Vec::with_capacity(question.end + answers.len() * 28);
+    packet.extend_from_slice(&query[..2]);
+    // QR=1, AA=1, 保留 RD；RA=1
+    packet.push(0x84 | (query[2] & 0x01));
+    packet.push(0x80 | (rcode & 0x0f));
+    packet.extend_from_slice(&1u16.to_be_bytes());
+    packet.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 0]);
+    packet.extend_from_slice(&query[HEADER_LEN..question.end]);
+    for addr in answers {
+        // 名称指向报文头之后的问题
+        packet.extend_from_slice(&[0xc0, HEADER_LEN as u8]);
+        packet.extend_from_slice(&question.qtype.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        packet.extend_from_slice(&ttl.to_be_bytes());
+        match addr {
+            IpAddr::V4(ip) => {
+                packet.extend_from_slice(&4u16.to_be_bytes());
+                packet.extend_from_slice(&ip.octets());
+            }
+            IpAddr::V6(ip) => {
+                packet.extend_from_slice(&16u16.to_be_bytes());
+                packet.extend_from_slice(&ip.octets());
+            }
+        }
+    }
+    packet
+}
+
+/// 网桥上的网关地址，即 host-local 默认分配给网关的子网第一个主机地址
+pub fn gateway_addrs(subnets: &[cidr::IpCidr]) -> Vec<IpAddr> {
+    subnets
+        .iter()
+        .map(|subnet| match subnet.first_address() {
+            IpAddr::V4(ip) => IpAddr::V4(Ipv4Addr::from_bits(ip.to_bits() + 1)),
+            IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from_bits(ip.to_bits() + 1)),
+        })
+        .collect()
+}
+
+/// 读取 resolv.conf 中的上游服务器，跳过内置 DNS 自己的地址以免转发成环
+pub fn upstream_servers(resolv_conf: &str, exclude: &[IpAddr]) -> Vec<SocketAddr> {
+    resolv_conf
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("nameserver") => fields.next(),
+                _ => None,
+            }
+        })
+        // 带 zone 的 IPv6 链路本地地址无法解析，直接跳过
+        .filter_map(|addr| addr.parse::<IpAddr>().ok())
+        .filter(|addr| !exclude.contains(addr))
+        .map(|addr| SocketAddr::new(addr, DNS_PORT))
+        .collect()
+}
+
+/// 函数容器内的 resolv.conf，短名称先在函数所在的命名空间中查找
+pub fn resolv_conf(nameservers: &[IpAddr], namespace: &str) -> String {
+    let mut conf = String::new();
+    for ns in nameservers {
+        conf.push_str(&format!("nameserver {}\n", ns));
+    }
+    conf.push_str(&format!("search {}\n", namespace));
+    conf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+        let mut packet = id.to_be_bytes().to_vec();
+        packet.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in name.split('.') {
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label.as_bytes());
+        }
+        packet.push(0);
+        packet.extend_from_slice(&qtype.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let packet = query(7, "Hello.Default", TYPE_AAAA);
+        let question = parse_query(&packet).unwrap();
+        assert_eq!(question.name, "hello.default");
+        assert_eq!(question.qtype, TYPE_AAAA);
+        assert!(question.is_address_query());
+        assert_eq!(question.end, packet.len());
+
+        // 应答报文、截断的报文与压缩指针都不接受
+        let mut answer = packet.clone();
+        answer[2] |= 0x80;
+        assert!(parse_query(&answer).is_none());
+        assert!(parse_query(&packet[..packet.len() - 1]).is_none());
+        let mut pointer = packet.clone();
+        pointer[HEADER_LEN] = 0xc0;
+        assert!(parse_query(&pointer).is_none());
+    }
+
+    #[test]
+    fn test_response() {
+        let packet = query(0x1234, "hello.default", TYPE_A);
+        let question = parse_query(&packet).unwrap();
+        let addrs = [
+            IpAddr::V4(Ipv4Addr::new(10, 66, 0, 5)),
+            "fd66::5".parse().unwrap(),
+        ];
+        let resp = response(&packet, &question, RCODE_NOERROR, &addrs, 5);
+        assert_eq!(&resp[..2], &[0x12, 0x34]);
+        assert_eq!(resp[2], 0x85);
+        assert_eq!(resp[3], 0x80);
+        // 只有一条 A 记录
+        assert_eq!(&resp[6..8], &[0, 1]);
+        assert_eq!(&resp[HEADER_LEN..question.end], &packet[HEADER_LEN..]);
+        assert_eq!(&resp[resp.len() - 4..], &[10, 66, 0, 5]);
+
+        let resp = response(&packet, &question, RCODE_NXDOMAIN, &[], 5);
+        assert_eq!(resp[3] & 0x0f, RCODE_NXDOMAIN);
+        assert_eq!(resp.len(), question.end);
+    }
+
+    #[test]
+    fn test_resolv_conf() {
+        let subnets = [
+            "10.66.0.0/16".parse().unwrap(),
+            "fd66::/64".parse().unwrap(),
+        ];
+        let gateways = gateway_addrs(&subnets);
+        assert_eq!(gateways[0], IpAddr::V4(Ipv4Addr::new(10, 66, 0, 1)));
+        assert_eq!(gateways[1], "fd66::1".parse::<IpAddr>().unwrap());
+
+        let host = "# generated\nnameserver 10.66.0.1\nnameserver 1.1.1.1\nnameserver fe80::1%eth0\nsearch lan\n";
+        assert_eq!(
+            upstream_servers(host, &gateways),
+            vec!["1.1.1.1:53".parse::<SocketAddr>().unwrap()]
+        );
+        assert_eq!(
+            resolv_conf(&gateways[..1], "default"),
+            "nameserver 10.66.0.1\nsearch default\n"
+        );
+    }
+}
@@ -0,0 +1,419 @@
+use std::{
+    io::Write,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::LazyLock,
+};
+
+use derive_more::Display;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use sha2::Digest;
+
+/// 冒号分隔的插件目录
+static CNI_BIN_DIR: LazyLock<String> =
+    LazyLock::new(|| std::env::var("CNI_BIN_DIR").unwrap_or_else(|_| "/opt/cni/bin".to_string()));
+/// 与 libcni 相同的结果缓存目录，删除网络时作为 prevResult 传给插件
+static CNI_CACHE_DIR: LazyLock<String> =
+    LazyLock::new(|| std::env::var("CNI_CACHE_DIR").unwrap_or_else(|_| "/var/lib/cni".to_string()));
+
+pub const DEFAULT_IFNAME: &str = "eth0";
+
+#[derive(Debug, Display)]
+pub enum CniError {
+    #[display("CNI plugin {} not found in {}", _0, _1)]
+    PluginNotFound(String, String),
+    #[display("Failed to execute CNI plugin {}: {}", _0, _1)]
+    Exec(String, std::io::Error),
+    #[display("CNI plugin {} failed with code {}: {} {}", plugin, code, msg, details)]
+    Plugin {
+        plugin: String,
+        code: u32,
+        msg: String,
+        details: String,
+    },
+    #[display("Invalid output of CNI plugin {}: {}", _0, _1)]
+    InvalidOutput(String, String),
+    #[display("Invalid CNI config: {}", _0)]
+    InvalidConfig(String),
+    #[display("Failed to access CNI result cache: {}", _0)]
+    Cache(std::io::Error),
+}
+
+impl std::error::Error for CniError {}
+
+/// 插件失败时输出的错误 (CNI spec: Error)
+#[derive(Deserialize)]
+struct PluginError {
+    code: u32,
+    msg: String,
+    #[serde(default)]
+    details: String,
+}
+
+/// 一次 ADD 或 DEL 的运行时参数
+pub struct RuntimeArgs<'a> {
+    pub container_id: &'a str,
+    pub netns: &'a Path,
+    pub ifname: &'a str,
+    /// 只注入到声明了对应 capabilities 的插件的 runtimeConfig 中
+    pub capability_args: &'a Map<String, Value>,
+}
+
+/// 与 cnitool 相同的容器 ID，旧版本通过 cnitool 创建的网络仍能正确释放地址
+pub fn container_id(netns: &Path) -> String {
+    let digest = sha2::Sha512::digest(netns.as_os_str().as_bytes());
+    format!("cnitool-{}", hex::encode(&digest[..10]))
+}
+
+/// 按 CNI spec 的执行协议直接调用插件，配置通过 stdin 传入，参数通过环境变量传入
+pub struct CniExec {
+    bin_dirs: Vec<PathBuf>,
+    cache_dir: PathBuf,
+}
+
+impl CniExec {
+    pub fn new(bin_dirs: Vec<PathBuf>, cache_dir: PathBuf) -> Self {
+        Self {
+            bin_dirs,
+            cache_dir,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::split_paths(CNI_BIN_DIR.as_str()).collect(),
+            PathBuf::from(CNI_CACHE_DIR.as_str()),
+        )
+    }
+
+    pub fn find_plugin(&self, ty: &str) -> Result<PathBuf, CniError> {
+        self.bin_dirs
+            .iter()
+            .map(|dir| dir.join(ty))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                CniError::PluginNotFound(
+                    ty.to_string(),
+                    std::env::join_paths(&self.bin_dirs)
+                        .map(|p| p.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                )
+            })
+    }
+
+    /// 依次调用列表中的插件，后一个插件以前一个的结果为 prevResult。
+    /// 失败时对整个列表执行 DEL 以释放已分配的资源
+    pub fn add(&self, conflist: &Value, args: &RuntimeArgs) -> Result<Value, CniError> {
+        let (name, version, plugins) = parse_conflist(conflist)?;
+        let mut prev_result: Option<Value> = None;
+        for plugin in plugins {
+            let conf = plugin_conf(plugin, name, version, prev_result.as_ref(), args);
+            match self.exec("ADD", &conf, args) {
+                Ok(result) => prev_result = Some(result),
+                Err(e) => {
+                    log::error!("CNI ADD of {} failed: {}", args.container_id, e);
+                    if let Err(del_err) = self.del(conflist, args) {
+                        log::warn!("Failed to clean up after CNI ADD: {}", del_err);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        let result = prev_result.unwrap_or_else(|| json!({ "cniVersion": version }));
+        self.write_cache(name, &result, args)?;
+        Ok(result)
+    }
+
+    /// 逆序调用列表中的插件，尽量执行所有插件，返回第一个错误
+    pub fn del(&self, conflist: &Value, args: &RuntimeArgs) -> Result<(), CniError> {
+        let (name, version, plugins) = parse_conflist(conflist)?;
+        let cache = self.cache_path(name, args);
+        let cached = match std::fs::read(&cache) {
+            Ok(bytes) => serde_json::from_slice::<Value>(&bytes)
+                .ok()
+                .map(|mut cached| cached["result"].take())
+                .filter(Value::is_object),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(CniError::Cache(e)),
+        };
+
+        let mut first_err = None;
+        for plugin in plugins.iter().rev() {
+            let conf = plugin_conf(plugin, name, version, cached.as_ref(), args);
+            if let Err(e) = self.exec("DEL", &conf, args) {
+                log::warn!("CNI DEL of {} failed: {}", args.container_id, e);
+                first_err.get_or_insert(e);
+            }
+        }
+        if let Some(e) = first_err {
+            return Err(e);
+        }
+        match std::fs::remove_file(&cache) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(CniError::Cache(e)),
+            _ => Ok(()),
+        }
+    }
+
+    fn exec(&self, command: &str, conf: &Value, args: &RuntimeArgs) -> Result<Value, CniError> {
+        let ty = conf["type"].as_str().unwrap_or_default().to_string();
+        let path = self.find_plugin(&ty)?;
+        let cni_path = std::env::join_paths(&self.bin_dirs)
+            .map_err(|e| CniError::InvalidConfig(e.to_string()))?;
+
+        let mut child = Command::new(&path)
+            .env("CNI_COMMAND", command)
+            .env("CNI_CONTAINERID", args.container_id)
+            .env("CNI_NETNS", args.netns)
+            .env("CNI_IFNAME", args.ifname)
+            .env("CNI_PATH", cni_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| CniError::Exec(ty.clone(), e))?;
+        let stdin_result = child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(conf.to_string().as_bytes());
+        let output = child
+            .wait_with_output()
+            .map_err(|e| CniError::Exec(ty.clone(), e))?;
+
+        if !output.status.success() {
+            return Err(
+                match serde_json::from_slice::<PluginError>(&output.stdout) {
+                    Ok(err) => CniError::Plugin {
+                        plugin: ty,
+                        code: err.code,
+                        msg: err.msg,
+                        details: err.details,
+                    },
+                    Err(_) => CniError::InvalidOutput(
+                        ty,
+                        format!(
+                            "{}: {}",
+                            output.status,
+                            String::from_utf8_lossy(&output.stderr).trim()
+                        ),
+                    ),
+                },
+            );
+        }
+        stdin_result.map_err(|e| CniError::Exec(ty.clone(), e))?;
+        if command == "DEL" {
+            return Ok(Value::Null);
+        }
+        serde_json::from_slice::<Value>(&output.stdout)
+            .ok()
+            .filter(Value::is_object)
+            .ok_or_else(|| {
+                CniError::InvalidOutput(ty, String::from_utf8_lossy(&output.stdout).into_owned())
+            })
+    }
+
+    fn cache_path(&self, name: &str, args: &RuntimeArgs) -> PathBuf {
+        self.cache_dir
+            .join("results")
+            .join(format!("{}-{}-{}", name, args.container_id, args.ifname))
+    }
+
+    fn write_cache(&self, name: &str, result: &Value, args: &RuntimeArgs) -> Result<(), CniError> {
+        let cache = self.cache_path(name, args);
+        let cached = json!({
+            "kind": "cniCacheV1",
+            "containerId": args.container_id,
+            "ifName": args.ifname,
+            "networkName": name,
+            "result": result,
+        });
+        std::fs::create_dir_all(cache.parent().unwrap()).map_err(CniError::Cache)?;
+        std::fs::write(&cache, cached.to_string()).map_err(CniError::Cache)
+    }
+}
+
+fn parse_conflist(conflist: &Value) -> Result<(&str, &str, &Vec<Value>), CniError> {
+    let name = conflist["name"]
+        .as_str()
+        .ok_or_else(|| CniError::InvalidConfig("missing network name".to_string()))?;
+    let version = conflist["cniVersion"]
+        .as_str()
+        .ok_or_else(|| CniError::InvalidConfig("missing cniVersion".to_string()))?;
+    let plugins = conflist["plugins"]
+        .as_array()
+        .filter(|plugins| !plugins.is_empty())
+        .ok_or_else(|| CniError::InvalidConfig("missing plugins".to_string()))?;
+    Ok((name, version, plugins))
+}
+
+/// 单个插件的配置：注入网络名、版本、prevResult 以及插件声明的 capabilities 参数
+fn plugin_conf(
+    plugin: &Value,
+    name: &str,
+    version: &str,
+    prev_result: Option<&Value>,
+    args: &RuntimeArgs,
+) -> Value {
+    let mut conf = plugin.clone();
+    conf["name"] = json!(name);
+    conf["cniVersion"] = json!(version);
+    if let Some(prev_result) = prev_result {
+        conf["prevResult"] = prev_result.clone();
+    }
+    let runtime_config: Map<String, Value> = args
+        .capability_args
+        .iter()
+        .filter(|(key, _)| plugin["capabilities"][key.as_str()] == json!(true))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    if !runtime_config.is_empty() {
+        conf["runtimeConfig"] = Value::Object(runtime_config);
+    }
+    conf
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    /// 用 shell 脚本模拟插件，记录每次调用的命令与 stdin
+    struct FakePlugins {
+        dir: PathBuf,
+    }
+
+    impl FakePlugins {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("faasrs-cni-test-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self { dir }
+        }
+
+        fn plugin(&self, ty: &str, output: &str, code: i32) {
+            let script = format!(
+                "#!/bin/sh\ncat > \"{dir}/{ty}.$CNI_COMMAND.json\"\necho \"$CNI_COMMAND {ty} $CNI_CONTAINERID\" >> \"{dir}/calls\"\n[ \"$CNI_COMMAND\" = DEL ] && exit 0\necho '{output}'\nexit {code}\n",
+                dir = self.dir.display(),
+            );
+            let path = self.dir.join(ty);
+            std::fs::write(&path, script).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        fn stdin(&self, ty: &str, command: &str) -> Value {
+            let stdin = std::fs::read(self.dir.join(format!("{}.{}.json", ty, command))).unwrap();
+            serde_json::from_slice(&stdin).unwrap()
+        }
+
+        fn take_calls(&self) -> Vec<String> {
+            let calls = std::fs::read_to_string(self.dir.join("calls")).unwrap_or_default();
+            let _ = std::fs::remove_file(self.dir.join("calls"));
+            calls.lines().map(str::to_string).collect()
+        }
+    }
+
+    impl Drop for FakePlugins {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    // 所有插件在同一个测试中创建后再执行，避免并发 fork 导致 ETXTBSY
+    #[test]
+    fn test_fake_plugins() {
+        let fake = FakePlugins::new();
+        let result = r#"{"cniVersion":"0.4.0","ips":[{"version":"4","address":"10.66.0.2/16"}]}"#;
+        fake.plugin("bridge", result, 0);
+        fake.plugin("firewall", result, 0);
+        fake.plugin(
+            "exhausted",
+            r#"{"cniVersion":"0.4.0","code":11,"msg":"no more addresses"}"#,
+            1,
+        );
+        let exec = CniExec::new(vec![fake.dir.clone()], fake.dir.join("cache"));
+
+        let capability_args = Map::from_iter([("bandwidth".to_string(), json!({"rate": 1}))]);
+        let args = RuntimeArgs {
+            container_id: "cnitool-test",
+            netns: Path::new("/var/run/netns/test"),
+            ifname: DEFAULT_IFNAME,
+            capability_args: &capability_args,
+        };
+        let conflist = json!({
+            "cniVersion": "0.4.0",
+            "name": "test",
+            "plugins": [
+                { "type": "bridge" },
+                { "type": "firewall", "capabilities": { "bandwidth": true } }
+            ]
+        });
+
+        exec.add(&conflist, &args).unwrap();
+        let bridge = fake.stdin("bridge", "ADD");
+        assert_eq!(bridge["name"], "test");
+        assert!(bridge.get("prevResult").is_none());
+        assert!(bridge.get("runtimeConfig").is_none());
+        let firewall = fake.stdin("firewall", "ADD");
+        assert_eq!(firewall["prevResult"]["ips"][0]["address"], "10.66.0.2/16");
+        assert_eq!(firewall["runtimeConfig"]["bandwidth"]["rate"], 1);
+
+        // 删除时以缓存的最终结果作为 prevResult
+        exec.del(&conflist, &args).unwrap();
+        assert_eq!(
+            fake.stdin("bridge", "DEL")["prevResult"]["ips"][0]["version"],
+            "4"
+        );
+        assert_eq!(
+            fake.take_calls(),
+            vec![
+                "ADD bridge cnitool-test",
+                "ADD firewall cnitool-test",
+                "DEL firewall cnitool-test",
+                "DEL bridge cnitool-test"
+            ]
+        );
+        assert!(!exec.cache_path("test", &args).exists());
+
+        // 插件失败时返回其错误，并清理已添加的插件
+        let failing = json!({
+            "cniVersion": "0.4.0",
+            "name": "test",
+            "plugins": [{ "type": "bridge" }, { "type": "exhausted" }]
+        });
+        match exec.add(&failing, &args) {
+            Err(CniError::Plugin { code, msg, .. }) => {
+                assert_eq!(code, 11);
+                assert_eq!(msg, "no more addresses");
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(
+            fake.take_calls(),
+            vec![
+                "ADD bridge cnitool-test",
+                "ADD exhausted cnitool-test",
+                "DEL exhausted cnitool-test",
+                "DEL bridge cnitool-test"
+            ]
+        );
+
+        let missing = json!({
+            "cniVersion": "0.4.0",
+            "name": "test",
+            "plugins": [{ "type": "portmap" }]
+        });
+        assert!(matches!(
+            exec.add(&missing, &args),
+            Err(CniError::PluginNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn test_container_id() {
+        let id = container_id(Path::new("/var/run/netns/default-echo"));
+        assert!(id.starts_with("cnitool-"));
+        assert_eq!(id.len(), "cnitool-".len() + 20);
+    }
+}
@@ -1,7 +1,10 @@
 use crate::consts;
 
 pub mod cni_impl;
-mod command;
+pub mod config;
+pub mod dns;
+pub mod exec;
+pub mod policy;
 mod util;
 
 pub use cni_impl::init_cni_network;
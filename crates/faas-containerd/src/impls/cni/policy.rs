@@ -0,0 +1,529 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+    io::Write,
+    net::IpAddr,
+    process::{Command, Stdio},
+    sync::{LazyLock, Mutex},
+};
+
+use cidr::{Family, IpCidr};
+use derive_more::Display;
+use gateway::types::{
+    annotation,
+    namespace::{LABEL_EGRESS_ALLOW, LABEL_EGRESS_DENY, LABEL_NETWORK_INGRESS},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+static NFT: LazyLock<String> =
+    LazyLock::new(|| std::env::var("NFT_BIN").unwrap_or_else(|_| "nft".to_string()));
+/// 为 `off` 时不下发隔离规则
+static NETWORK_POLICY: LazyLock<String> =
+    LazyLock::new(|| std::env::var("NETWORK_POLICY").unwrap_or_else(|_| "enforce".to_string()));
+/// 保证规则按生成的顺序下发
+static APPLY_LOCK: Mutex<()> = Mutex::new(());
+
+const TABLE: &str = "inet faasrs";
+
+#[derive(Debug, Display)]
+pub enum PolicyError {
+    #[display("Invalid network policy: {}", _0)]
+    Invalid(String),
+    #[display("Failed to execute {}: {}", *NFT, _0)]
+    Exec(std::io::Error),
+    #[display("Failed to apply nftables rules: {}", _0)]
+    Nft(String),
+    #[display("Failed to store network policy: {}", _0)]
+    Store(String),
+}
+
+impl std::error::Error for PolicyError {}
+
+/// 谁可以直接访问函数，网关通过宿主机访问函数，不受限制
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Ingress {
+    Open,
+    /// 同一命名空间的函数
+    #[default]
+    Namespace,
+    /// 只有网关
+    Gateway,
+}
+
+/// 命名空间的网络策略，出站规则只作用于函数网络之外的地址
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkPolicy {
+    pub ingress: Ingress,
+    #[serde(with = "cidr_list")]
+    pub egress_allow: Vec<IpCidr>,
+    #[serde(with = "cidr_list")]
+    pub egress_deny: Vec<IpCidr>,
+}
+
+impl NetworkPolicy {
+    pub fn from_labels(labels: &HashMap<String, String>) -> Result<Self, PolicyError> {
+        let ingress = match labels.get(LABEL_NETWORK_INGRESS).map(String::as_str) {
+            None | Some("namespace") => Ingress::Namespace,
+            Some("gateway") => Ingress::Gateway,
+            Some("open") => Ingress::Open,
+            Some(other) => {
+                return Err(PolicyError::Invalid(format!(
+                    "invalid {} '{}', expected one of namespace, gateway, open",
+                    LABEL_NETWORK_INGRESS, other
+                )));
+            }
+        };
+        let cidrs = |key: &str| {
+            labels
+                .get(key)
+                .map_or(Ok(Vec::new()), |value| parse_cidrs(value))
+                .map_err(|e| PolicyError::Invalid(format!("{}: {}", key, e)))
+        };
+        Ok(NetworkPolicy {
+            ingress,
+            egress_allow: cidrs(LABEL_EGRESS_ALLOW)?,
+            egress_deny: cidrs(LABEL_EGRESS_DENY)?,
+        })
+    }
+}
+
+/// 部署注解中的单个函数的网络控制
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionNetwork {
+    pub egress_disabled: bool,
+    /// 在命名空间的允许列表之上进一步限制
+    #[serde(with = "cidr_list")]
+    pub egress_allow: Vec<IpCidr>,
+    /// bit/s，0 表示不限速
+    pub ingress_rate: u64,
+    pub egress_rate: u64,
+}
+
+impl FunctionNetwork {
+    pub fn from_annotations(
+        annotations: Option<&HashMap<String, String>>,
+    ) -> Result<Self, PolicyError> {
+        let get = |key: &str| annotations.and_then(|a| a.get(key)).map(String::as_str);
+        let invalid = |key: &str, e: String| PolicyError::Invalid(format!("{}: {}", key, e));
+        let rate = |key: &str| {
+            get(key)
+                .map_or(Ok(0), parse_rate)
+                .map_err(|e| invalid(key, e))
+        };
+        Ok(FunctionNetwork {
+            egress_disabled: match get(annotation::EGRESS_DISABLED) {
+                None | Some("false") => false,
+                Some("true") => true,
+                Some(other) => {
+                    return Err(invalid(
+                        annotation::EGRESS_DISABLED,
+                        format!("expected true or false, got '{}'", other),
+                    ));
+                }
+            },
+            egress_allow: get(annotation::EGRESS_ALLOW)
+                .map_or(Ok(Vec::new()), parse_cidrs)
+                .map_err(|e| invalid(annotation::EGRESS_ALLOW, e))?,
+            ingress_rate: rate(annotation::INGRESS_BANDWIDTH)?,
+            egress_rate: rate(annotation::EGRESS_BANDWIDTH)?,
+        })
+    }
+
+    pub fn has_bandwidth_limit(&self) -> bool {
+        self.ingress_rate > 0 || self.egress_rate > 0
+    }
+
+    /// bandwidth 插件的 runtimeConfig，突发量为一秒的流量
+    pub fn capability_args(&self) -> Map<String, Value> {
+        let mut args = Map::new();
+        if self.has_bandwidth_limit() {
+            args.insert(
+                "bandwidth".to_string(),
+                json!({
+                    "ingressRate": self.ingress_rate,
+                    "ingressBurst": self.ingress_rate,
+                    "egressRate": self.egress_rate,
+                    "egressBurst": self.egress_rate,
+                }),
+            );
+        }
+        args
+    }
+}
+
+/// 以 bit/s 为单位的速率，可带 `k`、`M`、`G` 后缀
+pub fn parse_rate(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, unit) = match value.char_indices().last() {
+        Some((i, 'k')) => (&value[..i], 1_000),
+        Some((i, 'M')) => (&value[..i], 1_000_000),
+        Some((i, 'G')) => (&value[..i], 1_000_000_000),
+        _ => (value, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .filter(|rate| *rate > 0)
+        .ok_or_else(|| format!("invalid rate '{}'", value))
+}
+
+/// 逗号分隔的 CIDR，单个地址视为主机地址
+pub fn parse_cidrs(value: &str) -> Result<Vec<IpCidr>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<IpCidr>()
+                .map_err(|e| format!("invalid CIDR '{}': {}", s, e))
+        })
+        .collect()
+}
+
+mod cidr_list {
+    use cidr::IpCidr;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(cidrs: &[IpCidr], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(cidrs.iter().map(ToString::to_string))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<IpCidr>, D::Error> {
+        Vec::<String>::deserialize(d)?
+            .iter()
+            .map(|cidr| cidr.parse().map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+/// 一个函数的地址及其生效的策略
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionRules {
+    pub namespace: String,
+    pub addrs: Vec<IpAddr>,
+    pub policy: NetworkPolicy,
+    #[serde(default)]
+    pub function: FunctionNetwork,
+}
+
+fn elements<'a, T: std::fmt::Display + 'a>(items: impl IntoIterator<Item = &'a T>) -> String {
+    items
+        .into_iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 每个地址族的匹配前缀与该地址族的元素
+fn by_family<T: std::fmt::Display>(
+    items: &[T],
+    is_v4: impl Fn(&T) -> bool,
+) -> [(&'static str, &'static str, String); 2] {
+    let v4 = elements(items.iter().filter(|item| is_v4(item)));
+    let v6 = elements(items.iter().filter(|item| !is_v4(item)));
+    [("ip", "ipv4", v4), ("ip6", "ipv6", v6)]
+}
+
+/// 生成完整的 nftables 表，整体替换以保证原子性。
+/// 转发链先检查来源函数的出站规则，再检查目的函数的入站规则，
+/// 函数之间经网桥转发的流量需要开启 br_netfilter 才会经过转发链
+pub fn render(functions: &BTreeMap<String, FunctionRules>, subnets: &[IpCidr]) -> String {
+    let mut chains = String::new();
+    let mut egress = Vec::new();
+    let mut ingress = Vec::new();
+
+    for (index, rules) in functions.values().enumerate() {
+        let policy = &rules.policy;
+        let function = &rules.function;
+        if !policy.egress_allow.is_empty()
+            || !policy.egress_deny.is_empty()
+            || function.egress_disabled
+            || !function.egress_allow.is_empty()
+        {
+            let chain = format!("out_{}", index);
+            let _ = writeln!(chains, "    chain {} {{", chain);
+            for (ip, _, set) in by_family(subnets, |s| s.family() == Family::Ipv4) {
+                if !set.is_empty() {
+                    let _ = writeln!(chains, "        {} daddr {{ {} }} return", ip, set);
+                }
+            }
+            for (ip, _, set) in by_family(&policy.egress_deny, |s| s.family() == Family::Ipv4) {
+                if !set.is_empty() {
+                    let _ = writeln!(chains, "        {} daddr {{ {} }} drop", ip, set);
+                }
+            }
+            if function.egress_disabled {
+                let _ = writeln!(chains, "        drop");
+            }
+            // 命名空间与函数的允许列表同时生效
+            for allow in [&policy.egress_allow, &function.egress_allow] {
+                if allow.is_empty() || function.egress_disabled {
+                    continue;
+                }
+                for (ip, proto, set) in by_family(allow, |s| s.family() == Family::Ipv4) {
+                    if set.is_empty() {
+                        let _ = writeln!(chains, "        meta nfproto {} drop", proto);
+                    } else {
+                        let _ = writeln!(chains, "        {} daddr != {{ {} }} drop", ip, set);
+                    }
+                }
+            }
+            let _ = writeln!(chains, "    }}");
+            egress.extend(rules.addrs.iter().map(|addr| (*addr, chain.clone())));
+        }
+
+        if policy.ingress != Ingress::Open {
+            let chain = format!("in_{}", index);
+            let _ = writeln!(chains, "    chain {} {{", chain);
+            if policy.ingress == Ingress::Namespace {
+                let peers: Vec<IpAddr> = functions
+                    .values()
+                    .filter(|peer| peer.namespace == rules.namespace)
+                    .flat_map(|peer| peer.addrs.iter().copied())
+                    .collect();
+                for (ip, _, set) in by_family(&peers, IpAddr::is_ipv4) {
+                    if !set.is_empty() {
+                        let _ = writeln!(chains, "        {} saddr {{ {} }} return", ip, set);
+                    }
+                }
+            }
+            let _ = writeln!(chains, "        drop");
+            let _ = writeln!(chains, "    }}");
+            ingress.extend(rules.addrs.iter().map(|addr| (*addr, chain.clone())));
+        }
+    }
+
+    let mut ruleset = format!("add table {table}\ndelete table {table}\n", table = TABLE);
+    if NETWORK_POLICY.as_str() == "off" {
+        return ruleset;
+    }
+    let _ = writeln!(ruleset, "table {} {{", TABLE);
+    let _ = writeln!(ruleset, "    chain forward {{");
+    let _ = writeln!(
+        ruleset,
+        "        type filter hook forward priority filter - 10; policy accept;"
+    );
+    let _ = writeln!(ruleset, "        ct state established,related accept");
+    for (dir, verdicts) in [("saddr", &egress), ("daddr", &ingress)] {
+        for (ip, _, map) in by_family(
+            &verdicts
+                .iter()
+                .map(|(addr, chain)| VerdictElement(*addr, chain))
+                .collect::<Vec<_>>(),
+            |e| e.0.is_ipv4(),
+        ) {
+            if !map.is_empty() {
+                let _ = writeln!(ruleset, "        {} {} vmap {{ {} }}", ip, dir, map);
+            }
+        }
+    }
+    let _ = writeln!(ruleset, "    }}");
+    ruleset.push_str(&chains);
+    ruleset.push_str("}\n");
+    ruleset
+}
+
+struct VerdictElement<'a>(IpAddr, &'a str);
+
+impl std::fmt::Display for VerdictElement<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} : jump {}", self.0, self.1)
+    }
+}
+
+/// 内置 DNS 是否向 `source` 公开 `target` 的名称：同一命名空间内，或双方都未隔离
+pub fn name_visible(
+    source: Option<&FunctionRules>,
+    target: Option<&FunctionRules>,
+    target_namespace: &str,
+) -> bool {
+    if source.is_some_and(|source| source.namespace == target_namespace) {
+        return true;
+    }
+    let open = |rules: Option<&FunctionRules>| {
+        rules.is_none_or(|rules| rules.policy.ingress == Ingress::Open)
+    };
+    open(source) && open(target)
+}
+
+/// 通过 `nft -f -` 原子地替换整张表
+pub fn apply(ruleset: &str) -> Result<(), PolicyError> {
+    let _guard = APPLY_LOCK.lock().unwrap();
+    log::trace!("Applying nftables rules:\n{}", ruleset);
+    let mut child = Command::new(NFT.as_str())
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(PolicyError::Exec)?;
+    let written = child.stdin.take().unwrap().write_all(ruleset.as_bytes());
+    let output = child.wait_with_output().map_err(PolicyError::Exec)?;
+    if !output.status.success() {
+        return Err(PolicyError::Nft(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    written.map_err(PolicyError::Exec)
+}
+
+/// 让经网桥转发的流量经过 nftables 的转发链，否则同一网桥上的函数之间无法隔离
+pub fn init_bridge_netfilter() {
+    if NETWORK_POLICY.as_str() == "off" {
+        return;
+    }
+    let _ = Command::new("modprobe").arg("br_netfilter").status();
+    for key in ["bridge-nf-call-iptables", "bridge-nf-call-ip6tables"] {
+        if let Err(e) = std::fs::write(format!("/proc/sys/net/bridge/{}", key), "1") {
+            log::warn!(
+                "Failed to enable net.bridge.{}, functions on the bridge are not isolated: {}",
+                key,
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(namespace: &str, addr: &str, policy: NetworkPolicy) -> FunctionRules {
+        FunctionRules {
+            namespace: namespace.to_string(),
+            addrs: vec![addr.parse().unwrap()],
+            policy,
+            function: FunctionNetwork::default(),
+        }
+    }
+
+    #[test]
+    fn test_policy_from_labels() {
+        let labels = HashMap::from([
+            (LABEL_NETWORK_INGRESS.to_string(), "gateway".to_string()),
+            (
+                LABEL_EGRESS_ALLOW.to_string(),
+                "10.1.0.0/16, 1.1.1.1".to_string(),
+            ),
+        ]);
+        let policy = NetworkPolicy::from_labels(&labels).unwrap();
+        assert_eq!(policy.ingress, Ingress::Gateway);
+        assert_eq!(policy.egress_allow[1].to_string(), "1.1.1.1");
+        assert_eq!(
+            serde_json::from_value::<NetworkPolicy>(serde_json::to_value(&policy).unwrap())
+                .unwrap(),
+            policy
+        );
+
+        assert_eq!(
+            NetworkPolicy::from_labels(&HashMap::new()).unwrap(),
+            NetworkPolicy::default()
+        );
+        let invalid = HashMap::from([(LABEL_EGRESS_DENY.to_string(), "10.1.0.1/16".to_string())]);
+        assert!(NetworkPolicy::from_labels(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let subnets = vec!["10.66.0.0/16".parse().unwrap()];
+        let functions = BTreeMap::from([
+            (
+                "a-echo".to_string(),
+                rules("a", "10.66.0.2", NetworkPolicy::default()),
+            ),
+            (
+                "a-sink".to_string(),
+                rules(
+                    "a",
+                    "10.66.0.3",
+                    NetworkPolicy {
+                        ingress: Ingress::Gateway,
+                        egress_allow: parse_cidrs("1.1.1.0/24").unwrap(),
+                        egress_deny: vec![],
+                    },
+                ),
+            ),
+            (
+                "b-echo".to_string(),
+                rules(
+                    "b",
+                    "10.66.0.4",
+                    NetworkPolicy {
+                        ingress: Ingress::Open,
+                        ..Default::default()
+                    },
+                ),
+            ),
+        ]);
+        let ruleset = render(&functions, &subnets);
+        assert!(ruleset.starts_with("add table inet faasrs\ndelete table inet faasrs\n"));
+        assert!(ruleset.contains("ip saddr vmap { 10.66.0.3 : jump out_1 }"));
+        assert!(ruleset.contains("ip daddr vmap { 10.66.0.2 : jump in_0, 10.66.0.3 : jump in_1 }"));
+        // 同一命名空间的函数可以互相访问，其他命名空间被拒绝
+        assert!(ruleset.contains(
+            "    chain in_0 {\n        ip saddr { 10.66.0.2, 10.66.0.3 } return\n        drop\n"
+        ));
+        assert!(ruleset.contains("    chain in_1 {\n        drop\n"));
+        assert!(!ruleset.contains("in_2"));
+        assert!(ruleset.contains("ip daddr { 10.66.0.0/16 } return"));
+        assert!(ruleset.contains("ip daddr != { 1.1.1.0/24 } drop"));
+        assert!(ruleset.contains("meta nfproto ipv6 drop"));
+    }
+
+    #[test]
+    fn test_name_visible() {
+        let open = NetworkPolicy {
+            ingress: Ingress::Open,
+            ..Default::default()
+        };
+        let a = rules("a", "10.66.0.2", NetworkPolicy::default());
+        let a_peer = rules("a", "10.66.0.3", NetworkPolicy::default());
+        let b = rules("b", "10.66.0.4", open.clone());
+        let c = rules("c", "10.66.0.5", open);
+        assert!(name_visible(Some(&a_peer), Some(&a), "a"));
+        // 任一方隔离时跨命名空间的名称不可见
+        assert!(!name_visible(Some(&b), Some(&a), "a"));
+        assert!(!name_visible(Some(&a), Some(&b), "b"));
+        assert!(!name_visible(None, Some(&a), "a"));
+        assert!(name_visible(Some(&c), Some(&b), "b"));
+    }
+
+    #[test]
+    fn test_function_network() {
+        let annotations = HashMap::from([
+            (annotation::EGRESS_DISABLED.to_string(), "true".to_string()),
+            (annotation::INGRESS_BANDWIDTH.to_string(), "10M".to_string()),
+        ]);
+        let function = FunctionNetwork::from_annotations(Some(&annotations)).unwrap();
+        assert!(function.egress_disabled);
+        assert_eq!(function.ingress_rate, 10_000_000);
+        assert_eq!(function.egress_rate, 0);
+        assert_eq!(
+            function.capability_args()["bandwidth"]["ingressRate"],
+            10_000_000
+        );
+        assert!(FunctionNetwork::default().capability_args().is_empty());
+
+        let mut rules = rules("a", "10.66.0.2", NetworkPolicy::default());
+        rules.function = function;
+        let ruleset = render(
+            &BTreeMap::from([("a-echo".to_string(), rules)]),
+            &["10.66.0.0/16".parse().unwrap()],
+        );
+        assert!(ruleset.contains(
+            "    chain out_0 {\n        ip daddr { 10.66.0.0/16 } return\n        drop\n"
+        ));
+
+        assert_eq!(parse_rate("512k"), Ok(512_000));
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("10Mi").is_err());
+        let invalid = HashMap::from([(annotation::EGRESS_DISABLED.to_string(), "yes".to_string())]);
+        assert!(FunctionNetwork::from_annotations(Some(&invalid)).is_err());
+    }
+}
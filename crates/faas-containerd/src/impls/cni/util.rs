@@ -1,8 +1,13 @@
-use std::fs::File;
-use std::io::Write;
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 
+use cidr::IpCidr;
+use serde_json::Value;
+
+use super::config::NetworkConfig;
+use super::exec::CniExec;
+
 pub static CNI_CONFIG_FILE: OnceLock<CniConfFile> = OnceLock::new();
 
 // /// Generate "cns-cid"
@@ -14,62 +19,86 @@ pub static CNI_CONFIG_FILE: OnceLock<CniConfFile> = OnceLock::new();
 pub fn init_net_fs(
     conf_dir: &Path,
     conf_filename: &str,
-    net_name: &str,
-    bridge: &str,
-    subnet: &str,
+    config: NetworkConfig,
+    exec: &CniExec,
     data_dir: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let conf_file = CniConfFile::new(conf_dir, conf_filename, net_name, bridge, subnet, data_dir)?;
+    // 启动时检查所有插件，而不是在第一次部署时才失败
+    let conflist = config.conflist(data_dir);
+    for plugin in conflist["plugins"].as_array().into_iter().flatten() {
+        for ty in [&plugin["type"], &plugin["ipam"]["type"]] {
+            if let Some(ty) = ty.as_str() {
+                exec.find_plugin(ty)?;
+            }
+        }
+    }
+    let conf_file = CniConfFile::new(conf_dir, conf_filename, config, data_dir)?;
     CNI_CONFIG_FILE
         .set(conf_file)
         .map_err(|_| "Failed to set CNI_CONFIG_FILE")?;
     Ok(())
 }
 
-fn cni_conf(name: &str, bridge: &str, subnet: &str, data_dir: &str) -> String {
-    format!(
-        r#"
-{{
-    "cniVersion": "0.4.0",
-    "name": "{}",
-    "plugins": [
-      {{
-        "type": "bridge",
-        "bridge": "{}",
-        "isGateway": true,
-        "ipMasq": true,
-        "ipam": {{
-            "type": "host-local",
-            "subnet": "{}",
-            "dataDir": "{}",
-            "routes": [
-                {{ "dst": "0.0.0.0/0" }}
-            ]
-        }}
-      }},
-      {{
-        "type": "firewall"
-      }}
-    ]
-}}
-"#,
-        name, bridge, subnet, data_dir
-    )
+/// host-local 以地址为文件名记录已分配的地址
+fn leases(dir: &Path) -> Vec<IpAddr> {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 仍有函数在旧网络上时，新配置必须保持网络名与网桥不变，且包含所有已分配的地址，
+/// 否则这些函数在重新生成配置后将无法访问，也无法被正常删除
+fn check_regeneration(
+    previous: &Value,
+    leases: &[IpAddr],
+    config: &NetworkConfig,
+) -> Result<(), String> {
+    if leases.is_empty() {
+        return Ok(());
+    }
+    let name = previous["name"].as_str().unwrap_or_default();
+    let bridge = previous["plugins"][0]["bridge"]
+        .as_str()
+        .unwrap_or_default();
+    if name != config.network_name || bridge != config.bridge {
+        return Err(format!(
+            "{} functions are still attached to network {} on bridge {}, remove them before changing the network name or bridge",
+            leases.len(),
+            name,
+            bridge
+        ));
+    }
+    let orphaned: Vec<_> = leases
+        .iter()
+        .filter(|addr| !config.subnets.iter().any(|subnet| subnet.contains(addr)))
+        .map(IpAddr::to_string)
+        .collect();
+    if !orphaned.is_empty() {
+        return Err(format!(
+            "addresses {} of running functions are outside the configured subnets, remove them before changing the subnets",
+            orphaned.join(", ")
+        ));
+    }
+    Ok(())
 }
 
 pub(super) struct CniConfFile {
     pub conf_dir: PathBuf,
     pub conf_filename: String,
     pub data_dir: PathBuf,
+    pub subnets: Vec<IpCidr>,
+    pub conflist: Value,
 }
 
 impl CniConfFile {
     fn new(
         conf_dir: &Path,
         conf_filename: &str,
-        net_name: &str,
-        bridge: &str,
-        subnet: &str,
+        config: NetworkConfig,
         data_dir: &str,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         if !conf_dir.exists() {
@@ -80,13 +109,31 @@ impl CniConfFile {
             panic!("CNI_CONF_DIR is not a directory");
         }
         let net_config = conf_dir.join(conf_filename);
-        File::create(&net_config)?
-            .write_all(cni_conf(net_name, bridge, subnet, data_dir).as_bytes())?;
+        let conflist = config.conflist(data_dir);
+        let content = serde_json::to_string_pretty(&conflist)?;
+
+        let previous = std::fs::read(&net_config).ok();
+        if previous.as_deref() != Some(content.as_bytes()) {
+            if let Some(previous) = previous.and_then(|p| serde_json::from_slice(&p).ok()) {
+                let previous: Value = previous;
+                let name = previous["name"].as_str().unwrap_or_default();
+                let leases = leases(&Path::new(data_dir).join(name));
+                check_regeneration(&previous, &leases, &config)?;
+                log::info!("Regenerating CNI config {}", net_config.display());
+            }
+            // 先写临时文件再替换，插件不会读到写了一半的配置
+            let tmp = conf_dir.join(format!(".{}.tmp", conf_filename));
+            std::fs::write(&tmp, content)?;
+            std::fs::rename(&tmp, &net_config)?;
+        }
+
         let data_dir = PathBuf::from(data_dir);
         Ok(Self {
             conf_dir: conf_dir.to_path_buf(),
             conf_filename: conf_filename.to_string(),
-            data_dir: data_dir.join(net_name),
+            data_dir: data_dir.join(&config.network_name),
+            subnets: config.subnets,
+            conflist,
         })
     }
 }
@@ -99,3 +146,32 @@ impl Drop for CniConfFile {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::impls::cni::config;
+
+    #[test]
+    fn test_check_regeneration() {
+        let mut config = NetworkConfig {
+            network_name: "faasrs".to_string(),
+            bridge: "faasrs0".to_string(),
+            subnets: config::parse_subnets("10.66.0.0/16").unwrap(),
+            plugins: vec![],
+        };
+        let previous = config.conflist("/var/run/cni");
+        let leases: Vec<IpAddr> = vec!["10.66.0.2".parse().unwrap()];
+
+        config.subnets = config::parse_subnets("10.66.0.0/16,fd66::/64").unwrap();
+        assert!(check_regeneration(&previous, &leases, &config).is_ok());
+
+        config.subnets = config::parse_subnets("172.30.0.0/16").unwrap();
+        assert!(check_regeneration(&previous, &leases, &config).is_err());
+        assert!(check_regeneration(&previous, &[], &config).is_ok());
+
+        config.subnets = config::parse_subnets("10.66.0.0/16").unwrap();
+        config.bridge = "faasrs1".to_string();
+        assert!(check_regeneration(&previous, &leases, &config).is_err());
+    }
+}
@@ -1,13 +1,17 @@
 use containerd_client::{
-    services::v1::{Container, DeleteContainerRequest, GetContainerRequest, ListContainersRequest},
+    services::v1::{
+        Container, DeleteContainerRequest, GetContainerRequest, ListContainersRequest,
+        UpdateContainerRequest,
+    },
     with_namespace,
 };
 
 use derive_more::Display;
 
-use containerd_client::services::v1::container::Runtime;
-
-use super::{ContainerdService, backend, cni::Endpoint, function::ContainerStaticMetadata};
+use super::{
+    ContainerdService, backend, cni::Endpoint, function::ContainerStaticMetadata,
+    runtime_class::RUNTIME_CLASSES,
+};
 use tonic::Request;
 
 #[derive(Debug, Display)]
@@ -26,16 +30,21 @@ impl ContainerdService {
         let container = Container {
             id: metadata.endpoint.function_name.clone(),
             image: metadata.image.clone(),
-            runtime: Some(Runtime {
-                name: "io.containerd.runc.v2".to_string(),
-                options: None,
-            }),
+            runtime: Some(
+                RUNTIME_CLASSES
+                    .runtime(&metadata.runtime_class)
+                    .ok_or_else(|| {
+                        log::error!("Unknown runtime class '{}'", metadata.runtime_class);
+                        ContainerError::Internal
+                    })?,
+            ),
             spec: Some(backend().get_spec(metadata).await.map_err(|_| {
                 log::error!("Failed to get spec");
                 ContainerError::Internal
             })?),
-            snapshotter: crate::consts::DEFAULT_SNAPSHOTTER.to_string(),
+            snapshotter: metadata.snapshotter.clone(),
             snapshot_key: metadata.endpoint.function_name.clone(),
+            labels: metadata.labels.clone().into_iter().collect(),
             ..Default::default()
         };
 
@@ -74,6 +83,35 @@ impl ContainerdService {
             .map(|_| ())
     }
 
+    /// 设置容器的一个标签，`value` 为 None 时删除该标签
+    pub async fn set_container_label(
+        &self,
+        endpoint: &Endpoint,
+        key: &str,
+        value: Option<String>,
+    ) -> Result<(), ContainerError> {
+        let mut cc = self.client.containers();
+        let req = UpdateContainerRequest {
+            container: Some(Container {
+                id: endpoint.function_name.clone(),
+                labels: value
+                    .map(|value| [(key.to_string(), value)].into())
+                    .unwrap_or_default(),
+                ..Default::default()
+            }),
+            update_mask: Some(prost_types::FieldMask {
+                paths: vec![format!("labels.{}", key)],
+            }),
+        };
+        cc.update(with_namespace!(req, endpoint.namespace))
+            .await
+            .map_err(|e| {
+                log::error!("Failed to update container labels: {}", e);
+                ContainerError::Internal
+            })
+            .map(|_| ())
+    }
+
     /// 根据查询条件加载容器参数
     pub async fn load_container(&self, endpoint: &Endpoint) -> Result<Container, ContainerError> {
         let mut cc = self.client.containers();
@@ -0,0 +1,229 @@
+//! 交互式 exec：在函数任务中以终端运行进程，输入输出经由 FIFO 与 containerd shim 交换
+
+use std::{
+    ffi::CString,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use bytes::Bytes;
+use containerd_client::{
+    services::v1::{
+        DeleteProcessRequest, ExecProcessRequest, KillRequest, ResizePtyRequest, StartRequest,
+        WaitRequest,
+    },
+    with_namespace,
+};
+use gateway::types::exec::{ExecRequest, ExecSession, TerminalSize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::unix::pipe,
+};
+use tonic::Request;
+
+use super::{
+    ContainerdService,
+    cni::Endpoint,
+    task::{TaskError, next_exec_id},
+};
+
+/// 每个 exec 进程在其下有一个保存 stdin/stdout FIFO 的目录
+const EXEC_FIFO_DIR: &str = "/run/faasdrs/exec";
+
+fn io_error(e: std::io::Error) -> TaskError {
+    TaskError::Internal(e.to_string())
+}
+
+fn mkfifo(path: &Path) -> std::io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: path 是以 NUL 结尾的有效字符串
+    if unsafe { libc::mkfifo(path.as_ptr(), 0o600) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl ContainerdService {
+    /// 以终端运行 `request.command` 并转发 `session` 的输入输出与窗口大小，返回退出码。
+    /// `session.stdin` 关闭时杀死进程
+    pub async fn exec_interactive(
+        &self,
+        endpoint: &Endpoint,
+        request: ExecRequest,
+        session: ExecSession,
+    ) -> Result<u32, TaskError> {
+        let exec_id = next_exec_id();
+        let dir = PathBuf::from(EXEC_FIFO_DIR).join(format!(
+            "{}-{}-{}",
+            endpoint.namespace, endpoint.function_name, exec_id
+        ));
+        std::fs::create_dir_all(&dir).map_err(io_error)?;
+        let result = self
+            .run_interactive(endpoint, &exec_id, &dir, request, session)
+            .await;
+        if let Err(e) = std::fs::remove_dir_all(&dir) {
+            log::warn!("Failed to remove exec fifos {:?}: {}", dir, e);
+        }
+        result
+    }
+
+    async fn run_interactive(
+        &self,
+        endpoint: &Endpoint,
+        exec_id: &str,
+        dir: &Path,
+        request: ExecRequest,
+        mut session: ExecSession,
+    ) -> Result<u32, TaskError> {
+        let Endpoint {
+            function_name: cid,
+            namespace: ns,
+        } = endpoint;
+        let stdin_path = dir.join("stdin");
+        let stdout_path = dir.join("stdout");
+        mkfifo(&stdin_path).map_err(io_error)?;
+        mkfifo(&stdout_path).map_err(io_error)?;
+        // 以读写方式打开，shim 打开另一端之前不会阻塞
+        let mut stdin = pipe::OpenOptions::new()
+            .read_write(true)
+            .open_sender(&stdin_path)
+            .map_err(io_error)?;
+        let mut stdout = pipe::OpenOptions::new()
+            .read_write(true)
+            .open_receiver(&stdout_path)
+            .map_err(io_error)?;
+
+        let spec = self
+            .exec_process_spec(endpoint, &request.command, true)
+            .await?;
+        let mut c = self.client.tasks();
+        let req = ExecProcessRequest {
+            container_id: cid.clone(),
+            exec_id: exec_id.to_string(),
+            stdin: stdin_path.to_string_lossy().into_owned(),
+            stdout: stdout_path.to_string_lossy().into_owned(),
+            terminal: true,
+            spec: Some(spec),
+            ..Default::default()
+        };
+        c.exec(with_namespace!(req, ns)).await?;
+
+        let result = async {
+            let req = StartRequest {
+                container_id: cid.clone(),
+                exec_id: exec_id.to_string(),
+            };
+            c.clone().start(with_namespace!(req, ns)).await?;
+            self.resize_exec(endpoint, exec_id, request.size).await?;
+
+            let req = WaitRequest {
+                container_id: cid.clone(),
+                exec_id: exec_id.to_string(),
+            };
+            let mut waiter = c.clone();
+            let wait = waiter.wait(with_namespace!(req, ns));
+            tokio::pin!(wait);
+            let mut buf = vec![0u8; 8192];
+            let mut attached = true;
+            let status = loop {
+                tokio::select! {
+                    resp = &mut wait => break resp?.into_inner().exit_status,
+                    data = session.stdin.recv(), if attached => match data {
+                        Some(data) => stdin.write_all(&data).await.map_err(io_error)?,
+                        None => {
+                            // 客户端已断开
+                            attached = false;
+                            self.kill_exec(endpoint, exec_id).await?;
+                        }
+                    },
+                    Some(size) = session.resize.recv() => {
+                        self.resize_exec(endpoint, exec_id, size).await?;
+                    }
+                    read = stdout.read(&mut buf) => {
+                        let n = read.map_err(io_error)?;
+                        let _ = session.output.send(Bytes::copy_from_slice(&buf[..n])).await;
+                    }
+                }
+            };
+            // 进程退出后 FIFO 中可能还有未读出的输出
+            while let Ok(n) = stdout.try_read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                let _ = session.output.send(Bytes::copy_from_slice(&buf[..n])).await;
+            }
+            Ok(status)
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = self.kill_exec(endpoint, exec_id).await;
+        }
+        let req = DeleteProcessRequest {
+            container_id: cid.clone(),
+            exec_id: exec_id.to_string(),
+        };
+        if let Err(e) = c.delete_process(with_namespace!(req, ns)).await {
+            log::warn!("Failed to delete exec process of {}: {}", endpoint, e);
+        }
+        result
+    }
+
+    async fn resize_exec(
+        &self,
+        endpoint: &Endpoint,
+        exec_id: &str,
+        size: TerminalSize,
+    ) -> Result<(), TaskError> {
+        let req = ResizePtyRequest {
+            container_id: endpoint.function_name.clone(),
+            exec_id: exec_id.to_string(),
+            width: size.cols as u32,
+            height: size.rows as u32,
+        };
+        self.client
+            .tasks()
+            .resize_pty(with_namespace!(req, &endpoint.namespace))
+            .await?;
+        Ok(())
+    }
+
+    async fn kill_exec(&self, endpoint: &Endpoint, exec_id: &str) -> Result<(), TaskError> {
+        let req = KillRequest {
+            container_id: endpoint.function_name.clone(),
+            exec_id: exec_id.to_string(),
+            signal: 9,
+            all: false,
+        };
+        self.client
+            .tasks()
+            .kill(with_namespace!(req, &endpoint.namespace))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fifo_relay() {
+        let dir = std::env::temp_dir().join(format!("faasrs-exec-{}", next_exec_id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stdout");
+        mkfifo(&path).unwrap();
+        assert!(mkfifo(&path).is_err());
+
+        let mut receiver = pipe::OpenOptions::new()
+            .read_write(true)
+            .open_receiver(&path)
+            .unwrap();
+        let mut sender = pipe::OpenOptions::new().open_sender(&path).unwrap();
+        sender.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        receiver.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,18 +1,61 @@
-use gateway::types::function;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use gateway::types::{function, limits::ProcessLimits, security::SecurityOptions};
 
 use crate::consts;
 
 use super::cni::Endpoint;
+use super::user::{IdMapping, ProcessUser};
+
+/// 绑定挂载进容器的卷目录
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct VolumeBind {
+    pub source: PathBuf,
+    pub destination: String,
+    pub read_only: bool,
+}
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct ContainerStaticMetadata {
+    /// Image reference the container runs, pinned to a digest once resolved
     pub image: String,
     pub endpoint: Endpoint,
+    /// Labels attached to the containerd container
+    pub labels: BTreeMap<String, String>,
+    /// 容器的主机名，由函数名与副本 ID 组成
+    pub hostname: Option<String>,
+    /// 以只读方式绑定挂载进容器的文件，键为容器内的路径
+    pub bind_files: BTreeMap<String, PathBuf>,
+    /// 挂载的卷
+    pub volumes: Vec<VolumeBind>,
+    /// 函数进程的用户
+    pub user: ProcessUser,
+    /// 开启用户命名空间时，容器内 ID 到宿主机 ID 的映射
+    pub userns: Option<IdMapping>,
+    /// 注解中的 capabilities 与 seccomp 配置
+    pub security: SecurityOptions,
+    /// 打开文件数、进程数与 /dev/shm 大小的限制
+    pub limits: ProcessLimits,
+    /// 运行时类，为空时使用默认类
+    pub runtime_class: String,
+    /// 函数快照所在的 snapshotter，部署时取启动时选择的 snapshotter
+    pub snapshotter: String,
 }
 
 impl From<function::Deployment> for ContainerStaticMetadata {
     fn from(info: function::Deployment) -> Self {
         ContainerStaticMetadata {
+            labels: BTreeMap::from([(consts::LABEL_IMAGE.to_string(), info.image.clone())]),
+            hostname: None,
+            bind_files: BTreeMap::new(),
+            volumes: Vec::new(),
+            user: ProcessUser::default(),
+            userns: None,
+            security: SecurityOptions::default(),
+            limits: ProcessLimits::default(),
+            runtime_class: String::new(),
+            snapshotter: String::new(),
             image: info.image,
             endpoint: Endpoint::new(
                 &info.function_name,
@@ -0,0 +1,545 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use containerd_client::{
+    services::v1::{
+        ApplyRequest, CreateImageRequest, CreateRequest, DeleteRequest, Image, Info, InfoRequest,
+        UpdateImageRequest, UpdateRequest as ContentUpdateRequest, WriteAction,
+        WriteContentRequest,
+        snapshots::{
+            CommitSnapshotRequest, PrepareSnapshotRequest, RemoveSnapshotRequest,
+            StatSnapshotRequest,
+        },
+    },
+    tonic::Request,
+    types::Descriptor,
+    with_namespace,
+};
+use futures::{
+    FutureExt,
+    future::{BoxFuture, Shared},
+};
+use gateway::types::operation::{OperationHandle, PullProgress};
+use oci_spec::image::{ImageConfiguration, ImageManifest};
+use prost_types::FieldMask;
+use sha2::Digest;
+use tokio::sync::watch;
+
+use super::{
+    ContainerdService, backend,
+    oci_image::{ImageError, RegistryCredentials, managed_labels, to_descriptor},
+    snapshot::chain_id,
+};
+use crate::consts::CACHE_NAMESPACE;
+
+/// 复制中断时租约的过期时间，过期后 containerd 回收复制了一半的内容
+const COPY_LEASE_EXPIRATION: chrono::TimeDelta = chrono::TimeDelta::hours(1);
+
+static COPY_SEQ: AtomicU64 = AtomicU64::new(0);
+
+type PullFuture = Shared<BoxFuture<'static, Result<(), ImageError>>>;
+
+/// 共享的拉取及其进度，等待同一拉取的部署都能看到进度
+pub(super) type InflightPulls = Mutex<HashMap<String, (PullFuture, watch::Receiver<PullProgress>)>>;
+
+/// 合并拉取的键：镜像引用加上凭据指纹，凭据不同的请求各自向仓库鉴权
+fn pull_key(image_name: &str, credentials: Option<&RegistryCredentials>) -> String {
+    let Some(credentials) = credentials else {
+        return image_name.to_string();
+    };
+    let mut hasher = sha2::Sha256::new();
+    for (host, cred) in credentials.iter().collect::<BTreeMap<_, _>>() {
+        hasher.update(host.as_bytes());
+        hasher.update([0]);
+        hasher.update(cred.username.as_bytes());
+        hasher.update([0]);
+        hasher.update(cred.token.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{}#{}", image_name, hex::encode(hasher.finalize()))
+}
+
+impl ContainerdService {
+    /// 拉取镜像到命名空间 `ns`。
+    ///
+    /// 镜像先拉取到缓存命名空间，已存在的 blob 不会重复下载，再从缓存复制到 `ns`，
+    /// 因此新的命名空间只需要解包。缓存拉取每次都会向仓库解析引用，凭据校验不会被跳过
+    pub async fn fetch_image(
+        &self,
+        image_name: &str,
+        ns: &str,
+        credentials: Option<&RegistryCredentials>,
+        operation: &OperationHandle,
+    ) -> Result<(), ImageError> {
+        let (pull, progress) = self.pull_to_cache(image_name, credentials);
+        report_progress(pull, progress, operation).await?;
+        if let Err(e) = self.copy_image(image_name, CACHE_NAMESPACE, ns).await {
+            log::warn!(
+                "Failed to copy image {} from cache, pulling directly: {}",
+                image_name,
+                e
+            );
+            let (tx, progress) = watch::channel(PullProgress::default());
+            let pull = self.pull_image(image_name, ns, credentials, true, Some(tx));
+            return report_progress(pull, progress, operation).await;
+        }
+        Ok(())
+    }
+
+    /// 查询镜像引用当前指向的 digest。
+    ///
+    /// 由 containerd 拉取到缓存命名空间而不解包，解析遵循其仓库配置，已有的 blob 不会重复下载，
+    /// 之后的部署从缓存复制即可
+    pub async fn resolve_digest(
+        &self,
+        image_name: &str,
+        credentials: Option<&RegistryCredentials>,
+        operation: &OperationHandle,
+    ) -> Result<String, ImageError> {
+        let (pull, progress) = self.pull_to_cache(image_name, credentials);
+        report_progress(pull, progress, operation).await?;
+        self.get_image(image_name, CACHE_NAMESPACE)
+            .await?
+            .and_then(|image| image.target)
+            .map(|target| target.digest)
+            .ok_or_else(|| ImageError::ImageNotFound(format!("{} in cache", image_name)))
+    }
+
+    /// 相同引用与凭据的并发拉取只会发起一次 transfer
+    fn pull_to_cache(
+        &self,
+        image_name: &str,
+        credentials: Option<&RegistryCredentials>,
+    ) -> (PullFuture, watch::Receiver<PullProgress>) {
+        let key = pull_key(image_name, credentials);
+        self.inflight_pulls
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_insert_with(|| {
+                let image_name = image_name.to_string();
+                let credentials = credentials.cloned();
+                let (tx, rx) = watch::channel(PullProgress::default());
+                let pull = async move {
+                    let result = backend()
+                        .pull_image(
+                            &image_name,
+                            CACHE_NAMESPACE,
+                            credentials.as_ref(),
+                            false,
+                            Some(tx),
+                        )
+                        .await;
+                    backend().inflight_pulls.lock().unwrap().remove(&key);
+                    result
+                };
+                (pull.boxed().shared(), rx)
+            })
+            .clone()
+    }
+
+    /// 在命名空间之间复制镜像。
+    ///
+    /// content store 中的 blob 由各命名空间共享，按 digest 写入已存在的 blob 时 containerd
+    /// 直接返回而不传输数据，因此新的命名空间只需登记 blob、创建镜像记录并解包。
+    /// `from` 与 `to` 相同时只在当前 snapshotter 中解包
+    pub async fn copy_image(
+        &self,
+        image_name: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<(), ImageError> {
+        let image = self
+            .get_image(image_name, from)
+            .await?
+            .ok_or_else(|| ImageError::ImageNotFound(format!("{} in {}", image_name, from)))?;
+        let target = image.target.ok_or_else(|| {
+            ImageError::ImageNotFound(format!("Image {} has no target", image_name))
+        })?;
+
+        // 复制过程中的 blob 与快照由租约保护，镜像记录创建之前不会被回收
+        let lease = self.create_lease(to).await?;
+        let result = self
+            .copy_leased(image_name, &target, from, to, &lease)
+            .await;
+        let req = DeleteRequest {
+            id: lease.clone(),
+            sync: false,
+        };
+        if let Err(e) = self.client.leases().delete(with_namespace!(req, to)).await {
+            log::warn!("Failed to delete lease {}: {}", lease, e);
+        }
+        result?;
+        log::trace!("Image {} copied from {} to {}", image_name, from, to);
+        Ok(())
+    }
+
+    async fn copy_leased(
+        &self,
+        image_name: &str,
+        target: &Descriptor,
+        from: &str,
+        to: &str,
+        lease: &str,
+    ) -> Result<(), ImageError> {
+        let (manifests, manifest) = self.platform_manifest(target, from).await?;
+        if from != to {
+            let blobs = manifests
+                .into_iter()
+                .chain(std::iter::once(to_descriptor(manifest.config())))
+                .chain(manifest.layers().iter().map(to_descriptor));
+            for blob in blobs {
+                self.share_blob(&blob, from, to, lease).await?;
+            }
+            self.put_image(image_name, target, to, lease).await?;
+        }
+        self.unpack(&manifest, to, lease).await
+    }
+
+    async fn create_lease(&self, ns: &str) -> Result<String, ImageError> {
+        let expire = chrono::Utc::now() + COPY_LEASE_EXPIRATION;
+        let req = CreateRequest {
+            id: format!("faasrs-copy-{}", unique_id()),
+            labels: HashMap::from([(
+                "containerd.io/gc.expire".to_string(),
+                expire.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            )]),
+        };
+        let lease = self
+            .client
+            .leases()
+            .create(with_namespace!(req, ns))
+            .await
+            .map_err(|e| ImageError::ImageImportFailed(format!("Failed to create lease: {}", e)))?
+            .into_inner()
+            .lease
+            .unwrap_or_default();
+        Ok(lease.id)
+    }
+
+    /// 以期望的 digest 打开写入，blob 已在其他命名空间中时 containerd 返回 `AlreadyExists`
+    /// 并把它登记到 `to`，之后复制 GC 引用等标签
+    async fn share_blob(
+        &self,
+        blob: &Descriptor,
+        from: &str,
+        to: &str,
+        lease: &str,
+    ) -> Result<(), ImageError> {
+        let failed = |e: tonic::Status| {
+            ImageError::ImageImportFailed(format!("Failed to share blob {}: {}", blob.digest, e))
+        };
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        tx.unbounded_send(WriteContentRequest {
+            action: WriteAction::Stat.into(),
+            r#ref: format!("faasrs-copy-{}", blob.digest),
+            total: blob.size,
+            expected: blob.digest.clone(),
+            ..Default::default()
+        })
+        .expect("receiver is alive");
+        match self.client.content().write(leased(rx, to, lease)).await {
+            Err(e) if e.code() == tonic::Code::AlreadyExists => {}
+            Err(e) => return Err(failed(e)),
+            Ok(_) => {
+                return Err(ImageError::ImageImportFailed(format!(
+                    "Blob {} is not shared between namespaces",
+                    blob.digest
+                )));
+            }
+        }
+        tx.close_channel();
+
+        let req = InfoRequest {
+            digest: blob.digest.clone(),
+        };
+        let info = self
+            .client
+            .content()
+            .info(with_namespace!(req, from))
+            .await
+            .map_err(failed)?
+            .into_inner()
+            .info
+            .unwrap_or_default();
+        if info.labels.is_empty() {
+            return Ok(());
+        }
+        self.update_content_labels(&blob.digest, info.labels, to, lease)
+            .await
+            .map_err(failed)
+    }
+
+    /// 只更新给出的标签，保留 blob 在 `ns` 中已有的其他标签
+    async fn update_content_labels(
+        &self,
+        digest: &str,
+        labels: HashMap<String, String>,
+        ns: &str,
+        lease: &str,
+    ) -> Result<(), tonic::Status> {
+        let paths = labels.keys().map(|key| format!("labels.{}", key)).collect();
+        let req = ContentUpdateRequest {
+            info: Some(Info {
+                digest: digest.to_string(),
+                labels,
+                ..Default::default()
+            }),
+            update_mask: Some(FieldMask { paths }),
+        };
+        self.client
+            .content()
+            .update(leased(req, ns, lease))
+            .await
+            .map(|_| ())
+    }
+
+    async fn put_image(
+        &self,
+        image_name: &str,
+        target: &Descriptor,
+        ns: &str,
+        lease: &str,
+    ) -> Result<(), ImageError> {
+        let image = Image {
+            name: image_name.to_string(),
+            labels: managed_labels(),
+            target: Some(target.clone()),
+            ..Default::default()
+        };
+        let req = CreateImageRequest {
+            image: Some(image.clone()),
+            ..Default::default()
+        };
+        let result = match self.client.images().create(leased(req, ns, lease)).await {
+            Err(e) if e.code() == tonic::Code::AlreadyExists => {
+                let req = UpdateImageRequest {
+                    image: Some(image),
+                    update_mask: Some(FieldMask {
+                        paths: vec![
+                            "target".to_string(),
+                            format!("labels.{}", crate::consts::LABEL_MANAGED),
+                        ],
+                    }),
+                    ..Default::default()
+                };
+                self.client
+                    .images()
+                    .update(leased(req, ns, lease))
+                    .await
+                    .map(|_| ())
+            }
+            result => result.map(|_| ()),
+        };
+        result.map_err(|e| {
+            log::error!("Failed to create image {} in {}: {}", image_name, ns, e);
+            ImageError::ImageImportFailed(e.message().to_string())
+        })
+    }
+
+    /// 按层在当前 snapshotter 中解包，已存在的层快照直接复用
+    async fn unpack(
+        &self,
+        manifest: &ImageManifest,
+        ns: &str,
+        lease: &str,
+    ) -> Result<(), ImageError> {
+        let config_desc = manifest.config();
+        let config: ImageConfiguration = serde_json::from_slice(
+            &self.read_content(config_desc.digest(), ns).await?,
+        )
+        .map_err(|e| ImageError::DeserializationFailed(format!("Failed to parse JSON: {}", e)))?;
+        let diff_ids = config.rootfs().diff_ids();
+        if diff_ids.len() != manifest.layers().len() {
+            return Err(ImageError::ImageConfigurationNotFound(format!(
+                "Image has {} layers but {} diff ids",
+                manifest.layers().len(),
+                diff_ids.len()
+            )));
+        }
+
+        let mut parent = String::new();
+        for (layer, diff_id) in manifest.layers().iter().zip(diff_ids) {
+            let chain = chain_id(&parent, diff_id);
+            let req = StatSnapshotRequest {
+                snapshotter: self.snapshotter.clone(),
+                key: chain.clone(),
+            };
+            match self.client.snapshots().stat(with_namespace!(req, ns)).await {
+                Ok(_) => {}
+                Err(e) if e.code() == tonic::Code::NotFound => {
+                    self.apply_layer(&to_descriptor(layer), diff_id, &parent, &chain, ns, lease)
+                        .await?;
+                }
+                Err(e) => {
+                    return Err(ImageError::ImageImportFailed(format!(
+                        "Failed to stat snapshot {}: {}",
+                        chain, e
+                    )));
+                }
+            }
+            parent = chain;
+        }
+
+        // 与 containerd 解包时一样由 config 引用顶层快照，镜像存在期间快照不会被回收
+        let labels = HashMap::from([(
+            format!("containerd.io/gc.ref.snapshot.{}", self.snapshotter),
+            parent,
+        )]);
+        self.update_content_labels(config_desc.digest(), labels, ns, lease)
+            .await
+            .map_err(|e| {
+                ImageError::ImageImportFailed(format!("Failed to label image config: {}", e))
+            })
+    }
+
+    async fn apply_layer(
+        &self,
+        layer: &Descriptor,
+        diff_id: &str,
+        parent: &str,
+        chain: &str,
+        ns: &str,
+        lease: &str,
+    ) -> Result<(), ImageError> {
+        let key = format!("extract-{}-{}", unique_id(), chain);
+        let req = PrepareSnapshotRequest {
+            snapshotter: self.snapshotter.clone(),
+            key: key.clone(),
+            parent: parent.to_string(),
+            ..Default::default()
+        };
+        let mounts = self
+            .client
+            .snapshots()
+            .prepare(leased(req, ns, lease))
+            .await
+            .map_err(|e| {
+                ImageError::ImageImportFailed(format!("Failed to prepare snapshot: {}", e))
+            })?
+            .into_inner()
+            .mounts;
+
+        let result = async {
+            let req = ApplyRequest {
+                diff: Some(layer.clone()),
+                mounts,
+                ..Default::default()
+            };
+            let applied = self
+                .client
+                .diff()
+                .apply(leased(req, ns, lease))
+                .await
+                .map_err(|e| format!("Failed to apply layer {}: {}", layer.digest, e))?
+                .into_inner()
+                .applied
+                .unwrap_or_default();
+            if applied.digest != diff_id {
+                return Err(format!(
+                    "Layer {} unpacked to {}, expected {}",
+                    layer.digest, applied.digest, diff_id
+                ));
+            }
+            let req = CommitSnapshotRequest {
+                snapshotter: self.snapshotter.clone(),
+                name: chain.to_string(),
+                key: key.clone(),
+                ..Default::default()
+            };
+            match self.client.snapshots().commit(leased(req, ns, lease)).await {
+                Ok(_) => Ok(true),
+                // 并发解包的另一方已提交同一层
+                Err(e) if e.code() == tonic::Code::AlreadyExists => Ok(false),
+                Err(e) => Err(format!("Failed to commit snapshot {}: {}", chain, e)),
+            }
+        }
+        .await;
+
+        if !matches!(result, Ok(true)) {
+            let req = RemoveSnapshotRequest {
+                snapshotter: self.snapshotter.clone(),
+                key,
+            };
+            if let Err(e) = self
+                .client
+                .snapshots()
+                .remove(with_namespace!(req, ns))
+                .await
+            {
+                log::warn!("Failed to remove snapshot of layer {}: {}", layer.digest, e);
+            }
+        }
+        result.map(|_| ()).map_err(ImageError::ImageImportFailed)
+    }
+}
+
+/// 本进程内唯一的标识，用于租约与解包时的临时快照
+fn unique_id() -> String {
+    format!(
+        "{}-{}",
+        std::process::id(),
+        COPY_SEQ.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// 带上租约的请求，创建的内容在租约删除前不会被回收
+fn leased<T>(message: T, ns: &str, lease: &str) -> Request<T> {
+    let mut req = with_namespace!(message, ns);
+    req.metadata_mut()
+        .insert("containerd-lease", lease.parse().unwrap());
+    req
+}
+
+/// 等待拉取完成，期间把下载进度同步到操作记录
+async fn report_progress(
+    pull: impl Future<Output = Result<(), ImageError>>,
+    mut progress: watch::Receiver<PullProgress>,
+    operation: &OperationHandle,
+) -> Result<(), ImageError> {
+    let mut pull = std::pin::pin!(pull);
+    loop {
+        tokio::select! {
+            result = &mut pull => return result,
+            changed = progress.changed() => match changed {
+                Ok(()) => operation.set_progress(*progress.borrow_and_update()),
+                Err(_) => return pull.await,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gateway::types::registry::RegistryCredential;
+
+    use super::*;
+
+    #[test]
+    fn test_pull_key() {
+        let creds = |token: &str| {
+            RegistryCredentials::from([(
+                "docker.io".to_string(),
+                RegistryCredential {
+                    username: "bot".to_string(),
+                    token: token.to_string(),
+                },
+            )])
+        };
+        assert_eq!(pull_key("alpine:3", None), "alpine:3");
+        assert_eq!(
+            pull_key("alpine:3", Some(&creds("a"))),
+            pull_key("alpine:3", Some(&creds("a")))
+        );
+        assert_ne!(
+            pull_key("alpine:3", Some(&creds("a"))),
+            pull_key("alpine:3", Some(&creds("b")))
+        );
+        assert_ne!(pull_key("alpine:3", Some(&creds("a"))), "alpine:3");
+    }
+}
@@ -1,12 +1,20 @@
 pub mod cni;
 pub mod container;
 pub mod error;
+pub mod exec;
 pub mod function;
+pub mod image_cache;
 pub mod namespace;
 pub mod oci_image;
+pub mod probe;
+pub mod runtime_class;
+pub mod seccomp;
 pub mod snapshot;
+pub mod snapshotter;
 pub mod spec;
+pub mod stream;
 pub mod task;
+pub mod user;
 
 use std::sync::OnceLock;
 
@@ -16,16 +24,35 @@ pub(crate) fn backend() -> &'static ContainerdService {
     __BACKEND.get().unwrap()
 }
 
-/// TODO: Panic on failure, should be handled in a better way
-pub async fn init_backend() {
+/// 连接 containerd 并初始化函数网络，配置无效或依赖不可用时返回可读的错误
+pub async fn init_backend() -> Result<(), String> {
     let socket =
         std::env::var("SOCKET_PATH").unwrap_or(crate::consts::DEFAULT_CTRD_SOCK.to_string());
-    let client = containerd_client::Client::from_path(socket).await.unwrap();
+    let client = containerd_client::Client::from_path(&socket)
+        .await
+        .map_err(|e| format!("failed to connect to containerd at {}: {}", socket, e))?;
+    let snapshotter = snapshotter::select_snapshotter(&client)
+        .await
+        .map_err(|e| format!("no usable snapshotter: {}", e))?;
+    log::info!("Using snapshotter {}", snapshotter);
 
-    __BACKEND.set(ContainerdService { client }).ok().unwrap();
-    cni::init_cni_network().unwrap();
+    __BACKEND
+        .set(ContainerdService {
+            client,
+            inflight_pulls: Default::default(),
+            snapshotter,
+        })
+        .ok()
+        .unwrap();
+    cni::init_cni_network().map_err(|e| format!("failed to initialize CNI network: {}", e))?;
+    std::sync::LazyLock::force(&runtime_class::RUNTIME_CLASSES);
+    Ok(())
 }
 
 pub struct ContainerdService {
     pub client: containerd_client::Client,
+    /// 正在进行的拉取，相同的镜像引用与凭据共享同一次拉取
+    inflight_pulls: image_cache::InflightPulls,
+    /// 启动时选择的 snapshotter，新部署的函数与镜像解包使用它
+    pub snapshotter: String,
 }
@@ -1,64 +1,122 @@
-use super::ContainerdService;
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use super::{ContainerdService, stream::ContainerdStream};
 
 use container_image_dist_ref::ImgRef;
 use containerd_client::{
-    services::v1::{GetImageRequest, ReadContentRequest, TransferOptions, TransferRequest},
+    services::v1::{
+        CreateImageRequest, DeleteImageRequest, GetImageRequest, Image, ListImagesRequest,
+        ReadContentRequest, TransferOptions, TransferRequest,
+    },
     to_any,
     tonic::Request,
     types::{
-        Platform,
-        transfer::{ImageStore, OciRegistry, UnpackConfiguration},
+        Descriptor, Platform,
+        transfer::{
+            AuthRequest, AuthResponse, AuthType, ImageImportStream, ImageReference, ImageStore,
+            OciRegistry, Progress, RegistryResolver, UnpackConfiguration,
+        },
     },
     with_namespace,
 };
+use gateway::types::{
+    image::PullPolicy,
+    operation::{OperationHandle, Phase, PullProgress},
+    registry::{RegistryCredential, normalize_registry_host},
+};
 use oci_spec::image::{Arch, ImageConfiguration, ImageIndex, ImageManifest, MediaType, Os};
+use prost::Message;
+use tokio::sync::watch;
+
+/// Registry credentials keyed by normalized registry host
+pub type RegistryCredentials = HashMap<String, RegistryCredential>;
+
+/// 归档中没有名称的镜像以 `import-<日期>` 为前缀命名
+const IMPORT_PREFIX: &str = "import";
+/// 导入结束后等待进度流送完剩余事件的时间
+const PROGRESS_DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
 
 impl ContainerdService {
-    async fn get_image(&self, image_name: &str, ns: &str) -> Result<(), ImageError> {
+    /// 查询本地镜像，不存在时返回 `None`
+    pub(super) async fn get_image(
+        &self,
+        image_name: &str,
+        ns: &str,
+    ) -> Result<Option<Image>, ImageError> {
         let mut c = self.client.images();
         let req = GetImageRequest {
             name: image_name.to_string(),
         };
 
-        let resp = match c.get(with_namespace!(req, ns)).await {
-            Ok(response) => response.into_inner(),
-            Err(e) => {
-                return Err(ImageError::ImageNotFound(format!(
-                    "Failed to get image {}: {}",
-                    image_name, e
-                )));
-            }
-        };
-        if resp.image.is_none() {
-            self.pull_image(image_name, ns).await?;
+        match c.get(with_namespace!(req, ns)).await {
+            Ok(response) => Ok(response.into_inner().image),
+            Err(e) if e.code() == tonic::Code::NotFound => Ok(None),
+            Err(e) => Err(ImageError::ImageNotFound(format!(
+                "Failed to get image {}: {}",
+                image_name, e
+            ))),
         }
-        Ok(())
     }
 
-    pub async fn pull_image(&self, image_name: &str, ns: &str) -> Result<(), ImageError> {
+    pub async fn pull_image(
+        &self,
+        image_name: &str,
+        ns: &str,
+        credentials: Option<&RegistryCredentials>,
+        unpack: bool,
+        progress: Option<watch::Sender<PullProgress>>,
+    ) -> Result<(), ImageError> {
         let ns = check_namespace(ns);
         let namespace = ns.as_str();
 
+        // 下载进度由 containerd 通过 progress stream 推送
+        let progress = match progress {
+            Some(tx) => {
+                let stream = self.open_stream(namespace).await.map_err(|e| {
+                    log::error!("Failed to open progress stream: {}", e);
+                    ImageError::ImagePullFailed(format!("Failed to open progress stream: {}", e))
+                })?;
+                let stream_id = stream.id.clone();
+                let handle = tokio::spawn(serve_progress_stream(stream, tx));
+                Some((stream_id, scopeguard::guard(handle, |h| h.abort())))
+            }
+            None => None,
+        };
+
+        // 有凭据时通过 auth stream 回应 containerd 的鉴权请求
+        let auth = match credentials {
+            Some(creds) => {
+                let stream = self.open_stream(namespace).await.map_err(|e| {
+                    log::error!("Failed to open auth stream: {}", e);
+                    ImageError::ImagePullFailed(format!("Failed to open auth stream: {}", e))
+                })?;
+                let stream_id = stream.id.clone();
+                let handle = tokio::spawn(serve_auth_stream(stream, creds.clone()));
+                Some((stream_id, scopeguard::guard(handle, |h| h.abort())))
+            }
+            None => None,
+        };
+
         let mut trans_cli = self.client.transfer();
         let source = OciRegistry {
             reference: image_name.to_string(),
-            resolver: Default::default(),
-        };
-
-        // 这里先写死linux amd64
-        let platform = Platform {
-            os: "linux".to_string(),
-            architecture: "amd64".to_string(),
-            ..Default::default()
+            resolver: auth.as_ref().map(|(stream_id, _)| RegistryResolver {
+                auth_stream: stream_id.clone(),
+                ..Default::default()
+            }),
         };
 
         let dest = ImageStore {
             name: image_name.to_string(),
-            platforms: vec![platform.clone()],
-            unpacks: vec![UnpackConfiguration {
-                platform: Some(platform),
-                ..Default::default()
-            }],
+            labels: managed_labels(),
+            platforms: vec![default_platform()],
+            unpacks: if unpack {
+                vec![unpack_configuration(&self.snapshotter)]
+            } else {
+                Vec::new()
+            },
             ..Default::default()
         };
 
@@ -69,7 +127,10 @@ impl ContainerdService {
             source: Some(anys),
             destination: Some(anyd),
             options: Some(TransferOptions {
-                ..Default::default()
+                progress_stream: progress
+                    .as_ref()
+                    .map(|(stream_id, _)| stream_id.clone())
+                    .unwrap_or_default(),
             }),
         };
 
@@ -78,30 +139,282 @@ impl ContainerdService {
             .await
             .map_err(|e| {
                 log::error!("Failed to pull image: {}", e);
-                ImageError::ImagePullFailed(format!("Failed to pull image {}: {}", image_name, e))
+                if is_auth_failure(e.message()) {
+                    ImageError::Unauthorized(format!(
+                        "Failed to pull image {}: {}",
+                        image_name,
+                        e.message()
+                    ))
+                } else {
+                    ImageError::ImagePullFailed(format!(
+                        "Failed to pull image {}: {}",
+                        image_name, e
+                    ))
+                }
             })
             .map(|resp| {
                 log::trace!("Pull image response: {:?}", resp);
             })
     }
 
-    pub async fn prepare_image(
+    /// 将 OCI layout 或 `docker save` 格式的归档导入 content store 并解包，
+    /// 镜像名取自归档中的注解
+    pub async fn import_image(
+        &self,
+        ns: &str,
+        archive: tokio::sync::mpsc::Receiver<Bytes>,
+    ) -> Result<Vec<String>, ImageError> {
+        let prefix = format!(
+            "{}-{}",
+            IMPORT_PREFIX,
+            chrono::Utc::now().format("%Y-%m-%d")
+        );
+        self.import_into(ns, archive, import_destination(&self.snapshotter, &prefix))
+            .await
+    }
+
+    /// 从字节流导入镜像到 `dest`，返回创建或更新的镜像名
+    pub(super) async fn import_into(
+        &self,
+        ns: &str,
+        archive: tokio::sync::mpsc::Receiver<Bytes>,
+        dest: ImageStore,
+    ) -> Result<Vec<String>, ImageError> {
+        let ns = check_namespace(ns);
+        let namespace = ns.as_str();
+
+        let open_err = |e: tonic::Status| {
+            log::error!("Failed to open import stream: {}", e);
+            ImageError::ImageImportFailed(format!("Failed to open import stream: {}", e))
+        };
+        let stream = self.open_stream(namespace).await.map_err(open_err)?;
+        let progress = self.open_stream(namespace).await.map_err(open_err)?;
+        let req = import_request(&stream.id, &progress.id, &dest);
+        let sender = scopeguard::guard(tokio::spawn(stream.send_bytes(archive)), |h| h.abort());
+        let (saved_tx, mut saved) = tokio::sync::mpsc::unbounded_channel();
+        let collector = tokio::spawn(collect_saved_images(progress, saved_tx));
+
+        let mut trans_cli = self.client.transfer();
+        trans_cli
+            .transfer(with_namespace!(req, namespace))
+            .await
+            .map_err(|e| {
+                log::error!("Failed to import image archive: {}", e);
+                ImageError::ImageImportFailed(e.message().to_string())
+            })?;
+
+        match scopeguard::ScopeGuard::into_inner(sender).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(ImageError::ImageImportFailed(e.message().to_string())),
+            Err(e) => return Err(ImageError::ImageImportFailed(e.to_string())),
+        }
+        // 传输结束时事件已经发出，等待进度流关闭以免漏掉最后几条
+        if tokio::time::timeout(PROGRESS_DRAIN_TIMEOUT, collector)
+            .await
+            .is_err()
+        {
+            log::warn!("Import progress stream was not closed in time");
+        }
+        let mut names = Vec::new();
+        while let Ok(name) = saved.try_recv() {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+
+    /// 列出命名空间中的全部镜像
+    pub async fn list_images(&self, ns: &str) -> Result<Vec<Image>, ImageError> {
+        let mut c = self.client.images();
+        let req = ListImagesRequest {
+            ..Default::default()
+        };
+        c.list(with_namespace!(req, ns))
+            .await
+            .map(|resp| resp.into_inner().images)
+            .map_err(|e| {
+                log::error!("Failed to list images: {}", e);
+                ImageError::ListImagesFailed(e.message().to_string())
+            })
+    }
+
+    /// 删除镜像记录，`sync` 为真时等待 containerd 完成对 content 与 snapshot 的回收
+    pub async fn delete_image(
         &self,
         image_name: &str,
         ns: &str,
-        always_pull: bool,
+        sync: bool,
     ) -> Result<(), ImageError> {
+        let mut c = self.client.images();
+        let req = DeleteImageRequest {
+            name: image_name.to_string(),
+            sync,
+            ..Default::default()
+        };
+        match c.delete(with_namespace!(req, ns)).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.code() == tonic::Code::NotFound => Ok(()),
+            Err(e) => {
+                log::error!("Failed to delete image {}: {}", image_name, e);
+                Err(ImageError::DeleteImageFailed(e.message().to_string()))
+            }
+        }
+    }
+
+    /// 镜像在本平台下占用的大小：manifest、config 与全部层的大小之和
+    pub async fn image_size(&self, target: &Descriptor, ns: &str) -> Result<i64, ImageError> {
+        let (manifests, manifest) = self.platform_manifest(target, ns).await?;
+        let size = manifests.iter().map(|desc| desc.size).sum::<i64>()
+            + manifest.config().size()
+            + manifest
+                .layers()
+                .iter()
+                .map(|layer| layer.size())
+                .sum::<i64>();
+        Ok(size)
+    }
+
+    /// 解析镜像在本平台下的 manifest，同时返回途经的索引与 manifest 的描述符
+    pub(super) async fn platform_manifest(
+        &self,
+        target: &Descriptor,
+        ns: &str,
+    ) -> Result<(Vec<Descriptor>, ImageManifest), ImageError> {
+        let mut manifests = vec![target.clone()];
+        let mut data = self.read_content(&target.digest, ns).await?;
+
+        if is_index(&target.media_type) {
+            let index: ImageIndex = serde_json::from_slice(&data).map_err(|e| {
+                ImageError::DeserializationFailed(format!("Failed to parse JSON: {}", e))
+            })?;
+            let manifest = index
+                .manifests()
+                .iter()
+                .find(|m| m.platform().as_ref().is_some_and(is_default_platform))
+                .ok_or_else(|| {
+                    ImageError::ImageConfigurationNotFound(
+                        "No manifest found for current platform".to_string(),
+                    )
+                })?;
+            data = self.read_content(manifest.digest(), ns).await?;
+            manifests.push(to_descriptor(manifest));
+        }
+
+        let manifest: ImageManifest = serde_json::from_slice(&data).map_err(|e| {
+            ImageError::DeserializationFailed(format!("Failed to parse JSON: {}", e))
+        })?;
+        Ok((manifests, manifest))
+    }
+
+    /// 读取完整的 blob，content 服务可能分多次返回
+    pub(super) async fn read_content(&self, digest: &str, ns: &str) -> Result<Vec<u8>, ImageError> {
+        let req = ReadContentRequest {
+            digest: digest.to_string(),
+            ..Default::default()
+        };
+        let mut c = self.client.content();
+        let mut inner = c
+            .read(with_namespace!(req, ns))
+            .await
+            .map_err(|e| {
+                ImageError::ReadContentFailed(format!("Failed to read content {}: {}", digest, e))
+            })?
+            .into_inner();
+
+        let mut data = Vec::new();
+        while let Some(resp) = inner.message().await.map_err(|e| {
+            ImageError::ReadContentFailed(format!("Failed to read content {}: {}", digest, e))
+        })? {
+            data.extend_from_slice(&resp.data);
+        }
+        Ok(data)
+    }
+
+    /// 按拉取策略准备镜像，返回镜像当前指向的 manifest 描述符
+    pub async fn prepare_image(
+        &self,
+        image_name: &str,
+        ns: &str,
+        policy: PullPolicy,
+        credentials: Option<&RegistryCredentials>,
+        operation: &OperationHandle,
+    ) -> Result<Descriptor, ImageError> {
         let _ = ImgRef::new(image_name).map_err(|e| {
             ImageError::ImageNotFound(format!("Invalid image name: {:?}", e.kind()))
         })?;
-        if always_pull {
-            self.pull_image(image_name, ns).await
-        } else {
-            let namespace = check_namespace(ns);
-            let namespace = namespace.as_str();
+        let namespace = check_namespace(ns);
+        let namespace = namespace.as_str();
 
-            self.get_image(image_name, namespace).await
+        let image = match policy {
+            PullPolicy::Always => None,
+            PullPolicy::IfNotPresent | PullPolicy::Never => {
+                self.get_image(image_name, namespace).await?
+            }
+        };
+        let image = match image {
+            Some(image) => image,
+            None if policy == PullPolicy::Never => {
+                return Err(ImageError::ImageNotFound(format!(
+                    "image {} is not present and pull policy is Never",
+                    image_name
+                )));
+            }
+            None => {
+                operation.set_phase(Phase::Pulling);
+                self.fetch_image(image_name, namespace, credentials, operation)
+                    .await?;
+                self.get_image(image_name, namespace)
+                    .await?
+                    .ok_or_else(|| {
+                        ImageError::ImageNotFound(format!(
+                            "image {} not found after pull",
+                            image_name
+                        ))
+                    })?
+            }
+        };
+
+        image.target.ok_or_else(|| {
+            ImageError::ImageConfigurationNotFound(format!("image {} has no target", image_name))
+        })
+    }
+
+    /// 为镜像创建 `name@digest` 形式的引用，使同一版本的函数不受 tag 移动的影响
+    pub async fn pin_image(
+        &self,
+        image_name: &str,
+        target: Descriptor,
+        ns: &str,
+    ) -> Result<String, ImageError> {
+        let pinned = pinned_reference(image_name, &target.digest)?;
+        if pinned == image_name {
+            return Ok(pinned);
         }
+
+        let mut c = self.client.images();
+        let req = CreateImageRequest {
+            image: Some(Image {
+                name: pinned.clone(),
+                labels: managed_labels(),
+                target: Some(target),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        match c.create(with_namespace!(req, ns)).await {
+            Ok(_) => {}
+            Err(e) if e.code() == tonic::Code::AlreadyExists => {}
+            Err(e) => {
+                log::error!("Failed to pin image {}: {}", image_name, e);
+                return Err(ImageError::ImagePullFailed(format!(
+                    "Failed to pin image {}: {}",
+                    image_name, e
+                )));
+            }
+        }
+        log::debug!("Image {} pinned as {}", image_name, pinned);
+        Ok(pinned)
     }
 
     pub async fn image_config(
@@ -275,10 +588,14 @@ impl ContainerdService {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ImageError {
     ImageNotFound(String),
     ImagePullFailed(String),
+    Unauthorized(String),
+    ImageImportFailed(String),
+    ListImagesFailed(String),
+    DeleteImageFailed(String),
     ImageConfigurationNotFound(String),
     ReadContentFailed(String),
     UnexpectedMediaType,
@@ -292,6 +609,10 @@ impl std::fmt::Display for ImageError {
         match self {
             ImageError::ImageNotFound(msg) => write!(f, "Image not found: {}", msg),
             ImageError::ImagePullFailed(msg) => write!(f, "Image pull failed: {}", msg),
+            ImageError::Unauthorized(msg) => write!(f, "Registry authentication failed: {}", msg),
+            ImageError::ImageImportFailed(msg) => write!(f, "Image import failed: {}", msg),
+            ImageError::ListImagesFailed(msg) => write!(f, "List images failed: {}", msg),
+            ImageError::DeleteImageFailed(msg) => write!(f, "Delete image failed: {}", msg),
             ImageError::ImageConfigurationNotFound(msg) => {
                 write!(f, "Image configuration not found: {}", msg)
             }
@@ -307,9 +628,168 @@ impl std::fmt::Display for ImageError {
     }
 }
 
-// 不用这个也能拉取镜像？
-pub fn get_resolver() {
-    todo!()
+/// 回应 transfer 服务在 auth stream 上发来的鉴权请求，直到流被关闭
+async fn serve_auth_stream(mut stream: ContainerdStream, credentials: RegistryCredentials) {
+    while let Ok(Some(msg)) = stream.recv().await {
+        let req = match AuthRequest::decode(msg.value.as_slice()) {
+            Ok(req) => req,
+            Err(e) => {
+                log::warn!("Unexpected message on auth stream {}: {}", stream.id, e);
+                continue;
+            }
+        };
+        let resp = match credentials.get(&normalize_registry_host(&req.host)) {
+            Some(cred) => {
+                log::debug!("Providing credentials for registry {}", req.host);
+                AuthResponse {
+                    auth_type: AuthType::Credentials.into(),
+                    username: cred.username.clone(),
+                    secret: cred.token.clone(),
+                    ..Default::default()
+                }
+            }
+            None => {
+                log::debug!("No credentials for registry {}", req.host);
+                AuthResponse {
+                    auth_type: AuthType::None.into(),
+                    ..Default::default()
+                }
+            }
+        };
+        if let Err(e) = stream.send(to_any(&resp)).await {
+            log::warn!("Failed to answer auth request: {}", e);
+            break;
+        }
+    }
+}
+
+/// 汇总各个 blob 的下载进度
+async fn serve_progress_stream(
+    mut stream: ContainerdStream,
+    progress: watch::Sender<PullProgress>,
+) {
+    let mut blobs: HashMap<String, (i64, i64)> = HashMap::new();
+    while let Ok(Some(msg)) = stream.recv().await {
+        let Ok(update) = Progress::decode(msg.value.as_slice()) else {
+            continue;
+        };
+        // resolving 等事件不携带大小
+        if update.total == 0 {
+            continue;
+        }
+        blobs.insert(update.name, (update.progress, update.total));
+        let (bytes, total) = blobs
+            .values()
+            .fold((0, 0), |(bytes, total), (b, t)| (bytes + b, total + t));
+        progress.send_replace(PullProgress { bytes, total });
+    }
+}
+
+/// 收集导入过程中保存的镜像名，containerd 为每个镜像发出一条 `saved` 事件
+async fn collect_saved_images(
+    mut stream: ContainerdStream,
+    saved: tokio::sync::mpsc::UnboundedSender<String>,
+) {
+    while let Ok(Some(msg)) = stream.recv().await {
+        if let Ok(update) = Progress::decode(msg.value.as_slice())
+            && update.event == "saved"
+            && saved.send(update.name).is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// 镜像以归档中记录的名称命名，没有名称的以 `prefix` 加 tag 命名，
+/// 同时添加 `prefix@digest` 的名称，与 `ctr import` 的行为一致
+fn import_destination(snapshotter: &str, prefix: &str) -> ImageStore {
+    let reference = ImageReference {
+        name: prefix.to_string(),
+        is_prefix: true,
+        allow_overwrite: true,
+        ..Default::default()
+    };
+    ImageStore {
+        labels: managed_labels(),
+        platforms: vec![default_platform()],
+        extra_references: vec![
+            reference.clone(),
+            ImageReference {
+                add_digest: true,
+                skip_named_digest: true,
+                ..reference
+            },
+        ],
+        unpacks: vec![unpack_configuration(snapshotter)],
+        ..Default::default()
+    }
+}
+
+fn import_request(stream: &str, progress: &str, dest: &ImageStore) -> TransferRequest {
+    let source = ImageImportStream {
+        stream: stream.to_string(),
+        ..Default::default()
+    };
+    TransferRequest {
+        source: Some(to_any(&source)),
+        destination: Some(to_any(dest)),
+        options: Some(TransferOptions {
+            progress_stream: progress.to_string(),
+        }),
+    }
+}
+
+fn is_auth_failure(msg: &str) -> bool {
+    let msg = msg.to_lowercase();
+    ["unauthorized", "403 forbidden", "authorization failed"]
+        .iter()
+        .any(|pattern| msg.contains(pattern))
+}
+
+// 这里先写死linux amd64
+pub(super) fn default_platform() -> Platform {
+    Platform {
+        os: "linux".to_string(),
+        architecture: "amd64".to_string(),
+        ..Default::default()
+    }
+}
+
+fn is_default_platform(p: &oci_spec::image::Platform) -> bool {
+    matches!(p.architecture(), &Arch::Amd64) && matches!(p.os(), &Os::Linux)
+}
+
+pub(super) fn to_descriptor(desc: &oci_spec::image::Descriptor) -> Descriptor {
+    Descriptor {
+        media_type: desc.media_type().to_string(),
+        digest: desc.digest().to_string(),
+        size: desc.size(),
+        annotations: desc.annotations().clone().unwrap_or_default(),
+    }
+}
+
+fn is_index(media_type: &str) -> bool {
+    matches!(MediaType::from(media_type), MediaType::ImageIndex)
+        || media_type == "application/vnd.docker.distribution.manifest.list.v2+json"
+}
+
+/// 由 faasrs 拉取或导入的镜像都带有该标签，镜像回收只会处理这些镜像
+pub(super) fn managed_labels() -> HashMap<String, String> {
+    HashMap::from([(crate::consts::LABEL_MANAGED.to_string(), "true".to_string())])
+}
+
+pub(super) fn unpack_configuration(snapshotter: &str) -> UnpackConfiguration {
+    UnpackConfiguration {
+        platform: Some(default_platform()),
+        snapshotter: snapshotter.to_string(),
+    }
+}
+
+/// `repo:tag` -> `repo@digest`
+fn pinned_reference(image_name: &str, digest: &str) -> Result<String, ImageError> {
+    let img_ref = ImgRef::new(image_name)
+        .map_err(|e| ImageError::ImageNotFound(format!("Invalid image name: {:?}", e.kind())))?;
+    Ok(format!("{}@{}", img_ref.name().to_str(), digest))
 }
 
 fn check_namespace(ns: &str) -> String {
@@ -318,3 +798,51 @@ fn check_namespace(ns: &str) -> String {
         _ => ns.to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_request() {
+        let req = import_request(
+            "stream-1",
+            "progress-1",
+            &import_destination("overlayfs", "import-2025-01-01"),
+        );
+        let source = ImageImportStream::decode(req.source.unwrap().value.as_slice()).unwrap();
+        assert_eq!(source.stream, "stream-1");
+        assert_eq!(req.options.unwrap().progress_stream, "progress-1");
+
+        let dest = ImageStore::decode(req.destination.unwrap().value.as_slice()).unwrap();
+        assert_eq!(dest.name, "");
+        assert_eq!(dest.unpacks[0].snapshotter, "overlayfs");
+        assert_eq!(
+            dest.labels
+                .get(crate::consts::LABEL_MANAGED)
+                .map(String::as_str),
+            Some("true")
+        );
+        let refs = &dest.extra_references;
+        assert_eq!(refs.len(), 2);
+        assert!(
+            refs.iter()
+                .all(|r| r.name == "import-2025-01-01" && r.is_prefix)
+        );
+        assert!(!refs[0].add_digest);
+        assert!(refs[1].add_digest && refs[1].skip_named_digest);
+    }
+
+    #[test]
+    fn test_pinned_reference() {
+        let digest = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        assert_eq!(
+            pinned_reference("hub.scutosc.cn/dolzhuying/echo:latest", digest).unwrap(),
+            format!("hub.scutosc.cn/dolzhuying/echo@{}", digest)
+        );
+        assert_eq!(
+            pinned_reference("localhost:5000/echo", digest).unwrap(),
+            format!("localhost:5000/echo@{}", digest)
+        );
+    }
+}
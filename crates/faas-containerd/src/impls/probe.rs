@@ -0,0 +1,83 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use gateway::types::probe::{ProbeHandler, ReadinessProbe};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use super::{backend, cni::Endpoint};
+
+/// 执行一次就绪探测，超时视为失败
+pub async fn probe_once(endpoint: &Endpoint, addr: IpAddr, probe: &ReadinessProbe) -> bool {
+    let timeout = Duration::from_secs(probe.timeout_seconds);
+    let result = match &probe.handler {
+        ProbeHandler::Http { path, port } => {
+            tokio::time::timeout(timeout, http_probe(SocketAddr::new(addr, *port), path)).await
+        }
+        ProbeHandler::Tcp { port } => {
+            tokio::time::timeout(timeout, async move {
+                TcpStream::connect(SocketAddr::new(addr, *port))
+                    .await
+                    .is_ok()
+            })
+            .await
+        }
+        ProbeHandler::Exec { command } => {
+            return match backend().exec_and_wait(endpoint, command, timeout).await {
+                Ok(code) => code == 0,
+                Err(e) => {
+                    log::debug!("Exec probe of {} failed: {}", endpoint, e);
+                    false
+                }
+            };
+        }
+    };
+    result.unwrap_or(false)
+}
+
+/// 发送一个 HTTP/1.0 请求，只关心状态行
+async fn http_probe(addr: SocketAddr, path: &str) -> bool {
+    let Ok(mut stream) = TcpStream::connect(addr).await else {
+        return false;
+    };
+    let req = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: faasrs-probe\r\n\r\n",
+        path, addr
+    );
+    if stream.write_all(req.as_bytes()).await.is_err() {
+        return false;
+    }
+    let mut buf = [0u8; 32];
+    let mut len = 0;
+    while len < buf.len() {
+        match stream.read(&mut buf[len..]).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => len += n,
+        }
+    }
+    status_code(&buf[..len]).is_some_and(|code| (200..400).contains(&code))
+}
+
+fn status_code(status_line: &[u8]) -> Option<u16> {
+    let line = std::str::from_utf8(status_line).ok()?;
+    let mut parts = line.split_whitespace();
+    parts.next().filter(|v| v.starts_with("HTTP/"))?;
+    parts.next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::status_code;
+
+    #[test]
+    fn test_status_code() {
+        assert_eq!(status_code(b"HTTP/1.1 200 OK\r\nContent-Len"), Some(200));
+        assert_eq!(status_code(b"HTTP/1.0 503 Service Unavai"), Some(503));
+        assert_eq!(status_code(b"SSH-2.0-OpenSSH"), None);
+        assert_eq!(status_code(b""), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! 运行时类：管理员在配置文件中登记的 containerd 运行时，函数通过注解选择
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::LazyLock;
+
+use containerd_client::services::v1::container::Runtime;
+use prost::Message;
+use serde::Deserialize;
+
+/// 未提供配置文件时唯一的运行时类
+const BUILTIN_CLASS: &str = "runc";
+const RUNC_RUNTIME_TYPE: &str = "io.containerd.runc.v2";
+const RUNC_OPTIONS_TYPE_URL: &str = "containerd.runc.v1.Options";
+const DEFAULT_CONFIG_PATH: &str = "/etc/faasrs/runtime-classes.json";
+
+/// runc 兼容的运行时 (runc、crun、youki) 的选项
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RuncOptions {
+    /// 代替 runc 的可执行文件，如 `crun`
+    #[serde(default)]
+    pub binary_name: String,
+    /// 运行时保存容器状态的目录
+    #[serde(default)]
+    pub root: String,
+    #[serde(default)]
+    pub systemd_cgroup: bool,
+}
+
+/// containerd 中 `containerd.runc.v1.Options` 的编码，只包含用到的字段
+#[derive(Clone, PartialEq, Message)]
+struct RuncOptionsProto {
+    #[prost(string, tag = "6")]
+    binary_name: String,
+    #[prost(string, tag = "7")]
+    root: String,
+    #[prost(bool, tag = "9")]
+    systemd_cgroup: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RuntimeHandler {
+    /// containerd 的运行时类型，如 `io.containerd.runc.v2` 或 `io.containerd.runsc.v1`
+    pub runtime_type: String,
+    /// 只有 `io.containerd.runc.*` 类型支持选项
+    #[serde(default)]
+    pub options: Option<RuncOptions>,
+}
+
+impl RuntimeHandler {
+    fn runtime(&self) -> Runtime {
+        let options = self.options.as_ref().map(|options| prost_types::Any {
+            type_url: RUNC_OPTIONS_TYPE_URL.to_string(),
+            value: RuncOptionsProto {
+                binary_name: options.binary_name.clone(),
+                root: options.root.clone(),
+                systemd_cgroup: options.systemd_cgroup,
+            }
+            .encode_to_vec(),
+        });
+        Runtime {
+            name: self.runtime_type.clone(),
+            options,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RuntimeClasses {
+    /// 没有选择运行时类的函数使用的类
+    pub default: String,
+    pub classes: BTreeMap<String, RuntimeHandler>,
+}
+
+impl RuntimeClasses {
+    fn builtin() -> Self {
+        RuntimeClasses {
+            default: BUILTIN_CLASS.to_string(),
+            classes: BTreeMap::from([(
+                BUILTIN_CLASS.to_string(),
+                RuntimeHandler {
+                    runtime_type: RUNC_RUNTIME_TYPE.to_string(),
+                    options: None,
+                },
+            )]),
+        }
+    }
+
+    /// 读取 `RUNTIME_CLASSES_FILE` 指定的 JSON 配置，默认路径下没有文件时只提供 runc
+    pub fn from_env() -> Result<Self, String> {
+        let path = std::env::var("RUNTIME_CLASSES_FILE");
+        let explicit = path.is_ok();
+        let path = path.unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+        if !explicit && !Path::new(&path).exists() {
+            return Ok(Self::builtin());
+        }
+        let content = std::fs::read(&path).map_err(|e| format!("{}: {}", path, e))?;
+        let classes: RuntimeClasses =
+            serde_json::from_slice(&content).map_err(|e| format!("{}: {}", path, e))?;
+        classes.validate().map_err(|e| format!("{}: {}", path, e))?;
+        Ok(classes)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.classes.contains_key(&self.default) {
+            return Err(format!(
+                "default runtime class '{}' is not defined",
+                self.default
+            ));
+        }
+        for (name, handler) in &self.classes {
+            if name.is_empty() || name.contains(',') || name.trim() != name {
+                return Err(format!("invalid runtime class name '{}'", name));
+            }
+            if handler.options.is_some() && !handler.runtime_type.starts_with("io.containerd.runc.")
+            {
+                return Err(format!(
+                    "runtime class '{}': options are only supported for io.containerd.runc runtimes",
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, class: &str) -> bool {
+        self.classes.contains_key(class)
+    }
+
+    /// 注解选择的类必须在命名空间允许的类之中；未选择时使用默认类，
+    /// 默认类不被允许时使用命名空间列出的第一个类
+    pub fn select(
+        &self,
+        requested: Option<&str>,
+        allowed: Option<&[String]>,
+    ) -> Result<String, String> {
+        let permitted =
+            |class: &str| allowed.is_none_or(|allowed| allowed.iter().any(|a| a == class));
+        let class = match requested {
+            Some(class) => class,
+            None if permitted(&self.default) => &self.default,
+            None => allowed
+                .and_then(|allowed| allowed.first())
+                .map(String::as_str)
+                .unwrap_or(&self.default),
+        };
+        if !self.contains(class) {
+            return Err(format!("unknown runtime class '{}'", class));
+        }
+        if !permitted(class) {
+            return Err(format!(
+                "runtime class '{}' is not allowed in this namespace",
+                class
+            ));
+        }
+        Ok(class.to_string())
+    }
+
+    /// 运行时类对应的 containerd 运行时，空名称表示默认类
+    pub fn runtime(&self, class: &str) -> Option<Runtime> {
+        let class = if class.is_empty() {
+            &self.default
+        } else {
+            class
+        };
+        self.classes.get(class).map(RuntimeHandler::runtime)
+    }
+}
+
+/// 启动时加载，配置无效时直接退出
+pub static RUNTIME_CLASSES: LazyLock<RuntimeClasses> = LazyLock::new(|| {
+    RuntimeClasses::from_env().unwrap_or_else(|e| panic!("Invalid runtime classes config {}", e))
+});
+
+/// 命名空间标签中逗号分隔的允许的运行时类，未设置时不限制
+pub fn allowed_classes(labels: &HashMap<String, String>) -> Option<Vec<String>> {
+    labels
+        .get(gateway::types::namespace::LABEL_RUNTIME_CLASSES)
+        .map(|classes| {
+            classes
+                .split(',')
+                .map(str::trim)
+                .filter(|class| !class.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_classes() {
+        let classes: RuntimeClasses = serde_json::from_str(
+            r#"{
+                "default": "runc",
+                "classes": {
+                    "runc": {"runtimeType": "io.containerd.runc.v2"},
+                    "crun": {"runtimeType": "io.containerd.runc.v2", "options": {"binaryName": "crun"}},
+                    "gvisor": {"runtimeType": "io.containerd.runsc.v1"}
+                }
+            }"#,
+        )
+        .unwrap();
+        assert!(classes.validate().is_ok());
+
+        let runtime = classes.runtime("crun").unwrap();
+        let options = runtime.options.unwrap();
+        assert_eq!(options.type_url, RUNC_OPTIONS_TYPE_URL);
+        assert_eq!(
+            RuncOptionsProto::decode(options.value.as_slice())
+                .unwrap()
+                .binary_name,
+            "crun"
+        );
+        assert_eq!(classes.runtime("").unwrap().name, RUNC_RUNTIME_TYPE);
+
+        assert_eq!(classes.select(None, None).unwrap(), "runc");
+        let sandboxed = vec!["gvisor".to_string()];
+        assert_eq!(classes.select(None, Some(&sandboxed)).unwrap(), "gvisor");
+        assert!(classes.select(Some("runc"), Some(&sandboxed)).is_err());
+        assert!(classes.select(Some("kata"), None).is_err());
+
+        let mut invalid = classes.clone();
+        invalid.classes.get_mut("gvisor").unwrap().options = Some(RuncOptions::default());
+        assert!(invalid.validate().is_err());
+        invalid.default = "kata".to_string();
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,567 @@
+//! 函数的 seccomp 配置：内置的默认配置参照 containerd 的默认配置，
+//! 只放行常用的系统调用，需要特权的系统调用只在拥有对应 capability 时放行
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use oci_spec::runtime::{
+    Arch, Capability, LinuxSeccomp, LinuxSeccompAction, LinuxSeccompArgBuilder,
+    LinuxSeccompBuilder, LinuxSeccompOperator, LinuxSyscall, LinuxSyscallBuilder,
+};
+
+/// 未放行的系统调用返回 EPERM
+const EPERM: u32 = 1;
+/// clone3 的参数无法过滤，返回 ENOSYS 让 libc 回退到 clone
+const ENOSYS: u32 = 38;
+/// CLONE_NEWNS | CLONE_NEWCGROUP | CLONE_NEWUTS | CLONE_NEWIPC | CLONE_NEWUSER | CLONE_NEWPID | CLONE_NEWNET
+const CLONE_NAMESPACE_FLAGS: u64 = 0x7e02_0000;
+
+const DEFAULT_PROFILE_DIR: &str = "/etc/faasrs/seccomp";
+
+const ALLOWED_SYSCALLS: &[&str] = &[
+    "accept",
+    "accept4",
+    "access",
+    "adjtimex",
+    "alarm",
+    "bind",
+    "brk",
+    "cachestat",
+    "capget",
+    "capset",
+    "chdir",
+    "chmod",
+    "chown",
+    "chown32",
+    "clock_adjtime",
+    "clock_adjtime64",
+    "clock_getres",
+    "clock_getres_time64",
+    "clock_gettime",
+    "clock_gettime64",
+    "clock_nanosleep",
+    "clock_nanosleep_time64",
+    "close",
+    "close_range",
+    "connect",
+    "copy_file_range",
+    "creat",
+    "dup",
+    "dup2",
+    "dup3",
+    "epoll_create",
+    "epoll_create1",
+    "epoll_ctl",
+    "epoll_ctl_old",
+    "epoll_pwait",
+    "epoll_pwait2",
+    "epoll_wait",
+    "epoll_wait_old",
+    "eventfd",
+    "eventfd2",
+    "execve",
+    "execveat",
+    "exit",
+    "exit_group",
+    "faccessat",
+    "faccessat2",
+    "fadvise64",
+    "fadvise64_64",
+    "fallocate",
+    "fanotify_mark",
+    "fchdir",
+    "fchmod",
+    "fchmodat",
+    "fchmodat2",
+    "fchown",
+    "fchown32",
+    "fchownat",
+    "fcntl",
+    "fcntl64",
+    "fdatasync",
+    "fgetxattr",
+    "flistxattr",
+    "flock",
+    "fork",
+    "fremovexattr",
+    "fsetxattr",
+    "fstat",
+    "fstat64",
+    "fstatat64",
+    "fstatfs",
+    "fstatfs64",
+    "fsync",
+    "ftruncate",
+    "ftruncate64",
+    "futex",
+    "futex_requeue",
+    "futex_time64",
+    "futex_wait",
+    "futex_waitv",
+    "futex_wake",
+    "futimesat",
+    "get_robust_list",
+    "get_thread_area",
+    "getcpu",
+    "getcwd",
+    "getdents",
+    "getdents64",
+    "getegid",
+    "getegid32",
+    "geteuid",
+    "geteuid32",
+    "getgid",
+    "getgid32",
+    "getgroups",
+    "getgroups32",
+    "getitimer",
+    "getpeername",
+    "getpgid",
+    "getpgrp",
+    "getpid",
+    "getppid",
+    "getpriority",
+    "getrandom",
+    "getresgid",
+    "getresgid32",
+    "getresuid",
+    "getresuid32",
+    "getrlimit",
+    "getrusage",
+    "getsid",
+    "getsockname",
+    "getsockopt",
+    "gettid",
+    "gettimeofday",
+    "getuid",
+    "getuid32",
+    "getxattr",
+    "inotify_add_watch",
+    "inotify_init",
+    "inotify_init1",
+    "inotify_rm_watch",
+    "io_cancel",
+    "io_destroy",
+    "io_getevents",
+    "io_pgetevents",
+    "io_pgetevents_time64",
+    "io_setup",
+    "io_submit",
+    "ioctl",
+    "ioprio_get",
+    "ioprio_set",
+    "ipc",
+    "kill",
+    "landlock_add_rule",
+    "landlock_create_ruleset",
+    "landlock_restrict_self",
+    "lchown",
+    "lchown32",
+    "lgetxattr",
+    "link",
+    "linkat",
+    "listen",
+    "listxattr",
+    "llistxattr",
+    "_llseek",
+    "lremovexattr",
+    "lseek",
+    "lsetxattr",
+    "lstat",
+    "lstat64",
+    "madvise",
+    "map_shadow_stack",
+    "membarrier",
+    "memfd_create",
+    "memfd_secret",
+    "mincore",
+    "mkdir",
+    "mkdirat",
+    "mknod",
+    "mknodat",
+    "mlock",
+    "mlock2",
+    "mlockall",
+    "mmap",
+    "mmap2",
+    "mprotect",
+    "mq_getsetattr",
+    "mq_notify",
+    "mq_open",
+    "mq_timedreceive",
+    "mq_timedreceive_time64",
+    "mq_timedsend",
+    "mq_timedsend_time64",
+    "mq_unlink",
+    "mremap",
+    "msgctl",
+    "msgget",
+    "msgrcv",
+    "msgsnd",
+    "msync",
+    "munlock",
+    "munlockall",
+    "munmap",
+    "name_to_handle_at",
+    "nanosleep",
+    "newfstatat",
+    "_newselect",
+    "open",
+    "openat",
+    "openat2",
+    "pause",
+    "pidfd_open",
+    "pidfd_send_signal",
+    "pipe",
+    "pipe2",
+    "pkey_alloc",
+    "pkey_free",
+    "pkey_mprotect",
+    "poll",
+    "ppoll",
+    "ppoll_time64",
+    "prctl",
+    "pread64",
+    "preadv",
+    "preadv2",
+    "prlimit64",
+    "process_mrelease",
+    "pselect6",
+    "pselect6_time64",
+    "pwrite64",
+    "pwritev",
+    "pwritev2",
+    "read",
+    "readahead",
+    "readlink",
+    "readlinkat",
+    "readv",
+    "recv",
+    "recvfrom",
+    "recvmmsg",
+    "recvmmsg_time64",
+    "recvmsg",
+    "remap_file_pages",
+    "removexattr",
+    "rename",
+    "renameat",
+    "renameat2",
+    "restart_syscall",
+    "rmdir",
+    "rseq",
+    "rt_sigaction",
+    "rt_sigpending",
+    "rt_sigprocmask",
+    "rt_sigqueueinfo",
+    "rt_sigreturn",
+    "rt_sigsuspend",
+    "rt_sigtimedwait",
+    "rt_sigtimedwait_time64",
+    "rt_tgsigqueueinfo",
+    "sched_get_priority_max",
+    "sched_get_priority_min",
+    "sched_getaffinity",
+    "sched_getattr",
+    "sched_getparam",
+    "sched_getscheduler",
+    "sched_rr_get_interval",
+    "sched_rr_get_interval_time64",
+    "sched_setaffinity",
+    "sched_setattr",
+    "sched_setparam",
+    "sched_setscheduler",
+    "sched_yield",
+    "seccomp",
+    "select",
+    "semctl",
+    "semget",
+    "semop",
+    "semtimedop",
+    "semtimedop_time64",
+    "send",
+    "sendfile",
+    "sendfile64",
+    "sendmmsg",
+    "sendmsg",
+    "sendto",
+    "set_robust_list",
+    "set_thread_area",
+    "set_tid_address",
+    "setfsgid",
+    "setfsgid32",
+    "setfsuid",
+    "setfsuid32",
+    "setgid",
+    "setgid32",
+    "setgroups",
+    "setgroups32",
+    "setitimer",
+    "setpgid",
+    "setpriority",
+    "setregid",
+    "setregid32",
+    "setresgid",
+    "setresgid32",
+    "setresuid",
+    "setresuid32",
+    "setreuid",
+    "setreuid32",
+    "setrlimit",
+    "setsid",
+    "setsockopt",
+    "setuid",
+    "setuid32",
+    "setxattr",
+    "shmat",
+    "shmctl",
+    "shmdt",
+    "shmget",
+    "shutdown",
+    "sigaltstack",
+    "signalfd",
+    "signalfd4",
+    "sigprocmask",
+    "sigreturn",
+    "socket",
+    "socketcall",
+    "socketpair",
+    "splice",
+    "stat",
+    "stat64",
+    "statfs",
+    "statfs64",
+    "statx",
+    "symlink",
+    "symlinkat",
+    "sync",
+    "sync_file_range",
+    "syncfs",
+    "sysinfo",
+    "tee",
+    "tgkill",
+    "time",
+    "timer_create",
+    "timer_delete",
+    "timer_getoverrun",
+    "timer_gettime",
+    "timer_gettime64",
+    "timer_settime",
+    "timer_settime64",
+    "timerfd_create",
+    "timerfd_gettime",
+    "timerfd_gettime64",
+    "timerfd_settime",
+    "timerfd_settime64",
+    "times",
+    "tkill",
+    "truncate",
+    "truncate64",
+    "ugetrlimit",
+    "umask",
+    "uname",
+    "unlink",
+    "unlinkat",
+    "utime",
+    "utimensat",
+    "utimensat_time64",
+    "utimes",
+    "vfork",
+    "vmsplice",
+    "wait4",
+    "waitid",
+    "waitpid",
+    "write",
+    "writev",
+];
+
+/// 只在拥有对应 capability 时放行的系统调用
+const CAPABILITY_SYSCALLS: &[(Capability, &[&str])] = &[
+    (
+        Capability::SysAdmin,
+        &[
+            "bpf",
+            "clone",
+            "clone3",
+            "fanotify_init",
+            "fsconfig",
+            "fsmount",
+            "fsopen",
+            "fspick",
+            "lookup_dcookie",
+            "mount",
+            "mount_setattr",
+            "move_mount",
+            "open_tree",
+            "perf_event_open",
+            "quotactl",
+            "quotactl_fd",
+            "setdomainname",
+            "sethostname",
+            "setns",
+            "syslog",
+            "umount",
+            "umount2",
+            "unshare",
+        ],
+    ),
+    (Capability::SysBoot, &["reboot"]),
+    (Capability::SysChroot, &["chroot"]),
+    (
+        Capability::SysModule,
+        &["delete_module", "init_module", "finit_module"],
+    ),
+    (Capability::SysPacct, &["acct"]),
+    (
+        Capability::SysPtrace,
+        &[
+            "kcmp",
+            "pidfd_getfd",
+            "process_madvise",
+            "process_vm_readv",
+            "process_vm_writev",
+            "ptrace",
+        ],
+    ),
+    (Capability::SysRawio, &["iopl", "ioperm"]),
+    (
+        Capability::SysTime,
+        &["settimeofday", "stime", "clock_settime", "clock_settime64"],
+    ),
+    (Capability::SysTtyConfig, &["vhangup"]),
+    (
+        Capability::SysNice,
+        &[
+            "get_mempolicy",
+            "mbind",
+            "set_mempolicy",
+            "set_mempolicy_home_node",
+        ],
+    ),
+    (Capability::Syslog, &["syslog"]),
+    (Capability::Bpf, &["bpf"]),
+    (Capability::Perfmon, &["perf_event_open"]),
+];
+
+fn syscalls(names: &[&str], action: LinuxSeccompAction) -> LinuxSyscall {
+    LinuxSyscallBuilder::default()
+        .names(names.iter().map(|n| n.to_string()).collect::<Vec<_>>())
+        .action(action)
+        .build()
+        .unwrap()
+}
+
+fn native_arches() -> Vec<Arch> {
+    if cfg!(target_arch = "x86_64") {
+        vec![Arch::ScmpArchX86_64, Arch::ScmpArchX86, Arch::ScmpArchX32]
+    } else if cfg!(target_arch = "aarch64") {
+        vec![Arch::ScmpArchAarch64, Arch::ScmpArchArm]
+    } else {
+        vec![Arch::ScmpArchNative]
+    }
+}
+
+/// 内置的默认配置，放行的系统调用随容器的 capabilities 变化
+pub fn default_profile(caps: &HashSet<Capability>) -> LinuxSeccomp {
+    let mut rules = vec![syscalls(ALLOWED_SYSCALLS, LinuxSeccompAction::ScmpActAllow)];
+
+    // 只允许 personality 切换到常见的执行域
+    for persona in [0x0u64, 0x0008, 0x20000, 0x20008, 0xffff_ffff] {
+        let mut rule = syscalls(&["personality"], LinuxSeccompAction::ScmpActAllow);
+        rule.set_args(Some(vec![
+            LinuxSeccompArgBuilder::default()
+                .index(0usize)
+                .value(persona)
+                .op(LinuxSeccompOperator::ScmpCmpEq)
+                .build()
+                .unwrap(),
+        ]));
+        rules.push(rule);
+    }
+    if cfg!(target_arch = "x86_64") {
+        rules.push(syscalls(
+            &["arch_prctl", "modify_ldt"],
+            LinuxSeccompAction::ScmpActAllow,
+        ));
+    }
+
+    for (cap, names) in CAPABILITY_SYSCALLS {
+        if caps.contains(cap) {
+            rules.push(syscalls(names, LinuxSeccompAction::ScmpActAllow));
+        }
+    }
+    if !caps.contains(&Capability::SysAdmin) {
+        // 没有 CAP_SYS_ADMIN 时 clone 不能创建新的命名空间
+        let mut clone = syscalls(&["clone"], LinuxSeccompAction::ScmpActAllow);
+        clone.set_args(Some(vec![
+            LinuxSeccompArgBuilder::default()
+                .index(0usize)
+                .value(CLONE_NAMESPACE_FLAGS)
+                .value_two(0u64)
+                .op(LinuxSeccompOperator::ScmpCmpMaskedEq)
+                .build()
+                .unwrap(),
+        ]));
+        rules.push(clone);
+        let mut clone3 = syscalls(&["clone3"], LinuxSeccompAction::ScmpActErrno);
+        clone3.set_errno_ret(Some(ENOSYS));
+        rules.push(clone3);
+    }
+
+    LinuxSeccompBuilder::default()
+        .default_action(LinuxSeccompAction::ScmpActErrno)
+        .default_errno_ret(EPERM)
+        .architectures(native_arches())
+        .syscalls(rules)
+        .build()
+        .unwrap()
+}
+
+/// 宿主机上存放自定义配置的目录，由 `SECCOMP_PROFILE_DIR` 指定
+fn profile_dir() -> PathBuf {
+    std::env::var("SECCOMP_PROFILE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_PROFILE_DIR))
+}
+
+/// 读取自定义配置 `<SECCOMP_PROFILE_DIR>/<name>.json`，格式为 OCI 运行时规范中的 `linux.seccomp`
+pub fn load_profile(name: &str) -> Result<LinuxSeccomp, String> {
+    let path = profile_dir().join(format!("{}.json", name));
+    let content = std::fs::read(&path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => format!("seccomp profile '{}' not found", name),
+        _ => format!("failed to read seccomp profile '{}': {}", name, e),
+    })?;
+    serde_json::from_slice(&content)
+        .map_err(|e| format!("invalid seccomp profile '{}': {}", name, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(profile: &LinuxSeccomp, name: &str) -> bool {
+        profile.syscalls().as_ref().unwrap().iter().any(|rule| {
+            rule.action() == LinuxSeccompAction::ScmpActAllow
+                && rule.args().is_none()
+                && rule.names().iter().any(|n| n == name)
+        })
+    }
+
+    #[test]
+    fn test_default_profile() {
+        let profile = default_profile(&HashSet::from([Capability::Chown]));
+        assert_eq!(profile.default_action(), LinuxSeccompAction::ScmpActErrno);
+        assert!(allowed(&profile, "read"));
+        assert!(!allowed(&profile, "mount"));
+        assert!(!allowed(&profile, "ptrace"));
+        // clone 只在不创建命名空间时放行
+        assert!(!allowed(&profile, "clone"));
+
+        let profile = default_profile(&HashSet::from([
+            Capability::SysAdmin,
+            Capability::SysPtrace,
+        ]));
+        assert!(allowed(&profile, "mount"));
+        assert!(allowed(&profile, "clone"));
+        assert!(allowed(&profile, "ptrace"));
+    }
+}
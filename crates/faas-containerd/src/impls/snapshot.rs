@@ -1,24 +1,28 @@
 use containerd_client::{
-    services::v1::snapshots::{MountsRequest, PrepareSnapshotRequest, RemoveSnapshotRequest},
+    services::v1::snapshots::{
+        MountsRequest, PrepareSnapshotRequest, RemoveSnapshotRequest, StatSnapshotRequest,
+    },
     types::Mount,
     with_namespace,
 };
+use std::collections::HashMap;
 use tonic::Request;
 
 use crate::impls::error::ContainerdError;
 
-use super::{ContainerdService, cni::Endpoint, function::ContainerStaticMetadata};
+use super::{ContainerdService, cni::Endpoint, function::ContainerStaticMetadata, user::IdMapping};
 
 impl ContainerdService {
-    #[allow(unused)]
-    pub(super) async fn get_mounts(
+    /// 函数已有快照的挂载信息，重新创建任务时使用
+    pub async fn get_mounts(
         &self,
         cid: &str,
         ns: &str,
+        snapshotter: &str,
     ) -> Result<Vec<Mount>, ContainerdError> {
         let mut sc = self.client.snapshots();
         let req = MountsRequest {
-            snapshotter: crate::consts::DEFAULT_SNAPSHOTTER.to_string(),
+            snapshotter: snapshotter.to_string(),
             key: cid.to_string(),
         };
         let mounts = sc
@@ -41,25 +45,71 @@ impl ContainerdService {
         let parent_snapshot = self
             .get_parent_snapshot(&container.image, &container.endpoint.namespace)
             .await?;
+        self.ensure_unpacked(container, &parent_snapshot).await?;
         self.do_prepare_snapshot(
             &container.endpoint.function_name,
             &container.endpoint.namespace,
+            &container.snapshotter,
             parent_snapshot,
+            container.userns.as_ref(),
         )
         .await
     }
 
+    /// 镜像可能在更换 snapshotter 之前解包，此时在当前 snapshotter 中重新解包
+    async fn ensure_unpacked(
+        &self,
+        container: &ContainerStaticMetadata,
+        parent_snapshot: &str,
+    ) -> Result<(), ContainerdError> {
+        let ns = &container.endpoint.namespace;
+        let req = StatSnapshotRequest {
+            snapshotter: container.snapshotter.clone(),
+            key: parent_snapshot.to_string(),
+        };
+        match self.client.snapshots().stat(with_namespace!(req, ns)).await {
+            Ok(_) => return Ok(()),
+            Err(e) if e.code() == tonic::Code::NotFound => {}
+            Err(e) => return Err(ContainerdError::GetParentSnapshotError(e.to_string())),
+        }
+        log::info!(
+            "Image {} is not unpacked in snapshotter {}, unpacking",
+            container.image,
+            container.snapshotter
+        );
+        self.copy_image(&container.image, ns, ns)
+            .await
+            .map_err(|e| ContainerdError::GetParentSnapshotError(e.to_string()))
+    }
+
     async fn do_prepare_snapshot(
         &self,
         cid: &str,
         ns: &str,
+        snapshotter: &str,
         parent_snapshot: String,
+        userns: Option<&IdMapping>,
     ) -> Result<Vec<Mount>, ContainerdError> {
+        // 让 snapshotter 把 rootfs 的属主映射到用户命名空间的 ID 段
+        let labels = userns
+            .map(|mapping| {
+                HashMap::from([
+                    (
+                        "containerd.io/snapshot/uidmapping".to_string(),
+                        mapping.label(),
+                    ),
+                    (
+                        "containerd.io/snapshot/gidmapping".to_string(),
+                        mapping.label(),
+                    ),
+                ])
+            })
+            .unwrap_or_default();
         let req = PrepareSnapshotRequest {
-            snapshotter: crate::consts::DEFAULT_SNAPSHOTTER.to_string(),
+            snapshotter: snapshotter.to_string(),
             key: cid.to_string(),
             parent: parent_snapshot,
-            ..Default::default()
+            labels,
         };
         let mut client = self.client.snapshots();
         let resp = client
@@ -80,7 +130,6 @@ impl ContainerdService {
         image_name: &str,
         namespace: &str,
     ) -> Result<String, ContainerdError> {
-        use sha2::Digest;
         let config = self
             .image_config(image_name, namespace)
             .await
@@ -96,27 +145,22 @@ impl ContainerdService {
             ));
         }
 
-        let mut iter = config.rootfs().diff_ids().iter();
-        let mut ret = iter
-            .next()
-            .map_or_else(String::new, |layer_digest| layer_digest.clone());
-
-        for layer_digest in iter {
-            let mut hasher = sha2::Sha256::new();
-            hasher.update(ret.as_bytes());
-            ret.push_str(&format!(",{}", layer_digest));
-            hasher.update(" ");
-            hasher.update(layer_digest);
-            let digest = ::hex::encode(hasher.finalize());
-            ret = format!("sha256:{digest}");
-        }
-        Ok(ret)
+        Ok(config
+            .rootfs()
+            .diff_ids()
+            .iter()
+            .fold(String::new(), |parent, diff_id| chain_id(&parent, diff_id)))
     }
 
-    pub async fn remove_snapshot(&self, endpoint: &Endpoint) -> Result<(), ContainerdError> {
+    /// 删除函数的快照，`snapshotter` 为部署时记录在容器上的值
+    pub async fn remove_snapshot(
+        &self,
+        endpoint: &Endpoint,
+        snapshotter: &str,
+    ) -> Result<(), ContainerdError> {
         let mut sc = self.client.snapshots();
         let req = RemoveSnapshotRequest {
-            snapshotter: crate::consts::DEFAULT_SNAPSHOTTER.to_string(),
+            snapshotter: snapshotter.to_string(),
             key: endpoint.function_name.clone(),
         };
         sc.remove(with_namespace!(req, endpoint.namespace))
@@ -129,3 +173,17 @@ impl ContainerdService {
         Ok(())
     }
 }
+
+/// 层的 chain id：第一层为其 diff id，之后为 `sha256(父层 chain id + " " + diff id)`，
+/// 也是解包后该层快照的名称
+pub(super) fn chain_id(parent: &str, diff_id: &str) -> String {
+    use sha2::Digest;
+    if parent.is_empty() {
+        return diff_id.to_string();
+    }
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(parent);
+    hasher.update(" ");
+    hasher.update(diff_id);
+    format!("sha256:{}", ::hex::encode(hasher.finalize()))
+}
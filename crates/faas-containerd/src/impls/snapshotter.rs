@@ -0,0 +1,99 @@
+//! snapshotter：启动时按 `SNAPSHOTTER` 选择并检查 containerd 是否已加载该插件
+
+use containerd_client::{
+    Client,
+    services::v1::{Plugin, PluginsRequest},
+    tonic::Request,
+};
+
+use crate::consts::DEFAULT_SNAPSHOTTER;
+
+const SNAPSHOTTER_PLUGIN_TYPE: &str = "io.containerd.snapshotter.v1";
+/// 未配置 `SNAPSHOTTER` 且 overlayfs 不可用时使用，不依赖宿主机文件系统
+const FALLBACK_SNAPSHOTTER: &str = "native";
+
+/// 显式配置的 snapshotter 不可用时返回错误；未配置时优先 overlayfs，不可用则退回 native
+pub async fn select_snapshotter(client: &Client) -> Result<String, String> {
+    if let Ok(name) = std::env::var("SNAPSHOTTER") {
+        probe(client, &name).await?;
+        return Ok(name);
+    }
+    match probe(client, DEFAULT_SNAPSHOTTER).await {
+        Ok(()) => Ok(DEFAULT_SNAPSHOTTER.to_string()),
+        Err(e) => {
+            log::warn!("{}, falling back to {}", e, FALLBACK_SNAPSHOTTER);
+            probe(client, FALLBACK_SNAPSHOTTER).await?;
+            Ok(FALLBACK_SNAPSHOTTER.to_string())
+        }
+    }
+}
+
+async fn probe(client: &Client, name: &str) -> Result<(), String> {
+    if !valid_name(name) {
+        return Err(format!("invalid snapshotter name '{}'", name));
+    }
+    let req = PluginsRequest {
+        filters: vec![format!("type=={},id=={}", SNAPSHOTTER_PLUGIN_TYPE, name)],
+    };
+    let plugins = client
+        .introspection()
+        .plugins(Request::new(req))
+        .await
+        .map_err(|e| format!("failed to list containerd plugins: {}", e))?
+        .into_inner()
+        .plugins;
+    plugin_status(&plugins, name)
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// 插件存在且初始化成功才可用，例如 btrfs 插件在非 btrfs 文件系统上会初始化失败
+fn plugin_status(plugins: &[Plugin], name: &str) -> Result<(), String> {
+    let plugin = plugins
+        .iter()
+        .find(|p| p.r#type == SNAPSHOTTER_PLUGIN_TYPE && p.id == name)
+        .ok_or_else(|| format!("snapshotter '{}' is not registered in containerd", name))?;
+    match &plugin.init_err {
+        Some(status) => Err(format!(
+            "snapshotter '{}' is unavailable: {}",
+            name, status.message
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use containerd_client::google::rpc::Status;
+
+    use super::*;
+
+    #[test]
+    fn test_plugin_status() {
+        let plugin = |id: &str, init_err: Option<&str>| Plugin {
+            r#type: SNAPSHOTTER_PLUGIN_TYPE.to_string(),
+            id: id.to_string(),
+            init_err: init_err.map(|message| Status {
+                message: message.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let plugins = vec![
+            plugin("overlayfs", None),
+            plugin("btrfs", Some("path must be a btrfs filesystem")),
+        ];
+        assert!(plugin_status(&plugins, "overlayfs").is_ok());
+        assert!(plugin_status(&plugins, "btrfs").is_err());
+        assert!(plugin_status(&plugins, "zfs").is_err());
+
+        assert!(valid_name("overlayfs"));
+        assert!(!valid_name(""));
+        assert!(!valid_name("zfs,id==native"));
+    }
+}
@@ -1,17 +1,27 @@
 use super::{
-    ContainerdService, cni::Endpoint, error::ContainerdError, function::ContainerStaticMetadata,
+    ContainerdService,
+    cni::Endpoint,
+    error::ContainerdError,
+    function::{ContainerStaticMetadata, VolumeBind},
+    seccomp,
+    user::{IdMapping, ProcessUser},
 };
 use crate::consts::{VERSION_DEV, VERSION_MAJOR, VERSION_MINOR, VERSION_PATCH};
+use gateway::types::{
+    limits::ProcessLimits,
+    security::{SeccompProfile, SecurityOptions},
+};
 use oci_spec::{
     image::ImageConfiguration,
     runtime::{
         Capability, LinuxBuilder, LinuxCapabilitiesBuilder, LinuxDeviceCgroupBuilder,
-        LinuxNamespaceBuilder, LinuxNamespaceType, LinuxResourcesBuilder, MountBuilder,
-        PosixRlimitBuilder, PosixRlimitType, ProcessBuilder, RootBuilder, Spec, SpecBuilder,
-        UserBuilder,
+        LinuxIdMappingBuilder, LinuxNamespaceBuilder, LinuxNamespaceType, LinuxPidsBuilder,
+        LinuxResourcesBuilder, MountBuilder, PosixRlimitBuilder, PosixRlimitType, ProcessBuilder,
+        RootBuilder, Spec, SpecBuilder, UserBuilder,
     },
 };
-use std::path::Path;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
 
 fn oci_version() -> String {
     format!(
@@ -20,28 +30,74 @@ fn oci_version() -> String {
     )
 }
 
+/// 函数默认拥有的 capabilities，NET_RAW、MKNOD 与 SETFCAP 需要通过注解添加
+const DEFAULT_CAPABILITIES: [Capability; 11] = [
+    Capability::Chown,
+    Capability::DacOverride,
+    Capability::Fsetid,
+    Capability::Fowner,
+    Capability::Setgid,
+    Capability::Setuid,
+    Capability::Setpcap,
+    Capability::NetBindService,
+    Capability::SysChroot,
+    Capability::Kill,
+    Capability::AuditWrite,
+];
+
+/// `ALL` 对应的全部 capabilities
+const ALL_CAPABILITIES: [Capability; 41] = [
+    Capability::AuditControl,
+    Capability::AuditRead,
+    Capability::AuditWrite,
+    Capability::BlockSuspend,
+    Capability::Bpf,
+    Capability::CheckpointRestore,
+    Capability::Chown,
+    Capability::DacOverride,
+    Capability::DacReadSearch,
+    Capability::Fowner,
+    Capability::Fsetid,
+    Capability::IpcLock,
+    Capability::IpcOwner,
+    Capability::Kill,
+    Capability::Lease,
+    Capability::LinuxImmutable,
+    Capability::MacAdmin,
+    Capability::MacOverride,
+    Capability::Mknod,
+    Capability::NetAdmin,
+    Capability::NetBindService,
+    Capability::NetBroadcast,
+    Capability::NetRaw,
+    Capability::Perfmon,
+    Capability::Setgid,
+    Capability::Setfcap,
+    Capability::Setpcap,
+    Capability::Setuid,
+    Capability::SysAdmin,
+    Capability::SysBoot,
+    Capability::SysChroot,
+    Capability::SysModule,
+    Capability::SysNice,
+    Capability::SysPacct,
+    Capability::SysPtrace,
+    Capability::SysRawio,
+    Capability::SysResource,
+    Capability::SysTime,
+    Capability::SysTtyConfig,
+    Capability::Syslog,
+    Capability::WakeAlarm,
+];
+
 pub(super) fn generate_default_unix_spec(
     ns: &str,
     cid: &str,
     runtime_config: &RuntimeConfig,
+    volumes: &[VolumeBind],
 ) -> Result<oci_spec::runtime::Spec, ContainerdError> {
-    let caps = [
-        Capability::Chown,
-        Capability::DacOverride,
-        Capability::Fsetid,
-        Capability::Fowner,
-        Capability::Mknod,
-        Capability::NetRaw,
-        Capability::Setgid,
-        Capability::Setuid,
-        Capability::Setfcap,
-        Capability::Setpcap,
-        Capability::NetBindService,
-        Capability::SysChroot,
-        Capability::Kill,
-        Capability::AuditWrite,
-    ];
-    let spec = SpecBuilder::default()
+    let caps = DEFAULT_CAPABILITIES;
+    let mut spec = SpecBuilder::default()
         .version(oci_version())
         .root(
             RootBuilder::default()
@@ -218,40 +274,216 @@ pub(super) fn generate_default_unix_spec(
             ContainerdError::GenerateSpecError(e.to_string())
         })?;
 
+    let mut mounts = spec.mounts().clone().unwrap_or_default();
+    for volume in volumes {
+        let mode = if volume.read_only { "ro" } else { "rw" };
+        mounts.push(
+            MountBuilder::default()
+                .destination(&volume.destination)
+                .typ("bind")
+                .source(&volume.source)
+                .options(["rbind".into(), "rprivate".into(), mode.into()])
+                .build()
+                .map_err(|e| {
+                    log::error!("Failed to build OCI ({}) Mount: {}", volume.destination, e);
+                    ContainerdError::GenerateSpecError(e.to_string())
+                })?,
+        );
+    }
+    spec.set_mounts(Some(mounts));
+
     Ok(spec)
 }
 
-#[allow(unused)]
-pub(super) fn with_vm_network(spec: &mut Spec) -> Result<(), ContainerdError> {
-    let mounts = spec
-        .mounts()
-        .as_ref()
-        .expect("Spec's 'Mounts' field should not be None");
-    let mut new_mounts = mounts.clone();
-    new_mounts.extend([
-        MountBuilder::default()
-            .destination("/etc/resolv.conf")
-            .typ("bind")
-            .source("/etc/resolv.conf")
-            .options(["rbind".into(), "ro".into()])
+/// 以只读方式绑定挂载为函数生成的文件，如 /etc/hosts 与指向内置 DNS 的 resolv.conf
+pub(super) fn with_vm_network(
+    spec: &mut Spec,
+    bind_files: &BTreeMap<String, PathBuf>,
+) -> Result<(), ContainerdError> {
+    let mut mounts = spec.mounts().clone().unwrap_or_default();
+    for (destination, source) in bind_files {
+        mounts.push(
+            MountBuilder::default()
+                .destination(destination)
+                .typ("bind")
+                .source(source)
+                .options(["rbind".into(), "ro".into()])
+                .build()
+                .map_err(|e| {
+                    log::error!("Failed to build OCI ({}) Mount: {}", destination, e);
+                    ContainerdError::GenerateSpecError(e.to_string())
+                })?,
+        );
+    }
+    spec.set_mounts(Some(mounts));
+
+    Ok(())
+}
+
+/// 在默认集合上删除、再添加注解中的 capabilities，名称为不带 `CAP_` 前缀的大写形式
+pub fn capabilities(options: &SecurityOptions) -> Result<HashSet<Capability>, String> {
+    let parse = |name: &String| {
+        name.parse::<Capability>()
+            .map_err(|_| format!("unknown capability '{}'", name))
+    };
+    let mut caps: HashSet<_> = DEFAULT_CAPABILITIES.into_iter().collect();
+    if options.cap_drop.iter().any(|cap| cap == "ALL") {
+        caps.clear();
+    }
+    for cap in options.cap_drop.iter().filter(|cap| *cap != "ALL") {
+        caps.remove(&parse(cap)?);
+    }
+    if options.cap_add.iter().any(|cap| cap == "ALL") {
+        caps.extend(ALL_CAPABILITIES);
+    }
+    for cap in options.cap_add.iter().filter(|cap| *cap != "ALL") {
+        caps.insert(parse(cap)?);
+    }
+    Ok(caps)
+}
+
+/// 按注解设置 capabilities 与 seccomp，需要在 `with_user` 之前调用
+pub(super) fn with_security(
+    spec: &mut Spec,
+    options: &SecurityOptions,
+) -> Result<(), ContainerdError> {
+    let caps = capabilities(options).map_err(ContainerdError::GenerateSpecError)?;
+    if let Some(process) = spec.process_mut() {
+        let linux_caps = LinuxCapabilitiesBuilder::default()
+            .bounding(caps.clone())
+            .permitted(caps.clone())
+            .effective(caps.clone())
+            .build()
+            .map_err(|e| ContainerdError::GenerateSpecError(e.to_string()))?;
+        process.set_capabilities(Some(linux_caps));
+    }
+    let seccomp = match &options.seccomp {
+        SeccompProfile::Default => Some(seccomp::default_profile(&caps)),
+        SeccompProfile::Unconfined => None,
+        SeccompProfile::Named(name) => {
+            Some(seccomp::load_profile(name).map_err(ContainerdError::GenerateSpecError)?)
+        }
+    };
+    if let Some(linux) = spec.linux_mut() {
+        linux.set_seccomp(seccomp);
+    }
+    Ok(())
+}
+
+/// 设置打开文件数、进程数与 /dev/shm 大小的限制
+pub(super) fn with_process_limits(
+    spec: &mut Spec,
+    limits: &ProcessLimits,
+) -> Result<(), ContainerdError> {
+    let build_err = |e: oci_spec::OciSpecError| {
+        log::error!("Failed to build OCI process limits: {}", e);
+        ContainerdError::GenerateSpecError(e.to_string())
+    };
+    if let Some(process) = spec.process_mut() {
+        process.set_rlimits(Some(vec![
+            PosixRlimitBuilder::default()
+                .typ(PosixRlimitType::RlimitNofile)
+                .hard(limits.nofile)
+                .soft(limits.nofile)
+                .build()
+                .map_err(build_err)?,
+        ]));
+    }
+    if let Some(pids_limit) = limits.pids_limit
+        && let Some(resources) = spec
+            .linux_mut()
+            .as_mut()
+            .and_then(|l| l.resources_mut().as_mut())
+    {
+        resources.set_pids(Some(
+            LinuxPidsBuilder::default()
+                .limit(pids_limit as i64)
+                .build()
+                .map_err(build_err)?,
+        ));
+    }
+    let mut mounts = spec.mounts().clone().unwrap_or_default();
+    for mount in mounts
+        .iter_mut()
+        .filter(|m| m.destination() == Path::new("/dev/shm"))
+    {
+        let mut options = mount.options().clone().unwrap_or_default();
+        options.retain(|o| !o.starts_with("size="));
+        options.push(format!("size={}", limits.shm_size));
+        mount.set_options(Some(options));
+    }
+    spec.set_mounts(Some(mounts));
+    Ok(())
+}
+
+/// 设置进程的用户。非 root 用户不保留有效的 capabilities；
+/// 开启用户命名空间时把容器内的 root 映射到宿主机上无特权的 ID 段
+pub(super) fn with_user(
+    spec: &mut Spec,
+    user: &ProcessUser,
+    userns: Option<&IdMapping>,
+) -> Result<(), ContainerdError> {
+    let build_err = |e: oci_spec::OciSpecError| {
+        log::error!("Failed to build OCI user settings: {}", e);
+        ContainerdError::GenerateSpecError(e.to_string())
+    };
+    if let Some(process) = spec.process_mut() {
+        let mut builder = UserBuilder::default().uid(user.uid).gid(user.gid);
+        if !user.additional_gids.is_empty() {
+            builder = builder.additional_gids(user.additional_gids.clone());
+        }
+        process.set_user(builder.build().map_err(build_err)?);
+        if user.uid != 0
+            && let Some(mut caps) = process.capabilities().clone()
+        {
+            caps.set_permitted(Some(HashSet::new()));
+            caps.set_effective(Some(HashSet::new()));
+            process.set_capabilities(Some(caps));
+        }
+    }
+
+    let Some(mapping) = userns else {
+        return Ok(());
+    };
+    if let Some(linux) = spec.linux_mut() {
+        let mut namespaces = linux.namespaces().clone().unwrap_or_default();
+        namespaces.push(
+            LinuxNamespaceBuilder::default()
+                .typ(LinuxNamespaceType::User)
+                .build()
+                .map_err(build_err)?,
+        );
+        linux.set_namespaces(Some(namespaces));
+        let id_mapping = LinuxIdMappingBuilder::default()
+            .container_id(0u32)
+            .host_id(mapping.host_id)
+            .size(mapping.size)
             .build()
-            .map_err(|e| {
-                log::error!("Failed to build OCI (resolv.conf) Mount: {}", e);
-                ContainerdError::GenerateSpecError(e.to_string())
-            })?,
-        MountBuilder::default()
-            .destination("/etc/hosts")
+            .map_err(build_err)?;
+        linux.set_uid_mappings(Some(vec![id_mapping]));
+        linux.set_gid_mappings(Some(vec![id_mapping]));
+    }
+    // 用户命名空间中不能挂载新的 sysfs，改为绑定挂载宿主机的 /sys
+    let mut mounts = spec.mounts().clone().unwrap_or_default();
+    for mount in mounts
+        .iter_mut()
+        .filter(|m| m.typ().as_deref() == Some("sysfs"))
+    {
+        *mount = MountBuilder::default()
+            .destination("/sys")
             .typ("bind")
-            .source("/etc/hosts")
-            .options(["rbind".into(), "ro".into()])
+            .source("/sys")
+            .options([
+                "rbind".into(),
+                "nosuid".into(),
+                "noexec".into(),
+                "nodev".into(),
+                "ro".into(),
+            ])
             .build()
-            .map_err(|e| {
-                log::error!("Failed to build OCI (hosts) Mount: {}", e);
-                ContainerdError::GenerateSpecError(e.to_string())
-            })?,
-    ]);
-    let _ = spec.set_mounts(Some(new_mounts));
-
+            .map_err(build_err)?;
+    }
+    spec.set_mounts(Some(mounts));
     Ok(())
 }
 
@@ -261,6 +493,22 @@ pub struct RuntimeConfig {
     pub args: Vec<String>,
     pub ports: Vec<String>,
     pub cwd: String,
+    /// 镜像配置中的 `User`，未设置时为空
+    pub user: String,
+}
+
+impl RuntimeConfig {
+    /// 镜像唯一暴露的 TCP 端口，`ExposedPorts` 中未写协议时视为 TCP
+    pub fn exposed_tcp_port(&self) -> Option<u16> {
+        let mut tcp = self.ports.iter().filter_map(|port| {
+            let (port, proto) = port.split_once('/').unwrap_or((port, "tcp"));
+            (proto == "tcp").then(|| port.parse::<u16>().ok()).flatten()
+        });
+        match (tcp.next(), tcp.next()) {
+            (Some(port), None) => Some(port),
+            _ => None,
+        }
+    }
 }
 
 impl TryFrom<ImageConfiguration> for RuntimeConfig {
@@ -286,35 +534,50 @@ impl TryFrom<ImageConfiguration> for RuntimeConfig {
             log::warn!("Working directory not found, using default /");
             "/".to_string()
         });
+        let user = config.user().clone().unwrap_or_default();
         Ok(RuntimeConfig {
             env,
             args,
             ports,
             cwd,
+            user,
         })
     }
 }
 
 impl ContainerdService {
+    /// 镜像的运行时配置
+    pub async fn runtime_config(
+        &self,
+        image: &str,
+        namespace: &str,
+    ) -> Result<RuntimeConfig, ContainerdError> {
+        let image_conf = self.image_config(image, namespace).await.map_err(|e| {
+            log::error!("Failed to get image config: {}", e);
+            ContainerdError::GenerateSpecError(e.to_string())
+        })?;
+        RuntimeConfig::try_from(image_conf)
+    }
+
     pub async fn get_spec(
         &self,
         metadata: &ContainerStaticMetadata,
     ) -> Result<prost_types::Any, ContainerdError> {
-        let image_conf = self
-            .image_config(&metadata.image, &metadata.endpoint.namespace)
-            .await
-            .map_err(|e| {
-                log::error!("Failed to get image config: {}", e);
-                ContainerdError::GenerateSpecError(e.to_string())
-            })?;
+        let rt_conf = self
+            .runtime_config(&metadata.image, &metadata.endpoint.namespace)
+            .await?;
 
-        let rt_conf = RuntimeConfig::try_from(image_conf)?;
-
-        let spec = generate_default_unix_spec(
+        let mut spec = generate_default_unix_spec(
             &metadata.endpoint.namespace,
             &metadata.endpoint.function_name,
             &rt_conf,
+            &metadata.volumes,
         )?;
+        spec.set_hostname(metadata.hostname.clone());
+        with_process_limits(&mut spec, &metadata.limits)?;
+        with_security(&mut spec, &metadata.security)?;
+        with_user(&mut spec, &metadata.user, metadata.userns.as_ref())?;
+        with_vm_network(&mut spec, &metadata.bind_files)?;
         let spec_json = serde_json::to_string(&spec).map_err(|e| {
             log::error!("Failed to serialize spec to JSON: {}", e);
             ContainerdError::GenerateSpecError(e.to_string())
@@ -327,3 +590,122 @@ impl ContainerdService {
         Ok(any_spec)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_ports(ports: &[&str]) -> RuntimeConfig {
+        RuntimeConfig {
+            env: Vec::new(),
+            args: Vec::new(),
+            ports: ports.iter().map(|p| p.to_string()).collect(),
+            cwd: "/".to_string(),
+            user: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_exposed_tcp_port() {
+        assert_eq!(with_ports(&["3000/tcp"]).exposed_tcp_port(), Some(3000));
+        assert_eq!(with_ports(&["53/udp", "80"]).exposed_tcp_port(), Some(80));
+        assert_eq!(with_ports(&["80/tcp", "443/tcp"]).exposed_tcp_port(), None);
+        assert_eq!(with_ports(&[]).exposed_tcp_port(), None);
+    }
+
+    #[test]
+    fn test_with_user() {
+        let mut spec =
+            generate_default_unix_spec("default", "hello", &with_ports(&[]), &[]).unwrap();
+        let user = ProcessUser {
+            uid: 1000,
+            gid: 1000,
+            additional_gids: vec![999],
+        };
+        let mapping = IdMapping::parse("100000:65536").unwrap();
+        with_user(&mut spec, &user, Some(&mapping)).unwrap();
+
+        let process = spec.process().as_ref().unwrap();
+        assert_eq!(process.user().uid(), 1000);
+        assert_eq!(
+            process.user().additional_gids().as_deref(),
+            Some(&[999][..])
+        );
+        let caps = process.capabilities().as_ref().unwrap();
+        assert!(caps.effective().as_ref().unwrap().is_empty());
+        assert!(!caps.bounding().as_ref().unwrap().is_empty());
+
+        let linux = spec.linux().as_ref().unwrap();
+        assert!(
+            linux
+                .namespaces()
+                .as_ref()
+                .unwrap()
+                .iter()
+                .any(|ns| ns.typ() == LinuxNamespaceType::User)
+        );
+        assert_eq!(linux.uid_mappings().as_ref().unwrap()[0].host_id(), 100000);
+        let sys = spec
+            .mounts()
+            .as_ref()
+            .unwrap()
+            .iter()
+            .find(|m| m.destination() == Path::new("/sys"))
+            .unwrap();
+        assert_eq!(sys.typ().as_deref(), Some("bind"));
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let default = capabilities(&SecurityOptions::default()).unwrap();
+        assert!(!default.contains(&Capability::NetRaw));
+        assert!(default.contains(&Capability::Chown));
+
+        let options = SecurityOptions {
+            cap_add: vec!["NET_RAW".to_string()],
+            cap_drop: vec!["ALL".to_string()],
+            seccomp: SeccompProfile::Unconfined,
+        };
+        assert_eq!(
+            capabilities(&options).unwrap(),
+            HashSet::from([Capability::NetRaw])
+        );
+        let mut spec =
+            generate_default_unix_spec("default", "hello", &with_ports(&[]), &[]).unwrap();
+        with_security(&mut spec, &options).unwrap();
+        assert!(spec.linux().as_ref().unwrap().seccomp().is_none());
+
+        let unknown = SecurityOptions {
+            cap_add: vec!["FLY".to_string()],
+            ..Default::default()
+        };
+        assert!(capabilities(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_process_limits() {
+        let mut spec =
+            generate_default_unix_spec("default", "hello", &with_ports(&[]), &[]).unwrap();
+        let limits = ProcessLimits {
+            nofile: 65536,
+            pids_limit: Some(256),
+            shm_size: 1 << 30,
+        };
+        with_process_limits(&mut spec, &limits).unwrap();
+
+        let rlimits = spec.process().as_ref().unwrap().rlimits().clone().unwrap();
+        assert_eq!(rlimits[0].soft(), 65536);
+        let resources = spec.linux().as_ref().unwrap().resources().as_ref().unwrap();
+        assert_eq!(resources.pids().as_ref().unwrap().limit(), 256);
+        let shm = spec
+            .mounts()
+            .as_ref()
+            .unwrap()
+            .iter()
+            .find(|m| m.destination() == Path::new("/dev/shm"))
+            .unwrap();
+        let options = shm.options().as_ref().unwrap();
+        assert!(options.contains(&"size=1073741824".to_string()));
+        assert_eq!(options.iter().filter(|o| o.starts_with("size=")).count(), 1);
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bytes::Bytes;
+use containerd_client::{
+    services::v1::StreamInit,
+    to_any,
+    types::transfer::{Data, WindowUpdate},
+    with_namespace,
+};
+use futures::{SinkExt, channel::mpsc};
+use prost::{Message, Name};
+use prost_types::Any;
+use tonic::{Request, Status, codec::Streaming};
+
+use super::ContainerdService;
+
+static STREAM_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// 接收字节流时允许 containerd 预先发送的字节数
+const RECV_WINDOW: i32 = 1 << 20;
+
+/// A bidirectional stream registered in containerd's streaming service,
+/// other services (e.g. transfer) refer to it by `id`
+pub struct ContainerdStream {
+    pub id: String,
+    pub tx: mpsc::Sender<Any>,
+    pub rx: Streaming<Any>,
+}
+
+impl ContainerdStream {
+    pub async fn send(&mut self, msg: Any) -> Result<(), Status> {
+        self.tx
+            .send(msg)
+            .await
+            .map_err(|e| Status::cancelled(format!("stream {} closed: {}", self.id, e)))
+    }
+
+    pub async fn recv(&mut self) -> Result<Option<Any>, Status> {
+        self.rx.message().await
+    }
+
+    /// 按 containerd 的流控协议发送字节流：收到 `WindowUpdate` 后才能发送相应大小的
+    /// `Data`，数据发送完毕后关闭流表示 EOF
+    pub async fn send_bytes(
+        mut self,
+        mut data: tokio::sync::mpsc::Receiver<Bytes>,
+    ) -> Result<(), Status> {
+        let mut window: i64 = 0;
+        let mut pending: Option<Bytes> = None;
+        loop {
+            let mut chunk = match pending.take() {
+                Some(chunk) => chunk,
+                None => match data.recv().await {
+                    Some(chunk) => chunk,
+                    None => break,
+                },
+            };
+            while window <= 0 {
+                let msg = self.recv().await?.ok_or_else(|| {
+                    Status::aborted(format!("stream {} closed by containerd", self.id))
+                })?;
+                if msg.type_url == WindowUpdate::full_name() {
+                    let update = WindowUpdate::decode(msg.value.as_slice())
+                        .map_err(|e| Status::internal(e.to_string()))?;
+                    window += update.update as i64;
+                }
+            }
+            if chunk.len() as i64 > window {
+                pending = Some(chunk.split_off(window as usize));
+            }
+            window -= chunk.len() as i64;
+            self.send(to_any(&Data {
+                data: chunk.to_vec(),
+            }))
+            .await?;
+        }
+        log::trace!("Stream {} reached EOF", self.id);
+        Ok(())
+    }
+
+    /// 作为接收方读取 containerd 发送的字节流，每消费一段数据就归还相应的窗口，
+    /// containerd 关闭流时结束，`data` 随之关闭表示 EOF
+    pub async fn recv_bytes(
+        mut self,
+        data: tokio::sync::mpsc::Sender<Bytes>,
+    ) -> Result<(), Status> {
+        self.send(to_any(&WindowUpdate {
+            update: RECV_WINDOW,
+        }))
+        .await?;
+        while let Some(msg) = self.recv().await? {
+            if msg.type_url != Data::full_name() {
+                continue;
+            }
+            let chunk = Data::decode(msg.value.as_slice())
+                .map_err(|e| Status::internal(e.to_string()))?
+                .data;
+            let len = chunk.len() as i32;
+            if data.send(Bytes::from(chunk)).await.is_err() {
+                // the consumer is gone, its result tells why
+                break;
+            }
+            self.send(to_any(&WindowUpdate { update: len })).await?;
+        }
+        log::trace!("Stream {} reached EOF", self.id);
+        Ok(())
+    }
+}
+
+impl ContainerdService {
+    /// 创建一个流，并等待 containerd 确认注册完成
+    pub async fn open_stream(&self, ns: &str) -> Result<ContainerdStream, Status> {
+        let id = format!(
+            "faasrs-{}-{}",
+            std::process::id(),
+            STREAM_SEQ.fetch_add(1, Ordering::Relaxed)
+        );
+        let (mut tx, outbound) = mpsc::channel::<Any>(16);
+        tx.send(to_any(&StreamInit { id: id.clone() }))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let mut sc = self.client.streaming();
+        let mut rx = sc.stream(with_namespace!(outbound, ns)).await?.into_inner();
+        // the first message is an ack of the init request
+        rx.message().await?;
+        log::trace!("Stream {} opened", id);
+
+        Ok(ContainerdStream { id, tx, rx })
+    }
+}
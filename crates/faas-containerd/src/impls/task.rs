@@ -1,9 +1,13 @@
-use std::time::Duration;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
 
 use containerd_client::{
     services::v1::{
-        CreateTaskRequest, DeleteTaskRequest, GetRequest, KillRequest, ListTasksRequest,
-        ListTasksResponse, StartRequest, WaitRequest, WaitResponse,
+        CreateTaskRequest, DeleteProcessRequest, DeleteTaskRequest, ExecProcessRequest, GetRequest,
+        KillRequest, ListTasksRequest, ListTasksResponse, PauseTaskRequest, ResumeTaskRequest,
+        StartRequest, WaitRequest, WaitResponse,
     },
     types::{Mount, v1::Process},
     with_namespace,
@@ -14,6 +18,10 @@ use tonic::Request;
 
 use super::{ContainerdService, cni::Endpoint};
 
+static EXEC_SEQ: AtomicU64 = AtomicU64::new(0);
+
+const PROCESS_SPEC_TYPE_URL: &str = "types.containerd.io/opencontainers/runtime-spec/1/Process";
+
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Display)]
 pub enum TaskError {
     NotFound,
@@ -23,6 +31,10 @@ pub enum TaskError {
     Internal(String),
 }
 
+pub(super) fn next_exec_id() -> String {
+    format!("faasrs-exec-{}", EXEC_SEQ.fetch_add(1, Ordering::Relaxed))
+}
+
 impl From<tonic::Status> for TaskError {
     fn from(status: tonic::Status) -> Self {
         use tonic::Code::*;
@@ -120,6 +132,26 @@ impl ContainerdService {
         Ok(task)
     }
 
+    /// 冻结任务的所有进程
+    pub async fn pause_task(&self, endpoint: &Endpoint) -> Result<(), TaskError> {
+        let mut c = self.client.tasks();
+        let req = PauseTaskRequest {
+            container_id: endpoint.function_name.clone(),
+        };
+        c.pause(with_namespace!(req, endpoint.namespace)).await?;
+        Ok(())
+    }
+
+    /// 解冻被暂停的任务
+    pub async fn resume_task(&self, endpoint: &Endpoint) -> Result<(), TaskError> {
+        let mut c = self.client.tasks();
+        let req = ResumeTaskRequest {
+            container_id: endpoint.function_name.clone(),
+        };
+        c.resume(with_namespace!(req, endpoint.namespace)).await?;
+        Ok(())
+    }
+
     #[allow(dead_code)]
     async fn list_task_by_cid(&self, cid: &str, ns: &str) -> Result<ListTasksResponse, TaskError> {
         let mut c = self.client.tasks();
@@ -176,6 +208,95 @@ impl ContainerdService {
         Ok(resp)
     }
 
+    /// 以容器的进程配置运行 `args` 的进程规格
+    pub(super) async fn exec_process_spec(
+        &self,
+        endpoint: &Endpoint,
+        args: &[String],
+        terminal: bool,
+    ) -> Result<prost_types::Any, TaskError> {
+        let container = self
+            .load_container(endpoint)
+            .await
+            .map_err(|e| TaskError::Internal(e.to_string()))?;
+        let spec = container
+            .spec
+            .ok_or_else(|| TaskError::Internal("container has no spec".to_string()))?;
+        let mut spec: serde_json::Value = serde_json::from_slice(&spec.value)
+            .map_err(|e| TaskError::Internal(format!("invalid container spec: {}", e)))?;
+        let mut process = spec["process"].take();
+        process["args"] = serde_json::json!(args);
+        process["terminal"] = serde_json::Value::Bool(terminal);
+        Ok(prost_types::Any {
+            type_url: PROCESS_SPEC_TYPE_URL.to_string(),
+            value: process.to_string().into_bytes(),
+        })
+    }
+
+    /// 以容器的进程配置在任务中执行 `args`，返回退出码，超时后杀死该进程
+    pub async fn exec_and_wait(
+        &self,
+        endpoint: &Endpoint,
+        args: &[String],
+        timeout: Duration,
+    ) -> Result<u32, TaskError> {
+        let Endpoint {
+            function_name: cid,
+            namespace: ns,
+        } = endpoint;
+        let spec = self.exec_process_spec(endpoint, args, false).await?;
+
+        let exec_id = next_exec_id();
+        let mut c = self.client.tasks();
+        let req = ExecProcessRequest {
+            container_id: cid.clone(),
+            exec_id: exec_id.clone(),
+            spec: Some(spec),
+            ..Default::default()
+        };
+        c.exec(with_namespace!(req, ns)).await?;
+
+        let result = async {
+            let req = StartRequest {
+                container_id: cid.clone(),
+                exec_id: exec_id.clone(),
+            };
+            c.clone().start(with_namespace!(req, ns)).await?;
+            let req = WaitRequest {
+                container_id: cid.clone(),
+                exec_id: exec_id.clone(),
+            };
+            let mut waiter = c.clone();
+            let wait = waiter.wait(with_namespace!(req, ns));
+            match tokio::time::timeout(timeout, wait).await {
+                Ok(resp) => Ok(resp?.into_inner().exit_status),
+                Err(_) => {
+                    let req = KillRequest {
+                        container_id: cid.clone(),
+                        exec_id: exec_id.clone(),
+                        signal: 9,
+                        all: false,
+                    };
+                    c.clone().kill(with_namespace!(req, ns)).await?;
+                    Err(TaskError::Internal(format!(
+                        "exec {:?} timed out after {:?}",
+                        args, timeout
+                    )))
+                }
+            }
+        }
+        .await;
+
+        let req = DeleteProcessRequest {
+            container_id: cid.clone(),
+            exec_id,
+        };
+        if let Err(e) = c.delete_process(with_namespace!(req, ns)).await {
+            log::warn!("Failed to delete exec process of {}: {}", endpoint, e);
+        }
+        result
+    }
+
     /// 杀死并删除任务
     pub async fn kill_task_with_timeout(&self, endpoint: &Endpoint) -> Result<(), TaskError> {
         let Endpoint {
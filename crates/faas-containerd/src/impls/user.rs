@@ -0,0 +1,472 @@
+//! 解析函数进程的用户，规则与 docker 对镜像 `User` 字段的处理一致
+
+use std::{
+    ffi::CString,
+    fs::Metadata,
+    io::Read,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{FileTypeExt, MetadataExt, OpenOptionsExt},
+    },
+    path::{Component, Path, PathBuf},
+};
+
+use containerd_client::types::Mount;
+
+/// 容器进程的用户与组
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq)]
+pub struct ProcessUser {
+    pub uid: u32,
+    pub gid: u32,
+    /// 在 group 文件中列出了该用户的组
+    pub additional_gids: Vec<u32>,
+}
+
+/// 用户命名空间的映射：容器内从 0 开始的 `size` 个 ID 映射到宿主机从 `host_id` 开始的 ID
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct IdMapping {
+    pub host_id: u32,
+    pub size: u32,
+}
+
+impl IdMapping {
+    /// 环境变量 `USERNS_REMAP` 的格式为 `<host_id>:<size>`，未设置时不支持用户命名空间
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match std::env::var("USERNS_REMAP") {
+            Ok(remap) if !remap.trim().is_empty() => Self::parse(remap.trim()).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn parse(remap: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "invalid USERNS_REMAP '{}', expected <host_id>:<size>",
+                remap
+            )
+        };
+        let (host_id, size) = remap.split_once(':').ok_or_else(invalid)?;
+        let mapping = IdMapping {
+            host_id: host_id.parse().map_err(|_| invalid())?,
+            size: size.parse().map_err(|_| invalid())?,
+        };
+        // 宿主机的 root 不能出现在映射中
+        if mapping.host_id == 0 || mapping.size == 0 {
+            return Err(invalid());
+        }
+        mapping
+            .host_id
+            .checked_add(mapping.size)
+            .ok_or_else(invalid)?;
+        Ok(mapping)
+    }
+
+    /// 快照标签中使用的 `<container_id>:<host_id>:<size>`
+    pub fn label(&self) -> String {
+        format!("0:{}:{}", self.host_id, self.size)
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        id < self.size
+    }
+}
+
+struct Entry<'a> {
+    name: &'a str,
+    id: u32,
+    gid: Option<u32>,
+    members: Vec<&'a str>,
+}
+
+/// passwd 与 group 文件的共同格式：`name:x:id:...`，
+/// passwd 的第四列是主组，group 的第四列是成员
+fn entries(file: &str, passwd: bool) -> Vec<Entry<'_>> {
+    file.lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<_> = line.split(':').collect();
+            let id = fields.get(2)?.parse().ok()?;
+            let (gid, members) = if passwd {
+                (fields.get(3).and_then(|gid| gid.parse().ok()), Vec::new())
+            } else {
+                let members = fields
+                    .get(3)
+                    .map(|m| m.split(',').filter(|m| !m.is_empty()).collect())
+                    .unwrap_or_default();
+                (None, members)
+            };
+            Some(Entry {
+                name: fields[0],
+                id,
+                gid,
+                members,
+            })
+        })
+        .collect()
+}
+
+/// 解析 `user`、`uid`、`user:group` 或 `uid:gid`，名称需要在 rootfs 的 passwd 与 group 中查找
+pub fn resolve_user(
+    user: &str,
+    passwd: Option<&str>,
+    group: Option<&str>,
+) -> Result<ProcessUser, String> {
+    let user = user.trim();
+    if user.is_empty() {
+        return Ok(ProcessUser::default());
+    }
+    let (user_part, group_part) = match user.split_once(':') {
+        Some((u, g)) => (u, Some(g)),
+        None => (user, None),
+    };
+    let users = passwd.map(|p| entries(p, true)).unwrap_or_default();
+    let groups = group.map(|g| entries(g, false)).unwrap_or_default();
+
+    let (uid, name, primary_gid) = match user_part.parse::<u32>() {
+        Ok(uid) => {
+            let entry = users.iter().find(|e| e.id == uid);
+            (uid, entry.map(|e| e.name), entry.and_then(|e| e.gid))
+        }
+        Err(_) => {
+            if passwd.is_none() {
+                return Err(format!(
+                    "cannot look up user '{}' without /etc/passwd in the image",
+                    user_part
+                ));
+            }
+            let entry = users
+                .iter()
+                .find(|e| e.name == user_part)
+                .ok_or_else(|| format!("user '{}' not found in /etc/passwd", user_part))?;
+            (entry.id, Some(entry.name), entry.gid)
+        }
+    };
+
+    let gid = match group_part {
+        None => primary_gid.unwrap_or(0),
+        Some(g) => match g.parse::<u32>() {
+            Ok(gid) => gid,
+            Err(_) => {
+                groups
+                    .iter()
+                    .find(|e| e.name == g)
+                    .ok_or_else(|| format!("group '{}' not found in /etc/group", g))?
+                    .id
+            }
+        },
+    };
+
+    // 显式指定组时不再附加其它组
+    let mut additional_gids: Vec<u32> = match (group_part, name) {
+        (None, Some(name)) => groups
+            .iter()
+            .filter(|e| e.members.contains(&name) && e.id != gid)
+            .map(|e| e.id)
+            .collect(),
+        _ => Vec::new(),
+    };
+    additional_gids.dedup();
+    Ok(ProcessUser {
+        uid,
+        gid,
+        additional_gids,
+    })
+}
+
+/// 快照各层的目录，上层在前。只支持 overlay 与 bind 挂载
+fn rootfs_layers(mounts: &[Mount]) -> Vec<PathBuf> {
+    let mut layers = Vec::new();
+    for mount in mounts {
+        match mount.r#type.as_str() {
+            "bind" => layers.push(PathBuf::from(&mount.source)),
+            "overlay" => {
+                let option = |key: &str| {
+                    mount
+                        .options
+                        .iter()
+                        .find_map(|o| o.strip_prefix(key).map(str::to_string))
+                };
+                if let Some(upper) = option("upperdir=") {
+                    layers.push(PathBuf::from(upper));
+                }
+                if let Some(lower) = option("lowerdir=") {
+                    layers.extend(lower.split(':').map(PathBuf::from));
+                }
+            }
+            _ => {}
+        }
+    }
+    layers
+}
+
+/// 解析符号链接的最大次数，与内核的限制一致
+const MAX_SYMLINKS: usize = 40;
+const WHITEOUT_PREFIX: &str = ".wh.";
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+/// overlay 的删除标记是设备号为 0 的字符设备
+fn is_overlay_whiteout(meta: &Metadata) -> bool {
+    meta.file_type().is_char_device() && meta.rdev() == 0
+}
+
+/// 目录中的 `.wh..wh..opq` 或 overlay 的 opaque 属性会遮住下层的同名目录
+fn is_opaque(dir: &Path) -> bool {
+    if dir.join(OPAQUE_WHITEOUT).symlink_metadata().is_ok() {
+        return true;
+    }
+    let (Ok(path), Ok(name)) = (
+        CString::new(dir.as_os_str().as_bytes()),
+        CString::new("trusted.overlay.opaque"),
+    ) else {
+        return false;
+    };
+    let mut value = [0u8; 1];
+    // SAFETY: path 与 name 是以 NUL 结尾的有效字符串，value 的长度与传入的一致
+    let len = unsafe {
+        libc::lgetxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_mut_ptr().cast(),
+            value.len(),
+        )
+    };
+    len == 1 && value[0] == b'y'
+}
+
+/// 目录中名为 `name` 的项在该层被删除
+fn is_whited_out(dir: &Path, name: &str) -> bool {
+    dir.join(format!("{}{}", WHITEOUT_PREFIX, name))
+        .symlink_metadata()
+        .is_ok()
+        || dir
+            .join(name)
+            .symlink_metadata()
+            .is_ok_and(|meta| is_overlay_whiteout(&meta))
+}
+
+/// 把路径的各部分逆序压入待解析的栈中，栈顶是路径的第一部分
+fn push_components(pending: &mut Vec<String>, path: &Path) -> Option<()> {
+    for component in path.components().rev() {
+        match component {
+            Component::Normal(name) => pending.push(name.to_str()?.to_string()),
+            Component::ParentDir => pending.push("..".to_string()),
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    Some(())
+}
+
+/// 从最上层的真实目录开始向下合并，遇到非目录或 opaque 目录为止
+fn merge_dir<'a>(layers: &[&'a PathBuf], dir: &Path) -> Vec<&'a PathBuf> {
+    let mut merged = Vec::new();
+    for layer in layers {
+        let path = layer.join(dir);
+        if !path.symlink_metadata().is_ok_and(|meta| meta.is_dir()) {
+            break;
+        }
+        merged.push(*layer);
+        if is_opaque(&path) {
+            break;
+        }
+    }
+    merged
+}
+
+/// 像 overlay 合并各层一样逐级查找 `path`，返回其在宿主机上的路径。
+/// 每一级只进入各层中真实的目录，符号链接按 rootfs 的根解析，`..` 不会越过根，
+/// 因此结果总在某一层之内；被删除标记遮住的文件视为不存在
+fn lookup_rootfs_file(layers: &[PathBuf], path: &str) -> Option<PathBuf> {
+    let mut pending = Vec::new();
+    push_components(&mut pending, Path::new(path))?;
+
+    // 当前目录在 rootfs 中的路径，以及合并出从根到当前各级目录的层
+    let mut resolved = PathBuf::new();
+    let mut dirs: Vec<Vec<&PathBuf>> = vec![layers.iter().collect()];
+    let mut symlinks = 0;
+    while let Some(name) = pending.pop() {
+        if name == ".." {
+            if dirs.len() > 1 {
+                resolved.pop();
+                dirs.pop();
+            }
+            continue;
+        }
+        let visible = dirs.last()?;
+        let mut found = None;
+        for (i, layer) in visible.iter().enumerate() {
+            let dir = layer.join(&resolved);
+            if is_whited_out(&dir, &name) {
+                break;
+            }
+            if let Ok(meta) = dir.join(&name).symlink_metadata() {
+                found = Some((i, meta));
+                break;
+            }
+        }
+        let (i, meta) = found?;
+        let host_path = visible[i].join(&resolved).join(&name);
+        if meta.file_type().is_symlink() {
+            symlinks += 1;
+            if symlinks > MAX_SYMLINKS {
+                return None;
+            }
+            let target = std::fs::read_link(&host_path).ok()?;
+            if target.has_root() {
+                resolved = PathBuf::new();
+                dirs.truncate(1);
+            }
+            push_components(&mut pending, &target)?;
+        } else if meta.is_dir() {
+            resolved.push(&name);
+            let merged = merge_dir(&visible[i..], &resolved);
+            dirs.push(merged);
+        } else if pending.is_empty() && meta.is_file() {
+            return Some(host_path);
+        } else {
+            return None;
+        }
+    }
+    None
+}
+
+/// 从上到下在各层中读取文件，找不到时返回 None
+fn read_rootfs_file(layers: &[PathBuf], path: &str) -> Option<String> {
+    let path = lookup_rootfs_file(layers, path)?;
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)
+        .ok()?;
+    let mut content = String::new();
+    file.read_to_string(&mut content).ok()?;
+    Some(content)
+}
+
+/// 按镜像的 rootfs 解析用户，只有用户或组是名称时才读取 passwd 与 group
+pub fn resolve_in_rootfs(user: &str, mounts: &[Mount]) -> Result<ProcessUser, String> {
+    let layers = rootfs_layers(mounts);
+    let by_name = user
+        .split(':')
+        .any(|part| !part.is_empty() && part.parse::<u32>().is_err());
+    if layers.is_empty() && by_name {
+        return Err(format!(
+            "cannot inspect the rootfs to look up user '{}', use a numeric uid:gid",
+            user
+        ));
+    }
+    let passwd = read_rootfs_file(&layers, "etc/passwd");
+    let group = read_rootfs_file(&layers, "etc/group");
+    resolve_user(user, passwd.as_deref(), group.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWD: &str = "root:x:0:0:root:/root:/bin/sh\n# comment\napp:x:1000:1000::/home/app:/bin/sh\nnobody:x:65534:65534::/:/sbin/nologin\n";
+    const GROUP: &str = "root:x:0:\napp:x:1000:\ndocker:x:999:app,ops\nnogroup:x:65534:\n";
+
+    #[test]
+    fn test_resolve_user() {
+        let resolve = |user| resolve_user(user, Some(PASSWD), Some(GROUP));
+        assert_eq!(resolve("").unwrap(), ProcessUser::default());
+        assert_eq!(
+            resolve("app").unwrap(),
+            ProcessUser {
+                uid: 1000,
+                gid: 1000,
+                additional_gids: vec![999]
+            }
+        );
+        assert_eq!(resolve("1000").unwrap().gid, 1000);
+        assert_eq!(resolve("4242").unwrap().gid, 0);
+        assert_eq!(
+            resolve("app:nogroup").unwrap(),
+            ProcessUser {
+                uid: 1000,
+                gid: 65534,
+                additional_gids: vec![]
+            }
+        );
+        assert_eq!(resolve("nobody:10").unwrap().gid, 10);
+        assert!(resolve("ghost").is_err());
+        assert!(resolve("app:ghosts").is_err());
+
+        assert_eq!(resolve_user("1000:1000", None, None).unwrap().uid, 1000);
+        assert!(resolve_user("app", None, None).is_err());
+    }
+
+    #[test]
+    fn test_rootfs_layers() {
+        let mounts = [Mount {
+            r#type: "overlay".to_string(),
+            source: "overlay".to_string(),
+            target: String::new(),
+            options: vec![
+                "workdir=/s/3/work".to_string(),
+                "upperdir=/s/3/fs".to_string(),
+                "lowerdir=/s/2/fs:/s/1/fs".to_string(),
+            ],
+        }];
+        assert_eq!(
+            rootfs_layers(&mounts),
+            vec![
+                PathBuf::from("/s/3/fs"),
+                PathBuf::from("/s/2/fs"),
+                PathBuf::from("/s/1/fs")
+            ]
+        );
+        assert!(resolve_in_rootfs("app", &[]).is_err());
+        assert_eq!(resolve_in_rootfs("1:2", &[]).unwrap().gid, 2);
+    }
+
+    #[test]
+    fn test_read_rootfs_file() {
+        use std::os::unix::fs::symlink;
+
+        let root = std::env::temp_dir().join(format!("faasrs-rootfs-{}", std::process::id()));
+        let (upper, lower) = (root.join("upper"), root.join("lower"));
+        let outside = root.join("secret");
+        for dir in ["etc", "opt/app", "srv/data"] {
+            std::fs::create_dir_all(lower.join(dir)).unwrap();
+            std::fs::create_dir_all(upper.join(dir)).unwrap();
+        }
+        std::fs::write(&outside, "host").unwrap();
+        std::fs::write(lower.join("etc/passwd"), "lower").unwrap();
+        std::fs::write(lower.join("etc/group"), "group").unwrap();
+        std::fs::write(lower.join("opt/app/conf"), "lower").unwrap();
+        std::fs::write(lower.join("srv/data/conf"), "lower").unwrap();
+        std::fs::write(upper.join("etc/passwd"), "upper").unwrap();
+        std::fs::write(upper.join("etc/.wh.group"), "").unwrap();
+        std::fs::write(upper.join("srv/data/.wh..wh..opq"), "").unwrap();
+        symlink(&outside, upper.join("etc/shadow")).unwrap();
+        symlink("../../../../secret", upper.join("etc/escape")).unwrap();
+        symlink("/opt/app/conf", upper.join("etc/conf")).unwrap();
+        symlink("/etc", upper.join("etc/loop")).unwrap();
+
+        let layers = [upper.clone(), lower.clone()];
+        let read = |path| read_rootfs_file(&layers, path);
+        assert_eq!(read("etc/passwd").as_deref(), Some("upper"));
+        assert_eq!(read("/etc/../etc/./passwd").as_deref(), Some("upper"));
+        assert_eq!(read("etc/conf").as_deref(), Some("lower"));
+        assert_eq!(read("etc/loop/loop/passwd").as_deref(), Some("upper"));
+        assert_eq!(read("etc/group"), None);
+        assert_eq!(read("srv/data/conf"), None);
+        assert_eq!(read("etc/shadow"), None);
+        assert_eq!(read("etc/escape"), None);
+        assert_eq!(read("../secret"), None);
+        assert_eq!(read("etc"), None);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_id_mapping() {
+        let mapping = IdMapping::parse("100000:65536").unwrap();
+        assert_eq!(mapping.label(), "0:100000:65536");
+        assert!(mapping.contains(65535));
+        assert!(!mapping.contains(65536));
+        assert!(IdMapping::parse("0:65536").is_err());
+        assert!(IdMapping::parse("100000").is_err());
+        assert!(IdMapping::parse("4294967295:2").is_err());
+    }
+}
@@ -1,5 +1,4 @@
 #![feature(ip_from)]
-#![feature(slice_as_array)]
 pub mod consts;
 pub mod impls;
 pub mod provider;
@@ -1,13 +1,26 @@
-use faas_containerd::consts::DEFAULT_FAASDRS_DATA_DIR;
+use faas_containerd::{
+    consts::DEFAULT_FAASDRS_DATA_DIR,
+    provider::function::{image_gc::ImageGcPolicy, registry},
+};
 use tokio::signal::unix::{SignalKind, signal};
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
-    faas_containerd::init_backend().await;
+    if let Err(e) = registry::check_encryption_key() {
+        log::error!("Refusing to start: {}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = faas_containerd::init_backend().await {
+        log::error!("Failed to initialize backend: {}", e);
+        std::process::exit(1);
+    }
     let provider = faas_containerd::provider::ContainerdProvider::new(DEFAULT_FAASDRS_DATA_DIR);
 
+    tokio::spawn(provider.clone().run_image_gc(ImageGcPolicy::from_env()));
+    tokio::spawn(provider.clone().run_dns());
+
     // leave for shutdown containers (stop tasks)
     let _handle = provider.clone();
 
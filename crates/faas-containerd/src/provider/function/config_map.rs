@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use gateway::{
+    handlers::config_map::ConfigError,
+    types::{
+        config_map::{ConfigMap, ConfigMount},
+        operation::{OperationHandle, Phase},
+        volume::valid_volume_name,
+    },
+};
+
+use crate::{
+    consts,
+    impls::{backend, cni::Endpoint, function::VolumeBind},
+    provider::ContainerdProvider,
+};
+
+use super::{dns::function_run_dir, volume::validate_mount_path};
+
+/// 配置内容，键为 `<namespace>/<name>`
+const CONFIGS_TREE: &str = "configs";
+/// 记录各函数引用的配置，键为 `<namespace>/<function>`
+const CONFIG_MOUNTS_TREE: &str = "config_mounts";
+
+/// 检查配置是否被引用与记录引用需要互斥，同卷的处理
+static CONFIG_LOCK: Mutex<()> = Mutex::new(());
+
+fn config_key(namespace: &str, name: &str) -> String {
+    format!("{}/{}", namespace, name)
+}
+
+fn namespace_or_default(namespace: Option<String>) -> String {
+    namespace.unwrap_or(consts::DEFAULT_FUNCTION_NAMESPACE.to_string())
+}
+
+fn internal<E: std::fmt::Display>(e: E) -> ConfigError {
+    ConfigError::Internal(e.to_string())
+}
+
+/// 配置投射到宿主机上的目录，以只读方式绑定挂载进容器
+fn projected_dir(endpoint: &Endpoint, name: &str) -> std::io::Result<PathBuf> {
+    Ok(function_run_dir(endpoint)?.join("configs").join(name))
+}
+
+/// 先写临时文件再重命名，运行中的函数不会读到写了一半的文件；
+/// 删除已不在配置中的文件。临时文件以 `..` 开头，不会与配置的键冲突
+fn write_projected_files(dir: &Path, data: &HashMap<String, String>) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    for (key, value) in data {
+        let tmp = dir.join(format!("..{}.tmp", key));
+        std::fs::write(&tmp, value)?;
+        std::fs::rename(&tmp, dir.join(key))?;
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with("..") && !data.contains_key(name.as_ref()) {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+fn validate_config_mounts(mounts: &[ConfigMount]) -> Result<(), String> {
+    for (i, mount) in mounts.iter().enumerate() {
+        if !valid_volume_name(&mount.name) {
+            return Err(format!("invalid config name '{}'", mount.name));
+        }
+        let path = mount.mount_path();
+        validate_mount_path(&path)?;
+        if mounts[..i].iter().any(|other| {
+            other.name == mount.name || Path::new(&other.mount_path()) == Path::new(&path)
+        }) {
+            return Err(format!("config {} is mounted twice", mount.name));
+        }
+    }
+    Ok(())
+}
+
+impl ContainerdProvider {
+    fn configs_tree(&self) -> Result<sled::Tree, ConfigError> {
+        self.database.open_tree(CONFIGS_TREE).map_err(internal)
+    }
+
+    fn config_mounts_tree(&self) -> Result<sled::Tree, ConfigError> {
+        self.database
+            .open_tree(CONFIG_MOUNTS_TREE)
+            .map_err(internal)
+    }
+
+    fn load_config(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<HashMap<String, String>>, ConfigError> {
+        self.configs_tree()?
+            .get(config_key(namespace, name))
+            .map_err(internal)?
+            .map(|value| serde_json::from_slice(&value).map_err(internal))
+            .transpose()
+    }
+
+    /// 命名空间中引用了该配置的函数
+    fn config_users(&self, namespace: &str, name: &str) -> Result<Vec<Endpoint>, ConfigError> {
+        let mut users = Vec::new();
+        for entry in self
+            .config_mounts_tree()?
+            .scan_prefix(format!("{}/", namespace))
+        {
+            let (key, value) = entry.map_err(internal)?;
+            let mounts: Vec<ConfigMount> = serde_json::from_slice(&value).unwrap_or_default();
+            if mounts.iter().any(|m| m.name == name) {
+                let key = String::from_utf8_lossy(&key);
+                users.push(Endpoint::new(&key[namespace.len() + 1..], namespace));
+            }
+        }
+        Ok(users)
+    }
+
+    async fn check_config_namespace(&self, namespace: &str) -> Result<(), ConfigError> {
+        let exist = backend()
+            .namespace_exist(namespace)
+            .await
+            .map_err(internal)?;
+        if exist.is_none() {
+            return Err(ConfigError::NotFound(format!(
+                "namespace {} not found",
+                namespace
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn _create_config(&self, config: ConfigMap) -> Result<(), ConfigError> {
+        config.validate().map_err(ConfigError::Invalid)?;
+        let namespace = namespace_or_default(config.namespace);
+        self.check_config_namespace(&namespace).await?;
+        let value = serde_json::to_vec(&config.data).map_err(internal)?;
+        self.configs_tree()?
+            .compare_and_swap(
+                config_key(&namespace, &config.name),
+                None as Option<&[u8]>,
+                Some(value),
+            )
+            .map_err(internal)?
+            .map_err(|_| {
+                ConfigError::AlreadyExists(format!(
+                    "config {} already exists in namespace {}",
+                    config.name, namespace
+                ))
+            })?;
+        log::info!("Config {} created in namespace {}", config.name, namespace);
+        Ok(())
+    }
+
+    pub(crate) async fn _update_config(
+        &self,
+        config: ConfigMap,
+    ) -> Result<Vec<String>, ConfigError> {
+        config.validate().map_err(ConfigError::Invalid)?;
+        let namespace = namespace_or_default(config.namespace);
+        let key = config_key(&namespace, &config.name);
+        let users = {
+            let _lock = CONFIG_LOCK.lock().unwrap();
+            let tree = self.configs_tree()?;
+            if !tree.contains_key(&key).map_err(internal)? {
+                return Err(ConfigError::NotFound(format!(
+                    "config {} not found in namespace {}",
+                    config.name, namespace
+                )));
+            }
+            tree.insert(&key, serde_json::to_vec(&config.data).map_err(internal)?)
+                .map_err(internal)?;
+            self.config_users(&namespace, &config.name)?
+        };
+
+        for endpoint in &users {
+            projected_dir(endpoint, &config.name)
+                .and_then(|dir| write_projected_files(&dir, &config.data))
+                .map_err(|e| {
+                    ConfigError::Internal(format!(
+                        "failed to refresh config files of {}: {}",
+                        endpoint, e
+                    ))
+                })?;
+        }
+        log::info!(
+            "Config {} updated in namespace {}, used by {} functions",
+            config.name,
+            namespace,
+            users.len()
+        );
+        Ok(users.into_iter().map(|e| e.function_name).collect())
+    }
+
+    pub(crate) async fn _restart_config_users(
+        &self,
+        namespace: Option<String>,
+        name: String,
+        operation: OperationHandle,
+    ) -> Result<(), ConfigError> {
+        let namespace = namespace_or_default(namespace);
+        let users = self.config_users(&namespace, &name)?;
+        operation.set_phase(Phase::Starting);
+        self.rolling_restart(&users)
+            .await
+            .map_err(ConfigError::Internal)?;
+        log::info!(
+            "Functions using config {} restarted in namespace {}",
+            name,
+            namespace
+        );
+        Ok(())
+    }
+
+    pub(crate) async fn _get_config(
+        &self,
+        namespace: Option<String>,
+        name: String,
+    ) -> Result<ConfigMap, ConfigError> {
+        let namespace = namespace_or_default(namespace);
+        let data = self.load_config(&namespace, &name)?.ok_or_else(|| {
+            ConfigError::NotFound(format!(
+                "config {} not found in namespace {}",
+                name, namespace
+            ))
+        })?;
+        Ok(ConfigMap {
+            name,
+            namespace: Some(namespace),
+            data,
+        })
+    }
+
+    pub(crate) async fn _list_configs(
+        &self,
+        namespace: Option<String>,
+    ) -> Result<Vec<ConfigMap>, ConfigError> {
+        let namespace = namespace_or_default(namespace);
+        self.check_config_namespace(&namespace).await?;
+        self.configs_tree()?
+            .scan_prefix(format!("{}/", namespace))
+            .map(|entry| {
+                let (key, value) = entry.map_err(internal)?;
+                let key = String::from_utf8_lossy(&key);
+                Ok(ConfigMap {
+                    name: key[namespace.len() + 1..].to_string(),
+                    namespace: Some(namespace.clone()),
+                    data: serde_json::from_slice(&value).map_err(internal)?,
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) async fn _delete_config(
+        &self,
+        namespace: Option<String>,
+        name: String,
+    ) -> Result<(), ConfigError> {
+        let namespace = namespace_or_default(namespace);
+        let _lock = CONFIG_LOCK.lock().unwrap();
+        let users = self.config_users(&namespace, &name)?;
+        if !users.is_empty() {
+            return Err(ConfigError::InUse(format!(
+                "config {} is used by {}",
+                name,
+                users
+                    .iter()
+                    .map(|e| e.function_name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+        self.configs_tree()?
+            .remove(config_key(&namespace, &name))
+            .map_err(internal)?
+            .ok_or_else(|| {
+                ConfigError::NotFound(format!(
+                    "config {} not found in namespace {}",
+                    name, namespace
+                ))
+            })?;
+        log::info!("Config {} deleted from namespace {}", name, namespace);
+        Ok(())
+    }
+
+    /// 写出部署引用的配置文件并记录引用，返回容器中要绑定挂载的目录
+    pub(crate) fn project_configs(
+        &self,
+        endpoint: &Endpoint,
+        mounts: &[ConfigMount],
+    ) -> Result<Vec<VolumeBind>, ConfigError> {
+        validate_config_mounts(mounts).map_err(ConfigError::Invalid)?;
+        let _lock = CONFIG_LOCK.lock().unwrap();
+        let mut binds = Vec::with_capacity(mounts.len());
+        for mount in mounts {
+            let data = self
+                .load_config(&endpoint.namespace, &mount.name)?
+                .ok_or_else(|| {
+                    ConfigError::Invalid(format!(
+                        "config {} not found in namespace {}",
+                        mount.name, endpoint.namespace
+                    ))
+                })?;
+            let dir = projected_dir(endpoint, &mount.name).map_err(internal)?;
+            write_projected_files(&dir, &data).map_err(internal)?;
+            binds.push(VolumeBind {
+                source: dir,
+                destination: mount.mount_path(),
+                read_only: true,
+            });
+        }
+
+        let tree = self.config_mounts_tree()?;
+        let key = config_key(&endpoint.namespace, &endpoint.function_name);
+        if mounts.is_empty() {
+            tree.remove(key)
+        } else {
+            tree.insert(key, serde_json::to_vec(mounts).map_err(internal)?)
+        }
+        .map_err(internal)?;
+        Ok(binds)
+    }
+
+    pub(crate) fn unproject_configs(&self, endpoint: &Endpoint) {
+        if let Err(e) = self.config_mounts_tree().and_then(|tree| {
+            tree.remove(config_key(&endpoint.namespace, &endpoint.function_name))
+                .map_err(internal)
+        }) {
+            log::warn!("Failed to forget configs of {}: {}", endpoint, e);
+        }
+    }
+
+    /// 函数引用的配置，没有引用时返回 None
+    pub(crate) fn config_mounts(&self, endpoint: &Endpoint) -> Option<Vec<ConfigMount>> {
+        let value = self
+            .config_mounts_tree()
+            .ok()?
+            .get(config_key(&endpoint.namespace, &endpoint.function_name))
+            .ok()??;
+        serde_json::from_slice(&value).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_projected_files() {
+        let dir = std::env::temp_dir().join(format!("faasrs-config-{}", std::process::id()));
+        let mut data = HashMap::from([
+            ("a.json".to_string(), "{}".to_string()),
+            ("b.txt".to_string(), "hello".to_string()),
+        ]);
+        write_projected_files(&dir, &data).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("b.txt")).unwrap(), "hello");
+
+        data.remove("a.json");
+        data.insert("b.txt".to_string(), "world".to_string());
+        write_projected_files(&dir, &data).unwrap();
+        assert!(!dir.join("a.json").exists());
+        assert_eq!(std::fs::read_to_string(dir.join("b.txt")).unwrap(), "world");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_config_mounts() {
+        let mount = |name: &str, path: Option<&str>| ConfigMount {
+            name: name.to_string(),
+            mount_path: path.map(str::to_string),
+        };
+        assert!(validate_config_mounts(&[mount("a", None), mount("b", Some("/etc/app"))]).is_ok());
+        assert!(validate_config_mounts(&[mount("a", None), mount("a", Some("/etc/app"))]).is_err());
+        assert!(
+            validate_config_mounts(&[mount("a", Some("/etc/app")), mount("b", Some("/etc/app"))])
+                .is_err()
+        );
+        assert!(validate_config_mounts(&[mount("a", Some("/etc/hosts"))]).is_err());
+    }
+}
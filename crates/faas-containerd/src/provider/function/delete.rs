@@ -1,3 +1,5 @@
+use containerd_client::types::v1::Status as TaskStatus;
+
 use crate::impls::cni::Endpoint;
 use crate::impls::{backend, cni, task::TaskError};
 use crate::provider::ContainerdProvider;
@@ -7,26 +9,58 @@ use gateway::types::function::Query;
 impl ContainerdProvider {
     pub(crate) async fn _delete(&self, function: Query) -> Result<(), DeleteError> {
         let endpoint: Endpoint = function.into();
+        self.teardown(&endpoint).await?;
+        self.forget_revisions(&endpoint);
+        Ok(())
+    }
+
+    /// 清理函数的任务、容器、快照与网络，保留其镜像版本记录以便更新时回滚
+    pub(crate) async fn teardown(&self, endpoint: &Endpoint) -> Result<(), DeleteError> {
         log::trace!("Deleting function: {:?}", endpoint);
+        self.stop_probe(endpoint);
+        self.unregister_network(endpoint);
+        self.unmount_volumes(endpoint);
+        self.unproject_configs(endpoint);
+        // 冻结的进程收不到 SIGTERM，先解冻
+        if let Ok(task) = backend().get_task(endpoint).await
+            && let Ok(TaskStatus::Paused | TaskStatus::Pausing) = TaskStatus::try_from(task.status)
+        {
+            backend()
+                .resume_task(endpoint)
+                .await
+                .map_err(|e| DeleteError::Internal(format!("failed to resume task: {}", e)))?;
+        }
+        self.forget_paused(endpoint);
+        self.forget_deployment(endpoint);
 
-        match backend().kill_task_with_timeout(&endpoint).await {
+        // 容器删除后就无法得知快照所在的 snapshotter，先读出来
+        let snapshotter = match backend().load_container(endpoint).await {
+            Ok(container) => container.snapshotter,
+            Err(_) => backend().snapshotter.clone(),
+        };
+
+        match backend().kill_task_with_timeout(endpoint).await {
             Ok(_) => {}
             Err(e) => match e {
                 TaskError::NotFound => {}
                 _ => return Err(DeleteError::Internal(format!("kill task failed: {:?}", e))),
             },
         };
-        let del_ctr_err = backend().delete_container(&endpoint).await.map_err(|e| {
+        let del_ctr_err = backend().delete_container(endpoint).await.map_err(|e| {
             log::error!("Failed to delete container: {:?}", e);
             e
         });
 
-        let rm_snap_err = backend().remove_snapshot(&endpoint).await.map_err(|e| {
-            log::error!("Failed to remove snapshot: {:?}", e);
-            e
-        });
+        let rm_snap_err = backend()
+            .remove_snapshot(endpoint, &snapshotter)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to remove snapshot: {:?}", e);
+                e
+            });
 
-        let del_net_err = cni::cni_impl::delete_cni_network(endpoint);
+        let del_net_err = cni::cni_impl::delete_cni_network(endpoint.clone());
+        super::dns::remove_run_dir(endpoint);
 
         if del_ctr_err.is_ok() && rm_snap_err.is_ok() && del_net_err.is_ok() {
             Ok(())
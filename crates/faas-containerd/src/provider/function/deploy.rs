@@ -1,28 +1,311 @@
-use crate::impls::cni;
-use crate::impls::{self, backend, function::ContainerStaticMetadata};
+use crate::consts;
+use crate::impls::cni::{self, policy::FunctionNetwork};
+use crate::impls::{self, backend, function::ContainerStaticMetadata, user::IdMapping};
 use crate::provider::ContainerdProvider;
-use gateway::handlers::function::DeployError;
-use gateway::types::function::Deployment;
+use gateway::handlers::{config_map::ConfigError, function::DeployError, volume::VolumeError};
+use gateway::types::{
+    annotation,
+    function::Deployment,
+    image::PullPolicy,
+    limits::ProcessLimits,
+    operation::{OperationHandle, Phase},
+    probe::ReadinessProbe,
+    security::{SeccompProfile, SecurityOptions},
+};
 use scopeguard::{ScopeGuard, guard};
 
+use super::resolve::Upstream;
+
+pub(super) fn upstream_port_annotation(config: &Deployment) -> Result<Option<u16>, DeployError> {
+    config
+        .annotations
+        .as_ref()
+        .and_then(|annotations| annotations.get(annotation::PORT))
+        .map(|port| {
+            port.parse::<u16>()
+                .ok()
+                .filter(|port| *port != 0)
+                .ok_or_else(|| DeployError::Invalid(format!("invalid port '{}'", port)))
+        })
+        .transpose()
+}
+
+/// 注解开启用户命名空间时，使用 `USERNS_REMAP` 配置的映射
+fn user_namespace(config: &Deployment) -> Result<Option<IdMapping>, DeployError> {
+    let enabled = match config
+        .annotations
+        .as_ref()
+        .and_then(|annotations| annotations.get(annotation::USER_NAMESPACE))
+    {
+        None => false,
+        Some(v) => v.parse::<bool>().map_err(|_| {
+            DeployError::Invalid(format!(
+                "invalid {} '{}', expected true or false",
+                annotation::USER_NAMESPACE,
+                v
+            ))
+        })?,
+    };
+    if !enabled {
+        return Ok(None);
+    }
+    match IdMapping::from_env().map_err(DeployError::InternalError)? {
+        Some(mapping) => Ok(Some(mapping)),
+        None => Err(DeployError::Invalid(
+            "user namespaces are not enabled on this provider, set USERNS_REMAP".to_string(),
+        )),
+    }
+}
+
+/// 未通过注解指定时使用 `IMAGE_PULL_POLICY`，在拉取时读取以便修改默认值后对已有函数生效
+pub(super) fn pull_policy(config: &Deployment) -> Result<PullPolicy, DeployError> {
+    match config
+        .annotations
+        .as_ref()
+        .and_then(|annotations| annotations.get(annotation::PULL_POLICY))
+    {
+        Some(policy) => policy.parse().map_err(DeployError::Invalid),
+        None => PullPolicy::from_env().map_err(DeployError::InternalError),
+    }
+}
+
 impl ContainerdProvider {
-    pub(crate) async fn _deploy(&self, config: Deployment) -> Result<(), DeployError> {
-        let metadata = ContainerStaticMetadata::from(config);
+    pub(crate) async fn _deploy(
+        &self,
+        config: Deployment,
+        operation: &OperationHandle,
+    ) -> Result<(), DeployError> {
+        let policy = pull_policy(&config)?;
+        let port_annotation = upstream_port_annotation(&config)?;
+        let userns = user_namespace(&config)?;
+        let annotations = config.annotations.clone();
+        let volume_mounts = config.volumes.clone().unwrap_or_default();
+        let config_mounts = config.configs.clone().unwrap_or_default();
+        let deployment =
+            serde_json::to_vec(&config).map_err(|e| DeployError::InternalError(e.to_string()))?;
+        let mut metadata = ContainerStaticMetadata::from(config);
         log::trace!("Deploying function: {:?}", metadata);
 
+        let credentials = self
+            .registry_credentials(&metadata.endpoint.namespace)
+            .map_err(|e| {
+                log::error!("Failed to load registry credentials: {}", e);
+                DeployError::InternalError(e.to_string())
+            })?;
+
+        let image = metadata.image.clone();
+        let image_err = move |img_err: impls::oci_image::ImageError| {
+            use impls::oci_image::ImageError;
+            log::error!("Image '{}' fetch failed: {}", image, img_err);
+            match img_err {
+                ImageError::ImageNotFound(e) => DeployError::Invalid(e.to_string()),
+                ImageError::Unauthorized(_) => DeployError::Invalid(img_err.to_string()),
+                _ => DeployError::InternalError(img_err.to_string()),
+            }
+        };
+
         // not going to check the conflict of namespace, should be handled by containerd backend
-        backend()
-            .prepare_image(&metadata.image, &metadata.endpoint.namespace, true)
+        let mut target = backend()
+            .prepare_image(
+                &metadata.image,
+                &metadata.endpoint.namespace,
+                policy,
+                credentials.as_ref(),
+                operation,
+            )
+            .await
+            .map_err(&image_err)?;
+        // 拉取完成到登记之间镜像可能已被回收，登记后确认镜像仍在，否则重新准备。
+        // 登记在失败返回时随之取消
+        let retry_policy = match policy {
+            PullPolicy::Never => PullPolicy::Never,
+            _ => PullPolicy::IfNotPresent,
+        };
+        let pending_pin = loop {
+            let pin = self
+                .hold_image(&metadata.endpoint.namespace, &target.digest)
+                .await;
+            let current = backend()
+                .prepare_image(
+                    &metadata.image,
+                    &metadata.endpoint.namespace,
+                    PullPolicy::Never,
+                    None,
+                    operation,
+                )
+                .await;
+            if current.is_ok_and(|current| current.digest == target.digest) {
+                break pin;
+            }
+            drop(pin);
+            target = backend()
+                .prepare_image(
+                    &metadata.image,
+                    &metadata.endpoint.namespace,
+                    retry_policy,
+                    credentials.as_ref(),
+                    operation,
+                )
+                .await
+                .map_err(&image_err)?;
+        };
+        log::trace!(
+            "Image '{}' fetch ok, digest {}",
+            metadata.image,
+            target.digest
+        );
+
+        // pin this revision to the resolved digest, replicas and restarts never follow a moved tag
+        metadata.labels.insert(
+            consts::LABEL_IMAGE_DIGEST.to_string(),
+            target.digest.clone(),
+        );
+        metadata.image = backend()
+            .pin_image(&metadata.image, target, &metadata.endpoint.namespace)
+            .await
+            .map_err(&image_err)?;
+        self.record_revision(&metadata.endpoint, &metadata.image);
+        drop(pending_pin);
+
+        let runtime_config = backend()
+            .runtime_config(&metadata.image, &metadata.endpoint.namespace)
+            .await
+            .map_err(|e| DeployError::InternalError(e.to_string()))?;
+        // 端口注解优先，其次是镜像唯一暴露的 TCP 端口
+        let port = port_annotation
+            .or_else(|| runtime_config.exposed_tcp_port())
+            .unwrap_or(consts::DEFAULT_UPSTREAM_PORT);
+        let probe = ReadinessProbe::from_annotations(annotations.as_ref(), port)
+            .map_err(DeployError::Invalid)?;
+        if let Some(probe) = &probe {
+            metadata.labels.insert(
+                consts::LABEL_READINESS_PROBE.to_string(),
+                serde_json::to_string(probe)
+                    .map_err(|e| DeployError::InternalError(e.to_string()))?,
+            );
+        }
+
+        metadata.security = SecurityOptions::from_annotations(annotations.as_ref())
+            .map_err(DeployError::Invalid)?;
+        impls::spec::capabilities(&metadata.security).map_err(DeployError::Invalid)?;
+        if let SeccompProfile::Named(name) = &metadata.security.seccomp {
+            impls::seccomp::load_profile(name).map_err(DeployError::Invalid)?;
+        }
+
+        let namespace_limits = self
+            .namespace_limits(&metadata.endpoint.namespace)
+            .await
+            .map_err(DeployError::Invalid)?;
+        metadata.limits = ProcessLimits::resolve(annotations.as_ref(), &namespace_limits)
+            .map_err(DeployError::Invalid)?;
+        metadata.labels.insert(
+            consts::LABEL_PROCESS_LIMITS.to_string(),
+            serde_json::to_string(&metadata.limits)
+                .map_err(|e| DeployError::InternalError(e.to_string()))?,
+        );
+
+        metadata.runtime_class = self
+            .select_runtime_class(&metadata.endpoint.namespace, annotations.as_ref())
+            .await
+            .map_err(DeployError::Invalid)?;
+        metadata.labels.insert(
+            consts::LABEL_RUNTIME_CLASS.to_string(),
+            metadata.runtime_class.clone(),
+        );
+        metadata.snapshotter = backend().snapshotter.clone();
+
+        let network_policy = self
+            .namespace_policy(&metadata.endpoint.namespace)
             .await
-            .map_err(|img_err| {
-                use impls::oci_image::ImageError;
-                log::error!("Image '{}' fetch failed: {}", &metadata.image, img_err);
-                match img_err {
-                    ImageError::ImageNotFound(e) => DeployError::Invalid(e.to_string()),
-                    _ => DeployError::InternalError(img_err.to_string()),
-                }
+            .map_err(|e| DeployError::Invalid(e.to_string()))?;
+        let function_network = FunctionNetwork::from_annotations(annotations.as_ref())
+            .map_err(|e| DeployError::Invalid(e.to_string()))?;
+        if function_network.has_bandwidth_limit()
+            && !cni::cni_impl::supports_capability("bandwidth")
+        {
+            return Err(DeployError::Invalid(
+                "bandwidth limits require the bandwidth CNI plugin in CNI_EXTRA_PLUGINS"
+                    .to_string(),
+            ));
+        }
+
+        let extra_hosts =
+            super::hosts::extra_hosts(annotations.as_ref()).map_err(DeployError::Invalid)?;
+        let hostname = super::hosts::hostname(
+            &metadata.endpoint.function_name,
+            &super::hosts::replica_id(&metadata.endpoint),
+        );
+        // 部署失败时删除已写入的 hostname、hosts 与 resolv.conf
+        let run_dir_defer = guard((), |()| super::dns::remove_run_dir(&metadata.endpoint));
+        let hostname_file = super::hosts::write_hostname(&metadata.endpoint, &hostname)
+            .map_err(|e| DeployError::InternalError(format!("failed to write hostname: {}", e)))?;
+        metadata
+            .bind_files
+            .insert("/etc/hostname".to_string(), hostname_file);
+        // /etc/hosts 在网络创建后写入
+        let hosts_file = super::hosts::hosts_path(&metadata.endpoint)
+            .map_err(|e| DeployError::InternalError(e.to_string()))?;
+        metadata
+            .bind_files
+            .insert("/etc/hosts".to_string(), hosts_file);
+        metadata.hostname = Some(hostname.clone());
+        if let Some(resolv_conf) =
+            super::dns::write_resolv_conf(&metadata.endpoint).map_err(|e| {
+                DeployError::InternalError(format!("failed to write resolv.conf: {}", e))
+            })?
+        {
+            metadata
+                .bind_files
+                .insert("/etc/resolv.conf".to_string(), resolv_conf);
+        }
+
+        metadata.volumes = self
+            .mount_volumes(&metadata.endpoint, &volume_mounts)
+            .map_err(|e| match e {
+                VolumeError::Invalid(e) => DeployError::Invalid(e),
+                _ => DeployError::InternalError(e.to_string()),
             })?;
-        log::trace!("Image '{}' fetch ok", &metadata.image);
+        let volume_defer = guard((), |()| self.unmount_volumes(&metadata.endpoint));
+        let config_binds = self
+            .project_configs(&metadata.endpoint, &config_mounts)
+            .map_err(|e| match e {
+                ConfigError::Invalid(e) => DeployError::Invalid(e),
+                _ => DeployError::InternalError(e.to_string()),
+            })?;
+        let config_defer = guard((), |()| self.unproject_configs(&metadata.endpoint));
+        metadata.volumes.extend(config_binds);
+        super::volume::check_destinations(&metadata.volumes).map_err(DeployError::Invalid)?;
+
+        operation.set_phase(Phase::Creating);
+        // 先准备快照，按镜像的 passwd 与 group 解析用户
+        let mounts = backend().prepare_snapshot(&metadata).await.map_err(|e| {
+            log::error!("Failed to prepare snapshot: {:?}", e);
+            DeployError::InternalError(e.to_string())
+        })?;
+
+        let snapshot_defer = scopeguard::guard((), |()| {
+            log::trace!("Cleaning up snapshot");
+            let endpoint = metadata.endpoint.clone();
+            let snapshotter = metadata.snapshotter.clone();
+            tokio::spawn(async move { backend().remove_snapshot(&endpoint, &snapshotter).await });
+        });
+
+        let user = annotations
+            .as_ref()
+            .and_then(|annotations| annotations.get(annotation::USER))
+            .unwrap_or(&runtime_config.user);
+        metadata.user =
+            impls::user::resolve_in_rootfs(user, &mounts).map_err(DeployError::Invalid)?;
+        metadata.userns = userns;
+        if let Some(mapping) = userns {
+            let user = &metadata.user;
+            if !mapping.contains(user.uid) || !mapping.contains(user.gid) {
+                return Err(DeployError::Invalid(format!(
+                    "user {}:{} is outside the {} ids mapped into the user namespace",
+                    user.uid, user.gid, mapping.size
+                )));
+            }
+        }
 
         let _ = backend().create_container(&metadata).await.map_err(|e| {
             log::error!("Failed to create container: {:?}", e);
@@ -35,26 +318,44 @@ impl ContainerdProvider {
         });
 
         // let network = CNIEndpoint::new(&metadata.container_id, &metadata.namespace)?;
-        let (ip, netns) = cni::cni_impl::create_cni_network(&metadata.endpoint).map_err(|e| {
+        operation.set_phase(Phase::Networking);
+        let (ips, netns) = cni::cni_impl::create_cni_network(
+            &metadata.endpoint,
+            &function_network.capability_args(),
+        )
+        .map_err(|e| {
             log::error!("Failed to create CNI network: {}", e);
             DeployError::InternalError(e.to_string())
         })?;
 
         let netns_defer = guard(netns, |ns| ns.remove().unwrap());
+        let addrs: Vec<_> = ips.iter().map(|ip| ip.address()).collect();
 
-        // TODO: Use ostree-ext
-        // let img_conf = BACKEND.get().unwrap().get_runtime_config(&metadata.image).unwrap();
-        let mounts = backend().prepare_snapshot(&metadata).await.map_err(|e| {
-            log::error!("Failed to prepare snapshot: {:?}", e);
+        // 任务启动前下发隔离规则
+        self.register_network(
+            &metadata.endpoint,
+            addrs.clone(),
+            network_policy,
+            function_network,
+        )
+        .map_err(|e| {
+            log::error!("Failed to apply network policy: {}", e);
             DeployError::InternalError(e.to_string())
         })?;
+        let policy_defer = guard((), |()| self.unregister_network(&metadata.endpoint));
 
-        let snapshot_defer = scopeguard::guard((), |()| {
-            log::trace!("Cleaning up snapshot");
-            let endpoint = metadata.endpoint.clone();
-            tokio::spawn(async move { backend().remove_snapshot(&endpoint).await });
-        });
+        super::hosts::write_hosts(
+            &metadata.endpoint,
+            &super::hosts::hosts_file(
+                &hostname,
+                &metadata.endpoint.function_name,
+                &addrs,
+                &extra_hosts,
+            ),
+        )
+        .map_err(|e| DeployError::InternalError(format!("failed to write hosts: {}", e)))?;
 
+        operation.set_phase(Phase::Starting);
         backend().new_task(mounts, &metadata.endpoint).await?;
 
         let task_defer = scopeguard::guard((), |()| {
@@ -62,34 +363,28 @@ impl ContainerdProvider {
             tokio::spawn(async move { backend().kill_task_with_timeout(&endpoint).await });
         });
 
-        use std::net::IpAddr::*;
-
-        match ip.address() {
-            V4(addr) => {
-                if let Err(err) = self
-                    .database
-                    .insert(metadata.endpoint.to_string(), &addr.octets())
-                {
-                    log::error!("Failed to insert into database: {:?}", err);
-                    return Err(DeployError::InternalError(err.to_string()));
-                }
-            }
-            V6(addr) => {
-                if let Err(err) = self
-                    .database
-                    .insert(metadata.endpoint.to_string(), &addr.octets())
-                {
-                    log::error!("Failed to insert into database: {:?}", err);
-                    return Err(DeployError::InternalError(err.to_string()));
-                }
-            }
+        let upstream = Upstream { addrs, port };
+        if let Err(err) = self
+            .database
+            .insert(metadata.endpoint.to_string(), upstream.encode())
+        {
+            log::error!("Failed to insert into database: {:?}", err);
+            return Err(DeployError::InternalError(err.to_string()));
         }
 
+        let addr = upstream.pick(cni::cni_impl::preferred_family());
+        self.start_probe(&metadata.endpoint, addr.ip(), probe, false);
+        self.record_deployment(&metadata.endpoint, &deployment);
+
         log::info!("container was created successfully: {}", metadata.endpoint);
         ScopeGuard::into_inner(snapshot_defer);
         ScopeGuard::into_inner(netns_defer);
+        ScopeGuard::into_inner(policy_defer);
+        ScopeGuard::into_inner(volume_defer);
+        ScopeGuard::into_inner(config_defer);
         ScopeGuard::into_inner(container_defer);
         ScopeGuard::into_inner(task_defer);
+        ScopeGuard::into_inner(run_dir_defer);
         Ok(())
     }
 }
@@ -0,0 +1,249 @@
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+
+use crate::consts;
+use crate::impls::cni::{
+    self, Endpoint,
+    dns::{self, Question},
+};
+use crate::provider::ContainerdProvider;
+
+use super::resolve::Upstream;
+
+/// 副本地址在更新后会变化，应答只缓存很短的时间
+const ANSWER_TTL: u32 = 5;
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(2);
+/// 网桥在第一个函数部署时才创建，之前绑定网关地址会失败
+const BIND_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_PACKET_LEN: usize = 4096;
+
+/// 内置 DNS 的配置，来自环境变量：
+/// - `INTERNAL_DNS`: 为 off 时不启动，函数沿用镜像自带的 resolv.conf
+/// - `DNS_GATEWAY_NAME`: 解析到网关地址的名称
+/// - `DNS_UPSTREAM`: 逗号分隔的上游服务器，默认读取宿主机的 /etc/resolv.conf
+#[derive(Debug, Clone)]
+pub struct DnsConfig {
+    pub enabled: bool,
+    pub gateway_name: String,
+    pub upstream: Vec<SocketAddr>,
+}
+
+impl DnsConfig {
+    pub fn from_env() -> Self {
+        let gateways = dns::gateway_addrs(cni::cni_impl::subnets());
+        let upstream = match std::env::var("DNS_UPSTREAM") {
+            Ok(servers) => dns::upstream_servers(
+                &servers
+                    .split(',')
+                    .map(|s| format!("nameserver {}\n", s.trim()))
+                    .collect::<String>(),
+                &gateways,
+            ),
+            Err(_) => std::fs::read_to_string("/etc/resolv.conf")
+                .map(|conf| dns::upstream_servers(&conf, &gateways))
+                .unwrap_or_else(|e| {
+                    log::warn!("Failed to read /etc/resolv.conf: {}", e);
+                    Vec::new()
+                }),
+        };
+        DnsConfig {
+            enabled: std::env::var("INTERNAL_DNS").map_or(true, |v| v != "off"),
+            gateway_name: std::env::var("DNS_GATEWAY_NAME")
+                .unwrap_or_else(|_| "gateway".to_string())
+                .to_ascii_lowercase(),
+            upstream,
+        }
+    }
+}
+
+static DNS_CONFIG: LazyLock<DnsConfig> = LazyLock::new(DnsConfig::from_env);
+
+/// 为函数生成的文件所在的目录。名称会拼接进宿主机的路径，含有 `/` 或 `.` 的名称一律拒绝
+pub(crate) fn function_run_dir(endpoint: &Endpoint) -> std::io::Result<PathBuf> {
+    for name in [&endpoint.namespace, &endpoint.function_name] {
+        if name.is_empty() || name.contains(['/', '.', '\0']) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid name '{}' for a run directory", name),
+            ));
+        }
+    }
+    Ok(PathBuf::from(consts::FUNCTION_RUN_DIR)
+        .join(&endpoint.namespace)
+        .join(&endpoint.function_name))
+}
+
+/// 写入指向内置 DNS 的 resolv.conf，未启用时返回 None
+pub(crate) fn write_resolv_conf(endpoint: &Endpoint) -> std::io::Result<Option<PathBuf>> {
+    if !DNS_CONFIG.enabled {
+        return Ok(None);
+    }
+    let dir = function_run_dir(endpoint)?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("resolv.conf");
+    let nameservers = dns::gateway_addrs(cni::cni_impl::subnets());
+    std::fs::write(&path, dns::resolv_conf(&nameservers, &endpoint.namespace))?;
+    Ok(Some(path))
+}
+
+/// 删除为函数生成的文件
+pub(crate) fn remove_run_dir(endpoint: &Endpoint) {
+    // 无效的名称不会有目录
+    let Ok(dir) = function_run_dir(endpoint) else {
+        return;
+    };
+    if let Err(e) = std::fs::remove_dir_all(&dir)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        log::warn!("Failed to remove {}: {}", dir.display(), e);
+    }
+}
+
+impl ContainerdProvider {
+    /// 本地解析：`<function>.<namespace>` 解析为副本地址，网关名解析为网桥地址。
+    /// 返回 None 的名称转发给上游，其他命名空间中不可见的函数返回空结果
+    fn lookup(&self, name: &str, src: IpAddr) -> Option<Vec<IpAddr>> {
+        if name == DNS_CONFIG.gateway_name {
+            return Some(dns::gateway_addrs(cni::cni_impl::subnets()));
+        }
+        let (function, namespace) = name.split_once('.')?;
+        if namespace.contains('.') {
+            return None;
+        }
+        let endpoint = Endpoint::new(function, namespace);
+        // 不是已部署的函数时可能是外部域名，如 example.com，交给上游解析
+        let value = self
+            .database
+            .get(endpoint.to_string())
+            .map_err(|e| log::warn!("Failed to look up {}: {}", endpoint, e))
+            .ok()??;
+        if !self.name_visible(src, &endpoint) {
+            return Some(Vec::new());
+        }
+        let upstream = Upstream::decode(&value)?;
+        Some(
+            upstream
+                .addrs
+                .into_iter()
+                .filter(|addr| cni::cni_impl::check_network_exists(*addr))
+                .collect(),
+        )
+    }
+
+    /// 在每个网关地址上提供 DNS 服务，直到进程退出
+    pub async fn run_dns(self: Arc<Self>) {
+        if !DNS_CONFIG.enabled {
+            log::info!("Internal DNS is disabled");
+            return;
+        }
+        if DNS_CONFIG.upstream.is_empty() {
+            log::warn!("No upstream DNS server, only function names will resolve");
+        }
+        let servers = dns::gateway_addrs(cni::cni_impl::subnets())
+            .into_iter()
+            .map(|addr| tokio::spawn(self.clone().serve_dns(addr)))
+            .collect::<Vec<_>>();
+        futures::future::join_all(servers).await;
+    }
+
+    async fn serve_dns(self: Arc<Self>, addr: IpAddr) {
+        let bind_addr = SocketAddr::new(addr, dns::DNS_PORT);
+        let socket = loop {
+            match UdpSocket::bind(bind_addr).await {
+                Ok(socket) => break Arc::new(socket),
+                Err(e) if e.kind() == std::io::ErrorKind::AddrNotAvailable => {
+                    log::debug!("Waiting for bridge address {}", addr);
+                    tokio::time::sleep(BIND_RETRY_INTERVAL).await;
+                }
+                Err(e) => {
+                    log::error!("Failed to bind DNS on {}: {}", bind_addr, e);
+                    return;
+                }
+            }
+        };
+        log::info!("Internal DNS listening on {}", bind_addr);
+
+        let mut buf = vec![0u8; MAX_PACKET_LEN];
+        loop {
+            let (len, src) = match socket.recv_from(&mut buf).await {
+                Ok(recv) => recv,
+                Err(e) => {
+                    log::warn!("DNS receive failed on {}: {}", bind_addr, e);
+                    continue;
+                }
+            };
+            // 只为函数网络提供服务，不做开放的递归解析
+            if !cni::cni_impl::subnets()
+                .iter()
+                .any(|subnet| subnet.contains(&src.ip()))
+            {
+                continue;
+            }
+            let packet = &buf[..len];
+            let Some(question) = dns::parse_query(packet) else {
+                continue;
+            };
+            let local = question
+                .is_address_query()
+                .then(|| self.lookup(&question.name, src.ip()))
+                .flatten();
+            match local {
+                Some(addrs) => {
+                    // 没有可用的副本时返回 NXDOMAIN，只是缺少该地址族的地址时返回空应答
+                    let rcode = if addrs.is_empty() {
+                        dns::RCODE_NXDOMAIN
+                    } else {
+                        dns::RCODE_NOERROR
+                    };
+                    let resp = dns::response(packet, &question, rcode, &addrs, ANSWER_TTL);
+                    let _ = socket.send_to(&resp, src).await;
+                }
+                None => {
+                    tokio::spawn(forward(socket.clone(), packet.to_vec(), question, src));
+                }
+            }
+        }
+    }
+}
+
+/// 依次尝试上游服务器，原样转回第一个应答，全部失败时返回 SERVFAIL。
+/// 被截断的应答也原样转回，内置 DNS 不提供 TCP 服务
+async fn forward(socket: Arc<UdpSocket>, query: Vec<u8>, question: Question, src: SocketAddr) {
+    for server in &DNS_CONFIG.upstream {
+        match exchange(&query, *server).await {
+            Ok(resp) => {
+                let _ = socket.send_to(&resp, src).await;
+                return;
+            }
+            Err(e) => log::debug!("DNS upstream {} failed: {}", server, e),
+        }
+    }
+    let resp = dns::response(&query, &question, dns::RCODE_SERVFAIL, &[], 0);
+    let _ = socket.send_to(&resp, src).await;
+}
+
+async fn exchange(query: &[u8], server: SocketAddr) -> std::io::Result<Vec<u8>> {
+    let local: SocketAddr = if server.is_ipv4() {
+        "0.0.0.0:0".parse().unwrap()
+    } else {
+        "[::]:0".parse().unwrap()
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(server).await?;
+    socket.send(query).await?;
+    let mut buf = vec![0u8; MAX_PACKET_LEN];
+    loop {
+        let len = tokio::time::timeout(FORWARD_TIMEOUT, socket.recv(&mut buf))
+            .await
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out"))??;
+        // 忽略 ID 不匹配的报文
+        if len >= 2 && buf[..2] == query[..2] {
+            buf.truncate(len);
+            return Ok(buf);
+        }
+    }
+}
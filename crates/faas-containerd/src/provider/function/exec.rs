@@ -0,0 +1,54 @@
+use gateway::{
+    handlers::exec::ExecError,
+    types::{
+        exec::{ExecRequest, ExecSession},
+        function::Query,
+    },
+};
+
+use crate::{
+    impls::{backend, cni::Endpoint, container::ContainerError, task::TaskError},
+    provider::ContainerdProvider,
+};
+
+impl ContainerdProvider {
+    pub(crate) async fn _exec(
+        &self,
+        function: Query,
+        request: ExecRequest,
+        session: ExecSession,
+    ) -> Result<u32, ExecError> {
+        let endpoint = Endpoint::from(function);
+        if request.command.is_empty() {
+            return Err(ExecError::Invalid("command is empty".to_string()));
+        }
+        backend()
+            .load_container(&endpoint)
+            .await
+            .map_err(|e| match e {
+                ContainerError::NotFound => {
+                    ExecError::NotFound(format!("function {} not found", endpoint))
+                }
+                _ => ExecError::Internal(e.to_string()),
+            })?;
+        // 冻结的任务中无法启动进程
+        if self.is_paused(&endpoint) {
+            return Err(ExecError::Conflict(format!(
+                "function {} is paused",
+                endpoint
+            )));
+        }
+
+        log::info!("Exec {:?} in function {}", request.command, endpoint);
+        backend()
+            .exec_interactive(&endpoint, request, session)
+            .await
+            .map_err(|e| match e {
+                TaskError::NotFound => {
+                    ExecError::Conflict(format!("function {} is not running", endpoint))
+                }
+                TaskError::InvalidArgument => ExecError::Invalid(e.to_string()),
+                _ => ExecError::Internal(e.to_string()),
+            })
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use sha2::{Digest, Sha256};
+
+use gateway::types::annotation;
+
+use crate::impls::cni::Endpoint;
+
+use super::dns::function_run_dir;
+
+/// 主机名的最大长度 (RFC 1123 单个标签)
+const MAX_HOSTNAME_LEN: usize = 63;
+/// 副本 ID 的长度，与 Kubernetes Pod 名称的后缀一样取 5 个字符
+const REPLICA_ID_LEN: usize = 5;
+
+/// 每次部署生成一个新的副本 ID
+pub(crate) fn replica_id(endpoint: &Endpoint) -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let digest = Sha256::digest(format!("{}/{}", endpoint, nanos));
+    hex::encode(digest)[..REPLICA_ID_LEN].to_string()
+}
+
+/// `<function>-<replica>`，函数名中不能出现在主机名里的字符替换为 `-`，过长时截断函数名
+pub(crate) fn hostname(function: &str, replica: &str) -> String {
+    let max_prefix = MAX_HOSTNAME_LEN - replica.len() - 1;
+    let prefix: String = function
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .take(max_prefix)
+        .collect();
+    let prefix = prefix.trim_matches('-');
+    if prefix.is_empty() {
+        return replica.to_string();
+    }
+    format!("{}-{}", prefix, replica)
+}
+
+fn valid_host_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= MAX_HOSTNAME_LEN
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// 额外的主机记录，逗号分隔的 `<name>:<ip>`，与 `docker run --add-host` 一致
+pub(crate) fn extra_hosts(
+    annotations: Option<&HashMap<String, String>>,
+) -> Result<Vec<(String, IpAddr)>, String> {
+    let Some(hosts) = annotations.and_then(|a| a.get(annotation::EXTRA_HOSTS)) else {
+        return Ok(Vec::new());
+    };
+    hosts
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (name, ip) = entry
+                .split_once(':')
+                .ok_or_else(|| format!("invalid host entry '{}', expected <name>:<ip>", entry))?;
+            if !valid_host_name(name) {
+                return Err(format!("invalid host name '{}'", name));
+            }
+            let ip = ip
+                .trim_matches(|c| c == '[' || c == ']')
+                .parse::<IpAddr>()
+                .map_err(|e| format!("invalid address in host entry '{}': {}", entry, e))?;
+            Ok((name.to_string(), ip))
+        })
+        .collect()
+}
+
+/// 容器内的 /etc/hosts：回环地址、副本自身的地址与额外的记录
+pub(crate) fn hosts_file(
+    hostname: &str,
+    function: &str,
+    addrs: &[IpAddr],
+    extra: &[(String, IpAddr)],
+) -> String {
+    let mut hosts =
+        String::from("127.0.0.1\tlocalhost\n::1\tlocalhost ip6-localhost ip6-loopback\n");
+    for addr in addrs {
+        hosts.push_str(&format!("{}\t{} {}\n", addr, hostname, function));
+    }
+    for (name, addr) in extra {
+        hosts.push_str(&format!("{}\t{}\n", addr, name));
+    }
+    hosts
+}
+
+pub(crate) fn hosts_path(endpoint: &Endpoint) -> std::io::Result<PathBuf> {
+    Ok(function_run_dir(endpoint)?.join("hosts"))
+}
+
+/// 写入 /etc/hostname，容器创建前调用
+pub(crate) fn write_hostname(endpoint: &Endpoint, hostname: &str) -> std::io::Result<PathBuf> {
+    let dir = function_run_dir(endpoint)?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("hostname");
+    std::fs::write(&path, format!("{}\n", hostname))?;
+    Ok(path)
+}
+
+/// 写入 /etc/hosts，副本地址在网络创建后才确定，因此在任务启动前调用
+pub(crate) fn write_hosts(endpoint: &Endpoint, content: &str) -> std::io::Result<()> {
+    let path = hosts_path(endpoint)?;
+    std::fs::create_dir_all(path.parent().unwrap_or(&path))?;
+    std::fs::write(path, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hostname() {
+        assert_eq!(hostname("Hello_World", "a1b2c"), "hello-world-a1b2c");
+        assert_eq!(hostname("_", "a1b2c"), "a1b2c");
+        let long = hostname(&"f".repeat(100), "a1b2c");
+        assert_eq!(long.len(), MAX_HOSTNAME_LEN);
+        assert!(long.ends_with("f-a1b2c"));
+
+        let endpoint = Endpoint::new("hello", "default");
+        assert_eq!(replica_id(&endpoint).len(), REPLICA_ID_LEN);
+    }
+
+    #[test]
+    fn test_run_dir_stays_inside() {
+        assert!(function_run_dir(&Endpoint::new("hello", "default")).is_ok());
+        for (function, namespace) in [
+            ("../../../../etc", "default"),
+            ("hello", ".."),
+            ("a/b", "c"),
+        ] {
+            assert!(function_run_dir(&Endpoint::new(function, namespace)).is_err());
+            assert!(write_hostname(&Endpoint::new(function, namespace), "x").is_err());
+        }
+    }
+
+    #[test]
+    fn test_hosts_file() {
+        let annotations = HashMap::from([(
+            annotation::EXTRA_HOSTS.to_string(),
+            "db.internal:10.0.0.5, cache:[fd00::5]".to_string(),
+        )]);
+        let extra = extra_hosts(Some(&annotations)).unwrap();
+        assert_eq!(extra[1], ("cache".to_string(), "fd00::5".parse().unwrap()));
+
+        let hosts = hosts_file(
+            "hello-a1b2c",
+            "hello",
+            &["10.66.0.5".parse().unwrap()],
+            &extra,
+        );
+        assert!(hosts.starts_with("127.0.0.1\tlocalhost\n"));
+        assert!(hosts.contains("10.66.0.5\thello-a1b2c hello\n"));
+        assert!(hosts.ends_with("10.0.0.5\tdb.internal\nfd00::5\tcache\n"));
+
+        for invalid in ["db", "-db:10.0.0.5", "db:10.0.0", "d b:10.0.0.5"] {
+            let annotations =
+                HashMap::from([(annotation::EXTRA_HOSTS.to_string(), invalid.to_string())]);
+            assert!(extra_hosts(Some(&annotations)).is_err(), "{}", invalid);
+        }
+    }
+}
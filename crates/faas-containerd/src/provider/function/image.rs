@@ -0,0 +1,84 @@
+use bytes::Bytes;
+use gateway::{handlers::image::ImageStoreError, types::image::ImageSummary};
+
+use crate::{
+    impls::{backend, namespace::NamespaceServiceError, oci_image::ImageError},
+    provider::ContainerdProvider,
+};
+
+impl ContainerdProvider {
+    pub(crate) async fn _import_image(
+        &self,
+        namespace: String,
+        archive: tokio::sync::mpsc::Receiver<Bytes>,
+    ) -> Result<Vec<String>, ImageStoreError> {
+        self.check_namespace_exists(&namespace).await?;
+        let names = backend()
+            .import_image(&namespace, archive)
+            .await
+            .map_err(|e| match e {
+                // containerd rejects archives it cannot parse
+                ImageError::ImageImportFailed(msg) => ImageStoreError::Invalid(msg),
+                _ => ImageStoreError::Internal(e.to_string()),
+            })?;
+        if names.is_empty() {
+            return Err(ImageStoreError::Invalid(
+                "the archive did not contain any image for this platform".to_string(),
+            ));
+        }
+        log::info!(
+            "Images {} imported into namespace {}",
+            names.join(", "),
+            namespace
+        );
+        Ok(names)
+    }
+
+    pub(crate) async fn _list_images(
+        &self,
+        namespace: String,
+    ) -> Result<Vec<ImageSummary>, ImageStoreError> {
+        self.check_namespace_exists(&namespace).await?;
+        let images = backend()
+            .list_images(&namespace)
+            .await
+            .map_err(|e| ImageStoreError::Internal(e.to_string()))?;
+        let users = self.image_users(&namespace).await?;
+
+        let mut summaries = Vec::with_capacity(images.len());
+        for image in images {
+            let Some(target) = image.target else {
+                continue;
+            };
+            let size = backend()
+                .image_size(&target, &namespace)
+                .await
+                .map_err(|e| log::warn!("Failed to get size of image {}: {}", image.name, e))
+                .ok();
+            summaries.push(ImageSummary {
+                name: image.name,
+                functions: users
+                    .get(&target.digest)
+                    .map(|f| f.iter().cloned().collect())
+                    .unwrap_or_default(),
+                digest: target.digest,
+                created_at: image.created_at.map(|t| t.to_string()),
+                size,
+            });
+        }
+        Ok(summaries)
+    }
+
+    pub(crate) async fn check_namespace_exists(
+        &self,
+        namespace: &str,
+    ) -> Result<(), ImageStoreError> {
+        match backend().namespace_exist(namespace).await {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) | Err(NamespaceServiceError::NotFound) => Err(ImageStoreError::NotFound(
+                format!("namespace {} not found", namespace),
+            )),
+            Err(e) => Err(ImageStoreError::Internal(e.to_string())),
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use gateway::handlers::image::ImageStoreError;
+
+use crate::{
+    consts,
+    impls::{backend, cni::Endpoint},
+    provider::ContainerdProvider,
+};
+
+const IMAGE_REVISIONS_TREE: &str = "image_revisions";
+
+/// 每个函数保留的镜像版本数（含当前版本），这些镜像不会被回收以便回滚
+const MAX_REVISION_HISTORY: usize = 3;
+
+/// 后台镜像回收策略
+#[derive(Debug, Clone)]
+pub struct ImageGcPolicy {
+    /// 回收间隔，为零时不启动后台回收
+    pub interval: Duration,
+    /// 每个命名空间保留最近创建的未使用镜像数
+    pub keep_unused: usize,
+    /// 每个命名空间镜像占用的磁盘上限（字节）
+    pub max_disk_bytes: Option<i64>,
+}
+
+impl ImageGcPolicy {
+    pub fn from_env() -> Self {
+        let interval = std::env::var("IMAGE_GC_INTERVAL")
+            .unwrap_or_else(|_| "3600".to_string()) // 默认1小时
+            .parse::<u64>()
+            .expect("IMAGE_GC_INTERVAL must be an integer");
+        let keep_unused = std::env::var("IMAGE_GC_KEEP_UNUSED")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse::<usize>()
+            .expect("IMAGE_GC_KEEP_UNUSED must be an integer");
+        let max_disk_bytes = std::env::var("IMAGE_GC_MAX_DISK_BYTES").ok().map(|v| {
+            v.parse::<i64>()
+                .expect("IMAGE_GC_MAX_DISK_BYTES must be an integer")
+        });
+        Self {
+            interval: Duration::from_secs(interval),
+            keep_unused,
+            max_disk_bytes,
+        }
+    }
+}
+
+/// 指向同一 digest 的未使用镜像，只有这些名字全部删除后内容才会被回收
+#[derive(Debug, Default)]
+struct Candidate {
+    names: Vec<String>,
+    created_at: i64,
+    size: i64,
+}
+
+/// 先保留最新的 `keep_unused` 个未使用镜像，超出磁盘预算时再从其中最旧的开始删除。
+/// 不同镜像共享的层会被重复计算，因此预算是偏保守的
+fn select_for_removal(
+    mut unused: Vec<Candidate>,
+    used_bytes: i64,
+    keep_unused: usize,
+    max_disk_bytes: Option<i64>,
+) -> Vec<String> {
+    unused.sort_by_key(|c| std::cmp::Reverse(c.created_at));
+    let mut removed = unused.split_off(keep_unused.min(unused.len()));
+    if let Some(budget) = max_disk_bytes {
+        let mut total = used_bytes + unused.iter().map(|c| c.size).sum::<i64>();
+        while total > budget {
+            let Some(oldest) = unused.pop() else {
+                break;
+            };
+            total -= oldest.size;
+            removed.push(oldest);
+        }
+    }
+    removed.into_iter().flat_map(|c| c.names).collect()
+}
+
+/// 部署中尚未写入版本记录的镜像，drop 时取消登记
+pub(crate) struct PendingPin<'a> {
+    provider: &'a ContainerdProvider,
+    key: (String, String),
+}
+
+impl Drop for PendingPin<'_> {
+    fn drop(&mut self) {
+        let mut pins = self.provider.pending_pins.lock().unwrap();
+        if let Some(count) = pins.get_mut(&self.key) {
+            *count -= 1;
+            if *count == 0 {
+                pins.remove(&self.key);
+            }
+        }
+    }
+}
+
+fn revision_key(endpoint: &Endpoint) -> String {
+    format!("{}/{}", endpoint.namespace, endpoint.function_name)
+}
+
+impl ContainerdProvider {
+    /// 登记部署刚解析出的镜像，直到写入版本记录前都不会被当作未使用的镜像回收。
+    /// 只在登记时短暂持有读锁，登记完成前开始的回收可能已经删除了该镜像，调用方需要再确认
+    pub(crate) async fn hold_image(&self, namespace: &str, digest: &str) -> PendingPin<'_> {
+        let _gc = self.image_gc_lock.read().await;
+        let key = (namespace.to_string(), digest.to_string());
+        *self
+            .pending_pins
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default() += 1;
+        PendingPin {
+            provider: self,
+            key,
+        }
+    }
+
+    /// 记录函数部署的镜像版本（已固定到 digest 的引用），最新的在前
+    pub(crate) fn record_revision(&self, endpoint: &Endpoint, pinned: &str) {
+        let result = self.revisions_tree().and_then(|tree| {
+            tree.update_and_fetch(revision_key(endpoint), |old| {
+                let mut refs: Vec<String> = old
+                    .and_then(|v| serde_json::from_slice(v).ok())
+                    .unwrap_or_default();
+                refs.retain(|r| r != pinned);
+                refs.insert(0, pinned.to_string());
+                refs.truncate(MAX_REVISION_HISTORY);
+                serde_json::to_vec(&refs).ok()
+            })
+        });
+        if let Err(e) = result {
+            log::warn!("Failed to record image revision of {}: {}", endpoint, e);
+        }
+    }
+
+    pub(crate) fn forget_revisions(&self, endpoint: &Endpoint) {
+        if let Err(e) = self
+            .revisions_tree()
+            .and_then(|tree| tree.remove(revision_key(endpoint)))
+        {
+            log::warn!("Failed to remove image revisions of {}: {}", endpoint, e);
+        }
+    }
+
+    /// 镜像 digest 到使用它的函数的映射，包括运行中的容器和保留的历史版本
+    pub(crate) async fn image_users(
+        &self,
+        namespace: &str,
+    ) -> Result<BTreeMap<String, BTreeSet<String>>, ImageStoreError> {
+        let containers = backend().list_container(namespace).await.map_err(|e| {
+            log::error!("Failed to list containers of {}: {:?}", namespace, e);
+            ImageStoreError::Internal(e.to_string())
+        })?;
+        let mut users: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for container in containers {
+            if let Some(digest) = container.labels.get(consts::LABEL_IMAGE_DIGEST) {
+                users
+                    .entry(digest.clone())
+                    .or_default()
+                    .insert(container.id);
+            }
+        }
+
+        let prefix = format!("{}/", namespace);
+        let tree = self
+            .revisions_tree()
+            .map_err(|e| ImageStoreError::Internal(e.to_string()))?;
+        for entry in tree.scan_prefix(&prefix) {
+            let (key, value) = entry.map_err(|e| ImageStoreError::Internal(e.to_string()))?;
+            let function = String::from_utf8_lossy(&key[prefix.len()..]).into_owned();
+            let refs: Vec<String> = serde_json::from_slice(&value).unwrap_or_default();
+            for pinned in refs {
+                if let Some((_, digest)) = pinned.rsplit_once('@') {
+                    users
+                        .entry(digest.to_string())
+                        .or_default()
+                        .insert(function.clone());
+                }
+            }
+        }
+        Ok(users)
+    }
+
+    pub(crate) async fn _prune_images(
+        &self,
+        namespace: Option<String>,
+    ) -> Result<Vec<String>, ImageStoreError> {
+        let namespaces = match namespace {
+            Some(namespace) => {
+                self.check_namespace_exists(&namespace).await?;
+                vec![namespace]
+            }
+            None => backend()
+                .list_namespace()
+                .await
+                .map_err(|e| ImageStoreError::Internal(e.to_string()))?
+                .into_iter()
+                .map(|ns| ns.name)
+                .collect(),
+        };
+
+        let mut removed = Vec::new();
+        for namespace in namespaces {
+            removed.extend(self.collect_images(&namespace, 0, None).await?);
+        }
+        log::info!("Pruned {} unused images", removed.len());
+        Ok(removed)
+    }
+
+    /// 回收一个命名空间中的镜像，只处理带有 [`consts::LABEL_MANAGED`] 标签且未被使用的镜像
+    async fn collect_images(
+        &self,
+        namespace: &str,
+        keep_unused: usize,
+        max_disk_bytes: Option<i64>,
+    ) -> Result<Vec<String>, ImageStoreError> {
+        let _gc = self.image_gc_lock.write().await;
+        let images = backend()
+            .list_images(namespace)
+            .await
+            .map_err(|e| ImageStoreError::Internal(e.to_string()))?;
+        let users = self.image_users(namespace).await?;
+
+        // 被函数使用、正在部署或同时被非托管镜像引用的 digest 都不能回收
+        let mut protected: BTreeSet<String> = users.into_keys().collect();
+        protected.extend(
+            self.pending_pins
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|(ns, _)| ns == namespace)
+                .map(|(_, digest)| digest.clone()),
+        );
+        for image in &images {
+            if let Some(target) = &image.target
+                && !image.labels.contains_key(consts::LABEL_MANAGED)
+            {
+                protected.insert(target.digest.clone());
+            }
+        }
+
+        let mut used: BTreeMap<String, i64> = BTreeMap::new();
+        let mut unused: BTreeMap<String, Candidate> = BTreeMap::new();
+        for image in images {
+            let Some(target) = image.target else {
+                continue;
+            };
+            let is_protected = protected.contains(&target.digest);
+            if is_protected && !image.labels.contains_key(consts::LABEL_MANAGED) {
+                continue;
+            }
+            let counted = used.contains_key(&target.digest) || unused.contains_key(&target.digest);
+            let size = if max_disk_bytes.is_some() && !counted {
+                backend()
+                    .image_size(&target, namespace)
+                    .await
+                    .unwrap_or_else(|e| {
+                        log::warn!("Failed to get size of image {}: {}", image.name, e);
+                        0
+                    })
+            } else {
+                0
+            };
+            if is_protected {
+                *used.entry(target.digest).or_default() += size;
+            } else {
+                let candidate = unused.entry(target.digest).or_default();
+                candidate.names.push(image.name);
+                candidate.size += size;
+                candidate.created_at = candidate
+                    .created_at
+                    .max(image.created_at.map_or(0, |t| t.seconds));
+            }
+        }
+
+        let selected = select_for_removal(
+            unused.into_values().collect(),
+            used.values().sum(),
+            keep_unused,
+            max_disk_bytes,
+        );
+        let mut removed = Vec::with_capacity(selected.len());
+        for name in selected {
+            match backend().delete_image(&name, namespace, true).await {
+                Ok(()) => removed.push(name),
+                Err(e) => log::warn!("Failed to remove image {}: {}", name, e),
+            }
+        }
+        Ok(removed)
+    }
+
+    /// 按策略周期性回收全部命名空间中的镜像
+    pub async fn run_image_gc(self: Arc<Self>, policy: ImageGcPolicy) {
+        if policy.interval.is_zero() {
+            log::info!("Image GC is disabled");
+            return;
+        }
+        let mut ticker = tokio::time::interval(policy.interval);
+        // 第一次 tick 会立即返回，跳过它以免拖慢启动
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let namespaces = match backend().list_namespace().await {
+                Ok(namespaces) => namespaces,
+                Err(e) => {
+                    log::warn!("Image GC failed to list namespaces: {}", e);
+                    continue;
+                }
+            };
+            for ns in namespaces {
+                match self
+                    .collect_images(&ns.name, policy.keep_unused, policy.max_disk_bytes)
+                    .await
+                {
+                    Ok(removed) if !removed.is_empty() => {
+                        log::info!("Image GC removed from {}: {:?}", ns.name, removed)
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Image GC failed in namespace {}: {}", ns.name, e),
+                }
+            }
+        }
+    }
+
+    fn revisions_tree(&self) -> sled::Result<sled::Tree> {
+        self.database.open_tree(IMAGE_REVISIONS_TREE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str, created_at: i64, size: i64) -> Candidate {
+        Candidate {
+            names: vec![name.to_string()],
+            created_at,
+            size,
+        }
+    }
+
+    #[test]
+    fn test_select_for_removal() {
+        let unused = || {
+            vec![
+                candidate("old", 1, 100),
+                candidate("newest", 3, 100),
+                candidate("middle", 2, 100),
+            ]
+        };
+        assert_eq!(select_for_removal(unused(), 0, 0, None).len(), 3);
+        assert_eq!(select_for_removal(unused(), 0, 2, None), vec!["old"]);
+        assert!(select_for_removal(unused(), 0, 5, None).is_empty());
+        // 预算包括正在使用的镜像
+        assert_eq!(
+            select_for_removal(unused(), 150, 3, Some(300)),
+            vec!["old", "middle"]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use containerd_client::types::v1::Status as TaskStatus;
+use gateway::{handlers::function::LifecycleError, types::function::Query};
+
+use crate::{
+    impls::{backend, cni::Endpoint, container::ContainerError, task::TaskError},
+    provider::ContainerdProvider,
+};
+
+/// 记录被暂停的函数，键为 `<namespace>/<function>`，网关重启后仍能拒绝对它们的调用
+const PAUSED_TREE: &str = "paused_functions";
+
+fn paused_key(endpoint: &Endpoint) -> String {
+    format!("{}/{}", endpoint.namespace, endpoint.function_name)
+}
+
+fn internal(e: impl std::fmt::Display) -> LifecycleError {
+    LifecycleError::Internal(e.to_string())
+}
+
+impl ContainerdProvider {
+    fn paused_tree(&self) -> Result<sled::Tree, LifecycleError> {
+        self.database.open_tree(PAUSED_TREE).map_err(internal)
+    }
+
+    pub(crate) fn is_paused(&self, endpoint: &Endpoint) -> bool {
+        self.paused_tree()
+            .and_then(|tree| tree.contains_key(paused_key(endpoint)).map_err(internal))
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to read paused state of {}: {}", endpoint, e);
+                false
+            })
+    }
+
+    pub(crate) fn forget_paused(&self, endpoint: &Endpoint) {
+        if let Err(e) = self
+            .paused_tree()
+            .and_then(|tree| tree.remove(paused_key(endpoint)).map_err(internal))
+        {
+            log::warn!("Failed to forget paused state of {}: {}", endpoint, e);
+        }
+    }
+
+    /// 函数任务的状态，容器存在但没有任务时返回 None
+    async fn task_status(&self, endpoint: &Endpoint) -> Result<Option<TaskStatus>, LifecycleError> {
+        backend()
+            .load_container(endpoint)
+            .await
+            .map_err(|e| match e {
+                ContainerError::NotFound => {
+                    LifecycleError::NotFound(format!("function {} not found", endpoint))
+                }
+                _ => internal(e),
+            })?;
+        match backend().get_task(endpoint).await {
+            Ok(task) => Ok(Some(
+                TaskStatus::try_from(task.status).unwrap_or(TaskStatus::Unknown),
+            )),
+            Err(TaskError::NotFound) => Ok(None),
+            Err(e) => Err(internal(e)),
+        }
+    }
+
+    pub(crate) async fn _pause(&self, function: Query) -> Result<(), LifecycleError> {
+        let endpoint = Endpoint::from(function);
+        match self.task_status(&endpoint).await? {
+            Some(TaskStatus::Running) => {}
+            Some(TaskStatus::Paused | TaskStatus::Pausing) => {
+                return Err(LifecycleError::Conflict(format!(
+                    "function {} is already paused",
+                    endpoint
+                )));
+            }
+            _ => {
+                return Err(LifecycleError::Conflict(format!(
+                    "function {} is not running",
+                    endpoint
+                )));
+            }
+        }
+
+        // 先记录再冻结，冻结期间到达的调用不会被转发给函数
+        let tree = self.paused_tree()?;
+        tree.insert(paused_key(&endpoint), &[]).map_err(internal)?;
+        if let Err(e) = backend().pause_task(&endpoint).await {
+            let _ = tree.remove(paused_key(&endpoint));
+            return Err(internal(format!("failed to pause task: {}", e)));
+        }
+        // 冻结的进程无法回应探测，恢复后重新探测
+        self.stop_probe(&endpoint);
+        log::info!("Function {} paused", endpoint);
+        Ok(())
+    }
+
+    pub(crate) async fn _resume(&self, function: Query) -> Result<(), LifecycleError> {
+        let endpoint = Endpoint::from(function);
+        match self.task_status(&endpoint).await? {
+            Some(TaskStatus::Paused | TaskStatus::Pausing) => {}
+            _ => {
+                return Err(LifecycleError::Conflict(format!(
+                    "function {} is not paused",
+                    endpoint
+                )));
+            }
+        }
+        backend()
+            .resume_task(&endpoint)
+            .await
+            .map_err(|e| internal(format!("failed to resume task: {}", e)))?;
+        self.forget_paused(&endpoint);
+        log::info!("Function {} resumed", endpoint);
+        Ok(())
+    }
+
+    pub(crate) async fn _restart(&self, function: Query) -> Result<(), LifecycleError> {
+        let endpoint = Endpoint::from(function);
+        // 冻结的进程收不到 SIGTERM，先解冻
+        if let Some(TaskStatus::Paused | TaskStatus::Pausing) = self.task_status(&endpoint).await? {
+            backend()
+                .resume_task(&endpoint)
+                .await
+                .map_err(|e| internal(format!("failed to resume task: {}", e)))?;
+        }
+        self.forget_paused(&endpoint);
+        self.restart_task(&endpoint)
+            .await
+            .map_err(LifecycleError::Internal)
+    }
+}
@@ -0,0 +1,28 @@
+use std::collections::HashMap;
+
+use gateway::types::limits::{NamespaceLimits, ProcessLimits};
+
+use crate::{consts, impls::backend, provider::ContainerdProvider};
+
+/// 容器标签中记录的进程限制，部署早于该功能的函数没有这个标签
+pub(crate) fn stored_limits(labels: &HashMap<String, String>) -> Option<ProcessLimits> {
+    labels
+        .get(consts::LABEL_PROCESS_LIMITS)
+        .and_then(|limits| serde_json::from_str(limits).ok())
+}
+
+impl ContainerdProvider {
+    /// 命名空间标签中的进程限制默认值与上限
+    pub(crate) async fn namespace_limits(
+        &self,
+        namespace: &str,
+    ) -> Result<NamespaceLimits, String> {
+        let labels = backend()
+            .namespace_exist(namespace)
+            .await
+            .map_err(|e| e.to_string())?
+            .map(|ns| ns.labels)
+            .unwrap_or_default();
+        NamespaceLimits::from_labels(&labels)
+    }
+}
@@ -1,6 +1,7 @@
 use gateway::{handlers::function::ListError, types::function::Status};
 
 use crate::{
+    consts,
     impls::{backend, cni::Endpoint, task::TaskError},
     provider::ContainerdProvider,
 };
@@ -47,25 +48,44 @@ impl ContainerdProvider {
                 }
             }
 
+            let available_replicas = self.available_replicas(&endpoint, replicas).await;
+
             // 大部分字段并未实现，使用None填充
+            let network = self.network_status(&endpoint);
+            let volumes = self.volume_mounts(&endpoint);
+            let configs = self.config_mounts(&endpoint);
+            let process_limits = super::limits::stored_limits(&container.labels);
+            let runtime = super::runtime_class::stored_runtime(&container);
+            let paused = self.is_paused(&endpoint);
+            let deployment = self.stored_deployment(&endpoint);
             let status = Status {
                 function_name: endpoint.function_name,
                 namespace: Some(endpoint.namespace),
-                image: container.image,
+                image: container
+                    .labels
+                    .get(consts::LABEL_IMAGE)
+                    .cloned()
+                    .unwrap_or(container.image),
                 env_process: None,
                 env_vars: None,
                 constraints: None,
                 secrets: None,
-                labels: None,
-                annotations: None,
+                labels: deployment.as_ref().and_then(|d| d.labels.clone()),
+                annotations: deployment.and_then(|d| d.annotations),
                 limits: None,
                 requests: None,
                 read_only_root_filesystem: false,
                 invocation_count: None,
                 replicas: Some(replicas),
-                available_replicas: Some(replicas),
+                available_replicas: Some(available_replicas),
                 created_at: Some(created_at),
                 usage: None,
+                network,
+                volumes,
+                configs,
+                process_limits,
+                runtime,
+                paused,
             };
             statuses.push(status);
         }
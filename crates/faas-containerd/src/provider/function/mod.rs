@@ -1,7 +1,21 @@
+pub mod config_map;
 pub mod delete;
 pub mod deploy;
+pub mod dns;
+pub mod exec;
+pub mod hosts;
+pub mod image;
+pub mod image_gc;
+pub mod lifecycle;
+pub mod limits;
 pub mod list;
 pub mod namespace;
+pub mod network_policy;
+pub mod readiness;
+pub mod registry;
 pub mod resolve;
+pub mod restart;
+pub mod runtime_class;
 pub mod status;
 pub mod update;
+pub mod volume;
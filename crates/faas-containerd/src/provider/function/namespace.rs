@@ -1,9 +1,13 @@
 use std::collections::HashMap;
 
-use gateway::{handlers::namespace::NamespaceError, types::namespace::Namespace};
+use gateway::{
+    handlers::namespace::NamespaceError,
+    types::{limits::NamespaceLimits, namespace::Namespace},
+};
 
 use crate::{
-    impls::{backend, namespace::NamespaceServiceError},
+    consts,
+    impls::{backend, cni::policy::NetworkPolicy, namespace::NamespaceServiceError},
     provider::ContainerdProvider,
 };
 
@@ -13,6 +17,10 @@ impl ContainerdProvider {
         namespace: String,
         labels: HashMap<String, String>,
     ) -> Result<(), NamespaceError> {
+        NetworkPolicy::from_labels(&labels).map_err(|e| NamespaceError::Invalid(e.to_string()))?;
+        NamespaceLimits::from_labels(&labels).map_err(NamespaceError::Invalid)?;
+        super::runtime_class::validate_namespace_classes(&labels)
+            .map_err(NamespaceError::Invalid)?;
         backend()
             .create_namespace(&namespace, labels)
             .await
@@ -52,7 +60,11 @@ impl ContainerdProvider {
             .await
             .map_err(|e| NamespaceError::Internal(e.to_string()))?;
         let mut ns_list_result = Vec::new();
-        for ns in ns_list {
+        // 镜像缓存命名空间仅供内部使用
+        for ns in ns_list
+            .into_iter()
+            .filter(|ns| ns.name != consts::CACHE_NAMESPACE)
+        {
             ns_list_result.push(Namespace {
                 name: Some(ns.name),
                 labels: ns.labels,
@@ -78,6 +90,11 @@ impl ContainerdProvider {
         namespace: String,
         labels: HashMap<String, String>,
     ) -> Result<(), NamespaceError> {
+        let policy = NetworkPolicy::from_labels(&labels)
+            .map_err(|e| NamespaceError::Invalid(e.to_string()))?;
+        NamespaceLimits::from_labels(&labels).map_err(NamespaceError::Invalid)?;
+        super::runtime_class::validate_namespace_classes(&labels)
+            .map_err(NamespaceError::Invalid)?;
         backend()
             .update_namespace(&namespace, labels)
             .await
@@ -86,6 +103,11 @@ impl ContainerdProvider {
                     NamespaceError::NotFound(format!("namespace {} not found", namespace))
                 }
                 _ => NamespaceError::Internal(e.to_string()),
+            })?;
+        self.refresh_namespace_policy(&namespace, &policy)
+            .map_err(|e| {
+                log::error!("Failed to refresh network policy of {}: {}", namespace, e);
+                NamespaceError::Internal(e.to_string())
             })
     }
 }
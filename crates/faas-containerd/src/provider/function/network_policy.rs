@@ -0,0 +1,155 @@
+use std::{collections::BTreeMap, net::IpAddr};
+
+use gateway::types::function::NetworkStatus;
+
+use crate::{
+    impls::{
+        backend,
+        cni::{
+            self, Endpoint,
+            policy::{self, FunctionNetwork, FunctionRules, NetworkPolicy, PolicyError},
+        },
+    },
+    provider::ContainerdProvider,
+};
+
+/// 各函数生效的网络策略，网关重启后据此恢复规则
+const POLICY_TREE: &str = "network_policies";
+
+impl ContainerdProvider {
+    /// 命名空间标签中的网络策略
+    pub(crate) async fn namespace_policy(
+        &self,
+        namespace: &str,
+    ) -> Result<NetworkPolicy, PolicyError> {
+        let labels = backend()
+            .namespace_exist(namespace)
+            .await
+            .map_err(|e| PolicyError::Store(e.to_string()))?
+            .map(|ns| ns.labels)
+            .unwrap_or_default();
+        NetworkPolicy::from_labels(&labels)
+    }
+
+    /// 记录函数的地址与策略并重新下发规则
+    pub(crate) fn register_network(
+        &self,
+        endpoint: &Endpoint,
+        addrs: Vec<IpAddr>,
+        policy: NetworkPolicy,
+        function: FunctionNetwork,
+    ) -> Result<(), PolicyError> {
+        let rules = FunctionRules {
+            namespace: endpoint.namespace.clone(),
+            addrs,
+            policy,
+            function,
+        };
+        let value = serde_json::to_vec(&rules).map_err(|e| PolicyError::Store(e.to_string()))?;
+        self.policy_tree()?
+            .insert(endpoint.to_string(), value)
+            .map_err(|e| PolicyError::Store(e.to_string()))?;
+        self.apply_network_policies()
+    }
+
+    pub(crate) fn unregister_network(&self, endpoint: &Endpoint) {
+        let removed = self
+            .policy_tree()
+            .and_then(|tree| {
+                tree.remove(endpoint.to_string())
+                    .map_err(|e| PolicyError::Store(e.to_string()))
+            })
+            .map(|old| old.is_some());
+        match removed {
+            Ok(true) => {
+                if let Err(e) = self.apply_network_policies() {
+                    log::error!("Failed to apply network policies: {}", e);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => log::error!("Failed to remove network policy of {}: {}", endpoint, e),
+        }
+    }
+
+    /// 命名空间标签变化后更新其中所有函数的策略
+    pub(crate) fn refresh_namespace_policy(
+        &self,
+        namespace: &str,
+        policy: &NetworkPolicy,
+    ) -> Result<(), PolicyError> {
+        let tree = self.policy_tree()?;
+        for (key, mut rules) in self.network_rules()? {
+            if rules.namespace == namespace && rules.policy != *policy {
+                rules.policy = policy.clone();
+                let value =
+                    serde_json::to_vec(&rules).map_err(|e| PolicyError::Store(e.to_string()))?;
+                tree.insert(key, value)
+                    .map_err(|e| PolicyError::Store(e.to_string()))?;
+            }
+        }
+        self.apply_network_policies()
+    }
+
+    /// 函数生效的网络控制，未记录时返回 `None`
+    pub(crate) fn network_status(&self, endpoint: &Endpoint) -> Option<NetworkStatus> {
+        let value = self
+            .policy_tree()
+            .ok()?
+            .get(endpoint.to_string())
+            .ok()
+            .flatten()?;
+        let function = serde_json::from_slice::<FunctionRules>(&value)
+            .ok()?
+            .function;
+        Some(NetworkStatus {
+            egress_disabled: function.egress_disabled,
+            egress_allow: function
+                .egress_allow
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            ingress_bandwidth: Some(function.ingress_rate).filter(|rate| *rate > 0),
+            egress_bandwidth: Some(function.egress_rate).filter(|rate| *rate > 0),
+        })
+    }
+
+    /// 内置 DNS 是否向地址为 `src` 的函数公开 `target` 的名称，读取策略失败时不公开
+    pub(crate) fn name_visible(&self, src: IpAddr, target: &Endpoint) -> bool {
+        let rules = match self.network_rules() {
+            Ok(rules) => rules,
+            Err(e) => {
+                log::warn!("Failed to read network policies: {}", e);
+                return false;
+            }
+        };
+        let source = rules.values().find(|rules| rules.addrs.contains(&src));
+        policy::name_visible(source, rules.get(&target.to_string()), &target.namespace)
+    }
+
+    pub(crate) fn apply_network_policies(&self) -> Result<(), PolicyError> {
+        policy::apply(&policy::render(
+            &self.network_rules()?,
+            cni::cni_impl::subnets(),
+        ))
+    }
+
+    fn network_rules(&self) -> Result<BTreeMap<String, FunctionRules>, PolicyError> {
+        let mut functions = BTreeMap::new();
+        for entry in self.policy_tree()?.iter() {
+            let (key, value) = entry.map_err(|e| PolicyError::Store(e.to_string()))?;
+            match serde_json::from_slice::<FunctionRules>(&value) {
+                Ok(rules) => {
+                    functions.insert(String::from_utf8_lossy(&key).into_owned(), rules);
+                }
+                Err(e) => log::warn!("Ignoring malformed network policy: {}", e),
+            }
+        }
+        Ok(functions)
+    }
+
+    fn policy_tree(&self) -> Result<sled::Tree, PolicyError> {
+        self.database
+            .open_tree(POLICY_TREE)
+            .map_err(|e| PolicyError::Store(e.to_string()))
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    net::IpAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use gateway::types::probe::ReadinessProbe;
+
+use crate::{
+    consts,
+    impls::{backend, cni::Endpoint, probe},
+    provider::ContainerdProvider,
+};
+
+/// 周期性探测一个函数的后台任务，被丢弃时停止
+pub(crate) struct ProbeWorker {
+    ready: Arc<AtomicBool>,
+    handle: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Drop for ProbeWorker {
+    fn drop(&mut self) {
+        if let Some(handle) = &self.handle {
+            handle.abort();
+        }
+    }
+}
+
+async fn probe_loop(
+    endpoint: Endpoint,
+    addr: IpAddr,
+    probe: ReadinessProbe,
+    ready: Arc<AtomicBool>,
+) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(probe.period_seconds));
+    let mut failures = 0;
+    loop {
+        ticker.tick().await;
+        if probe::probe_once(&endpoint, addr, &probe).await {
+            failures = 0;
+            if !ready.swap(true, Ordering::Relaxed) {
+                log::info!("Function {} passed its readiness probe", endpoint);
+            }
+        } else {
+            failures += 1;
+            if failures >= probe.failure_threshold && ready.swap(false, Ordering::Relaxed) {
+                log::warn!(
+                    "Function {} failed its readiness probe {} times, stop routing to it",
+                    endpoint,
+                    failures
+                );
+            }
+        }
+    }
+}
+
+impl ContainerdProvider {
+    /// 开始探测函数，首次探测成功前不会被路由，未配置探测时立即就绪
+    pub(crate) fn start_probe(
+        &self,
+        endpoint: &Endpoint,
+        addr: IpAddr,
+        probe: Option<ReadinessProbe>,
+        ready: bool,
+    ) -> bool {
+        let ready = probe.is_none() || ready;
+        let flag = Arc::new(AtomicBool::new(ready));
+        let handle = probe
+            .map(|probe| tokio::spawn(probe_loop(endpoint.clone(), addr, probe, flag.clone())));
+        self.probes.lock().unwrap().insert(
+            endpoint.to_string(),
+            ProbeWorker {
+                ready: flag,
+                handle,
+            },
+        );
+        ready
+    }
+
+    pub(crate) fn stop_probe(&self, endpoint: &Endpoint) {
+        self.probes.lock().unwrap().remove(&endpoint.to_string());
+    }
+
+    /// 运行中且通过了就绪探测的副本数
+    pub(crate) async fn available_replicas(&self, endpoint: &Endpoint, replicas: i32) -> i32 {
+        if replicas == 0 {
+            return 0;
+        }
+        match self.stored_addr(endpoint) {
+            Ok(Some(addr)) if self.is_ready(endpoint, addr.ip()).await => replicas,
+            _ => 0,
+        }
+    }
+
+    /// 容器标签中记录的探测配置，容器无法读取时返回 None
+    pub(crate) async fn stored_probe(&self, endpoint: &Endpoint) -> Option<Option<ReadinessProbe>> {
+        match backend().load_container(endpoint).await {
+            Ok(container) => Some(
+                container
+                    .labels
+                    .get(consts::LABEL_READINESS_PROBE)
+                    .and_then(|probe| serde_json::from_str::<ReadinessProbe>(probe).ok()),
+            ),
+            Err(e) => {
+                log::warn!("Failed to load container of {}: {:?}", endpoint, e);
+                None
+            }
+        }
+    }
+
+    /// 函数是否通过了就绪探测。网关重启后探测任务不存在，
+    /// 此时按容器标签中的配置立即探测一次并重新启动探测任务
+    pub(crate) async fn is_ready(&self, endpoint: &Endpoint, addr: IpAddr) -> bool {
+        if let Some(worker) = self.probes.lock().unwrap().get(&endpoint.to_string()) {
+            return worker.ready.load(Ordering::Relaxed);
+        }
+
+        let Some(probe) = self.stored_probe(endpoint).await else {
+            return false;
+        };
+        let ready = match &probe {
+            Some(probe) => probe::probe_once(endpoint, addr, probe).await,
+            None => true,
+        };
+        self.start_probe(endpoint, addr, probe, ready)
+    }
+}
@@ -0,0 +1,169 @@
+use aes_gcm::{
+    Aes256Gcm, Key, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng},
+};
+use gateway::{handlers::registry::RegistryAuthError, types::registry::RegistryAuth};
+use sha2::Digest;
+
+use crate::{
+    impls::{backend, oci_image::RegistryCredentials},
+    provider::ContainerdProvider,
+};
+
+const REGISTRY_AUTH_TREE: &str = "registry_auth";
+const NONCE_LEN: usize = 12;
+/// 曾作为示例写在 `.env` 中的密钥，不能用于加密
+const PLACEHOLDER_SECRET: &str = "ChangeMe";
+
+fn validate_secret(secret: Option<String>) -> Result<String, String> {
+    match secret {
+        None => Err("FAASDRS_ENCRYPTION_KEY is not set".to_string()),
+        Some(secret) if secret.trim().is_empty() => {
+            Err("FAASDRS_ENCRYPTION_KEY is empty".to_string())
+        }
+        Some(secret) if secret == PLACEHOLDER_SECRET => Err(
+            "FAASDRS_ENCRYPTION_KEY is the example value, set a random secret instead".to_string(),
+        ),
+        Some(secret) => Ok(secret),
+    }
+}
+
+fn encryption_secret() -> Result<String, String> {
+    validate_secret(std::env::var("FAASDRS_ENCRYPTION_KEY").ok())
+}
+
+/// 启动时调用，没有可用的密钥时拒绝启动，而不是在第一次保存凭据时才失败
+pub fn check_encryption_key() -> Result<(), String> {
+    encryption_secret().map(|_| ())
+}
+
+/// 凭据以 AES-256-GCM 加密后存储，密钥由环境变量 `FAASDRS_ENCRYPTION_KEY` 派生
+fn cipher() -> Result<Aes256Gcm, RegistryAuthError> {
+    let secret = encryption_secret().map_err(RegistryAuthError::Internal)?;
+    let key = sha2::Sha256::digest(secret.as_bytes());
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
+
+fn encrypt(plain: &[u8]) -> Result<Vec<u8>, RegistryAuthError> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let mut sealed = cipher()?
+        .encrypt(&nonce, plain)
+        .map_err(|e| RegistryAuthError::Internal(format!("failed to encrypt: {}", e)))?;
+    sealed.splice(0..0, nonce);
+    Ok(sealed)
+}
+
+fn decrypt(sealed: &[u8]) -> Result<Vec<u8>, RegistryAuthError> {
+    if sealed.len() < NONCE_LEN {
+        return Err(RegistryAuthError::Internal(
+            "stored registry auth is corrupted".to_string(),
+        ));
+    }
+    let (nonce, cipher_text) = sealed.split_at(NONCE_LEN);
+    cipher()?
+        .decrypt(Nonce::from_slice(nonce), cipher_text)
+        .map_err(|e| RegistryAuthError::Internal(format!("failed to decrypt: {}", e)))
+}
+
+impl ContainerdProvider {
+    pub(crate) async fn _set_registry_auth(
+        &self,
+        namespace: String,
+        auth: RegistryAuth,
+    ) -> Result<(), RegistryAuthError> {
+        let exist = backend()
+            .namespace_exist(&namespace)
+            .await
+            .map_err(|e| RegistryAuthError::Internal(e.to_string()))?;
+        if exist.is_none() {
+            return Err(RegistryAuthError::NotFound(format!(
+                "namespace {} not found",
+                namespace
+            )));
+        }
+
+        let credentials = auth.credentials().map_err(RegistryAuthError::Invalid)?;
+        let plain = serde_json::to_vec(&credentials)
+            .map_err(|e| RegistryAuthError::Internal(e.to_string()))?;
+        self.registry_auth_tree()?
+            .insert(namespace.as_bytes(), encrypt(&plain)?)
+            .map_err(|e| {
+                log::error!("Failed to store registry auth: {:?}", e);
+                RegistryAuthError::Internal(e.to_string())
+            })?;
+        log::info!("Registry auth of namespace {} updated", namespace);
+        Ok(())
+    }
+
+    pub(crate) async fn _get_registry_auth(
+        &self,
+        namespace: String,
+    ) -> Result<Vec<String>, RegistryAuthError> {
+        let credentials = self.registry_credentials(&namespace)?.ok_or_else(|| {
+            RegistryAuthError::NotFound(format!("no registry auth for namespace {}", namespace))
+        })?;
+        let mut registries: Vec<String> = credentials.into_keys().collect();
+        registries.sort();
+        Ok(registries)
+    }
+
+    pub(crate) async fn _delete_registry_auth(
+        &self,
+        namespace: String,
+    ) -> Result<(), RegistryAuthError> {
+        self.registry_auth_tree()?
+            .remove(namespace.as_bytes())
+            .map_err(|e| RegistryAuthError::Internal(e.to_string()))?
+            .map(|_| ())
+            .ok_or_else(|| {
+                RegistryAuthError::NotFound(format!("no registry auth for namespace {}", namespace))
+            })
+    }
+
+    /// 部署时使用的凭据，未配置时返回 `None` 以匿名拉取
+    pub(crate) fn registry_credentials(
+        &self,
+        namespace: &str,
+    ) -> Result<Option<RegistryCredentials>, RegistryAuthError> {
+        let Some(sealed) = self
+            .registry_auth_tree()?
+            .get(namespace.as_bytes())
+            .map_err(|e| RegistryAuthError::Internal(e.to_string()))?
+        else {
+            return Ok(None);
+        };
+        let plain = decrypt(&sealed)?;
+        serde_json::from_slice(&plain)
+            .map(Some)
+            .map_err(|e| RegistryAuthError::Internal(e.to_string()))
+    }
+
+    fn registry_auth_tree(&self) -> Result<sled::Tree, RegistryAuthError> {
+        self.database
+            .open_tree(REGISTRY_AUTH_TREE)
+            .map_err(|e| RegistryAuthError::Internal(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_encrypt_roundtrip() {
+        // SAFETY: tests in this module are the only users of this variable
+        unsafe { std::env::set_var("FAASDRS_ENCRYPTION_KEY", "test-key") };
+        let sealed = super::encrypt(b"{\"docker.io\":{}}").unwrap();
+        assert_ne!(&sealed[super::NONCE_LEN..], b"{\"docker.io\":{}}");
+        assert_eq!(super::decrypt(&sealed).unwrap(), b"{\"docker.io\":{}}");
+    }
+
+    #[test]
+    fn test_validate_secret() {
+        assert!(super::validate_secret(None).is_err());
+        assert!(super::validate_secret(Some(" ".to_string())).is_err());
+        assert!(super::validate_secret(Some(super::PLACEHOLDER_SECRET.to_string())).is_err());
+        assert_eq!(
+            super::validate_secret(Some("3f9a".to_string())).unwrap(),
+            "3f9a"
+        );
+    }
+}
@@ -1,16 +1,95 @@
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use cidr::Family;
 
 use actix_http::uri::Builder;
 use gateway::handlers::function::ResolveError;
 use gateway::types::function::Query;
 
+use crate::consts;
 use crate::impls::cni::{self, Endpoint};
 use crate::provider::ContainerdProvider;
 
-fn upstream(addr: IpAddr) -> Builder {
+fn upstream(addr: SocketAddr) -> Builder {
     actix_http::Uri::builder()
         .scheme("http")
-        .authority(format!("{}:{}", addr, 8080))
+        .authority(addr.to_string())
+}
+
+const TAG_V4: u8 = 4;
+const TAG_V6: u8 = 6;
+
+/// 部署时记录的函数地址，双栈时每个地址族各一个
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Upstream {
+    pub addrs: Vec<IpAddr>,
+    pub port: u16,
+}
+
+impl Upstream {
+    /// 大端序的端口，之后每个地址以地址族标记开头
+    pub fn encode(&self) -> Vec<u8> {
+        let mut value = self.port.to_be_bytes().to_vec();
+        for addr in &self.addrs {
+            match addr {
+                IpAddr::V4(ip) => {
+                    value.push(TAG_V4);
+                    value.extend_from_slice(&ip.octets());
+                }
+                IpAddr::V6(ip) => {
+                    value.push(TAG_V6);
+                    value.extend_from_slice(&ip.octets());
+                }
+            }
+        }
+        value
+    }
+
+    pub fn decode(value: &[u8]) -> Option<Self> {
+        // 旧版本只记录了 IPv4 地址，端口固定为默认端口
+        if let Ok(ip) = <[u8; 4]>::try_from(value) {
+            return Some(Self {
+                addrs: vec![IpAddr::V4(Ipv4Addr::from_octets(ip))],
+                port: consts::DEFAULT_UPSTREAM_PORT,
+            });
+        }
+
+        let (port, mut rest) = value.split_first_chunk::<2>()?;
+        let mut addrs = Vec::new();
+        while let Some((tag, tail)) = rest.split_first() {
+            let addr = match *tag {
+                TAG_V4 => {
+                    let (ip, tail) = tail.split_first_chunk::<4>()?;
+                    rest = tail;
+                    IpAddr::V4(Ipv4Addr::from_octets(*ip))
+                }
+                TAG_V6 => {
+                    let (ip, tail) = tail.split_first_chunk::<16>()?;
+                    rest = tail;
+                    IpAddr::V6(Ipv6Addr::from_octets(*ip))
+                }
+                _ => return None,
+            };
+            addrs.push(addr);
+        }
+        if addrs.is_empty() {
+            return None;
+        }
+        Some(Self {
+            addrs,
+            port: u16::from_be_bytes(*port),
+        })
+    }
+
+    /// 优先选择给定地址族的地址，没有时使用第一个地址
+    pub fn pick(&self, family: Family) -> SocketAddr {
+        let addr = self
+            .addrs
+            .iter()
+            .find(|addr| addr.is_ipv4() == (family == Family::Ipv4))
+            .unwrap_or(&self.addrs[0]);
+        SocketAddr::new(*addr, self.port)
+    }
 }
 
 impl ContainerdProvider {
@@ -20,26 +99,34 @@ impl ContainerdProvider {
     ) -> Result<actix_http::uri::Builder, ResolveError> {
         let endpoint = Endpoint::from(query);
         log::trace!("Resolving function: {:?}", endpoint);
-        let addr_oct = self
-            .database
-            .get(endpoint.to_string())
+        let addr = self
+            .stored_addr(&endpoint)
             .map_err(|e| {
                 log::error!("Failed to get container address: {:?}", e);
                 ResolveError::Internal(e.to_string())
             })?
             .ok_or(ResolveError::NotFound("container not found".to_string()))?;
 
-        log::trace!("Container address: {:?}", addr_oct.as_array::<4>());
-
-        // We force the address to be IPv4 here
-        let addr = IpAddr::V4(Ipv4Addr::from_octets(*addr_oct.as_array::<4>().unwrap()));
+        // 被冻结的进程不会回应，直接拒绝而不是让调用挂起
+        if self.is_paused(&endpoint) {
+            return Err(ResolveError::Paused(format!(
+                "function {} is paused",
+                endpoint
+            )));
+        }
 
         // Check if the coresponding netns is still alive
         // We can achieve this by checking the /run/cni/faasrs-cni-bridge,
         // if the ip filename is still there
 
-        if cni::cni_impl::check_network_exists(addr) {
+        if cni::cni_impl::check_network_exists(addr.ip()) {
             log::trace!("CNI network exists for {}", addr);
+            if !self.is_ready(&endpoint, addr.ip()).await {
+                return Err(ResolveError::NotReady(format!(
+                    "function {} has not passed its readiness probe",
+                    endpoint
+                )));
+            }
             Ok(upstream(addr))
         } else {
             log::error!("CNI network not exists for {}", addr);
@@ -47,19 +134,67 @@ impl ContainerdProvider {
             Err(ResolveError::Internal("CNI network not exists".to_string()))
         }
     }
+
+    /// 部署时记录的函数地址与端口，按配置的地址族选择
+    pub(crate) fn stored_addr(
+        &self,
+        endpoint: &Endpoint,
+    ) -> Result<Option<SocketAddr>, sled::Error> {
+        let Some(value) = self.database.get(endpoint.to_string())? else {
+            return Ok(None);
+        };
+        let Some(upstream) = Upstream::decode(&value) else {
+            log::error!("Malformed address of {}: {:?}", endpoint, value);
+            return Ok(None);
+        };
+        log::trace!("Container address: {:?}", upstream);
+        Ok(Some(upstream.pick(cni::cni_impl::preferred_family())))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::net::{IpAddr, Ipv4Addr};
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    use cidr::Family;
+
+    use super::Upstream;
 
     #[test]
     fn test_uri() {
         let addr = IpAddr::V4(Ipv4Addr::new(10, 42, 2, 48));
-        let uri = super::upstream(addr).path_and_query("").build().unwrap();
+        let uri = super::upstream(SocketAddr::new(addr, 8080))
+            .path_and_query("")
+            .build()
+            .unwrap();
         assert_eq!(uri.scheme_str(), Some("http"));
         assert_eq!(uri.authority().unwrap().host(), addr.to_string());
         assert_eq!(uri.authority().unwrap().port_u16(), Some(8080));
         assert_eq!(uri.to_string(), format!("http://{}:8080/", addr));
     }
+
+    #[test]
+    fn test_upstream_encoding() {
+        let v4 = IpAddr::V4(Ipv4Addr::new(10, 42, 2, 48));
+        let v6 = IpAddr::V6("fd66::30".parse().unwrap());
+        let upstream = Upstream {
+            addrs: vec![v4, v6],
+            port: 3000,
+        };
+        let value = upstream.encode();
+        assert_eq!(&value[..7], &[0x0b, 0xb8, 4, 10, 42, 2, 48]);
+        let decoded = Upstream::decode(&value).unwrap();
+        assert_eq!(decoded, upstream);
+        assert_eq!(decoded.pick(Family::Ipv6), SocketAddr::new(v6, 3000));
+        assert_eq!(decoded.pick(Family::Ipv4), SocketAddr::new(v4, 3000));
+
+        // 旧版本的记录
+        assert_eq!(
+            Upstream::decode(&[10, 42, 2, 48])
+                .unwrap()
+                .pick(Family::Ipv6),
+            SocketAddr::new(v4, 8080)
+        );
+        assert!(Upstream::decode(&[0x0b, 0xb8, 6, 0xfd, 0, 0, 0]).is_none());
+    }
 }
@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use crate::{
+    impls::{backend, cni::Endpoint, task::TaskError},
+    provider::ContainerdProvider,
+};
+
+/// 重启后等待函数就绪的最长时间
+const RESTART_READY_TIMEOUT: Duration = Duration::from_secs(60);
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+impl ContainerdProvider {
+    /// 在原有的容器、快照与网络中重新创建任务，并等待函数重新就绪
+    pub(crate) async fn restart_task(&self, endpoint: &Endpoint) -> Result<(), String> {
+        let probe = self
+            .stored_probe(endpoint)
+            .await
+            .ok_or_else(|| format!("function {} not found", endpoint))?;
+        let addr = self
+            .stored_addr(endpoint)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("address of {} not found", endpoint))?;
+
+        self.stop_probe(endpoint);
+        match backend().kill_task_with_timeout(endpoint).await {
+            Ok(()) | Err(TaskError::NotFound) => {}
+            Err(e) => return Err(format!("failed to stop task: {}", e)),
+        }
+        let snapshotter = backend()
+            .load_container(endpoint)
+            .await
+            .map_err(|e| format!("failed to load container: {}", e))?
+            .snapshotter;
+        let mounts = backend()
+            .get_mounts(&endpoint.function_name, &endpoint.namespace, &snapshotter)
+            .await
+            .map_err(|e| e.to_string())?;
+        backend()
+            .new_task(mounts, endpoint)
+            .await
+            .map_err(|e| format!("failed to start task: {}", e))?;
+
+        self.start_probe(endpoint, addr.ip(), probe, false);
+        let deadline = tokio::time::Instant::now() + RESTART_READY_TIMEOUT;
+        while !self.is_ready(endpoint, addr.ip()).await {
+            if tokio::time::Instant::now() >= deadline {
+                return Err(format!(
+                    "function {} was not ready {}s after restart",
+                    endpoint,
+                    RESTART_READY_TIMEOUT.as_secs()
+                ));
+            }
+            tokio::time::sleep(READY_POLL_INTERVAL).await;
+        }
+        log::info!("Function {} restarted", endpoint);
+        Ok(())
+    }
+
+    /// 依次重启函数，前一个就绪后才重启下一个，遇到失败即停止
+    pub(crate) async fn rolling_restart(&self, endpoints: &[Endpoint]) -> Result<(), String> {
+        for endpoint in endpoints {
+            self.restart_task(endpoint)
+                .await
+                .map_err(|e| format!("restart of {} failed: {}", endpoint, e))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use containerd_client::services::v1::Container;
+use gateway::types::{annotation, function::FunctionRuntime};
+
+use crate::{
+    consts,
+    impls::{
+        backend,
+        runtime_class::{self, RUNTIME_CLASSES},
+    },
+    provider::ContainerdProvider,
+};
+
+/// 命名空间标签中列出的运行时类必须已经登记
+pub(crate) fn validate_namespace_classes(labels: &HashMap<String, String>) -> Result<(), String> {
+    for class in runtime_class::allowed_classes(labels).unwrap_or_default() {
+        if !RUNTIME_CLASSES.contains(&class) {
+            return Err(format!("unknown runtime class '{}'", class));
+        }
+    }
+    Ok(())
+}
+
+/// 容器使用的运行时，类名来自部署时记录的标签
+pub(crate) fn stored_runtime(container: &Container) -> Option<FunctionRuntime> {
+    container.runtime.as_ref().map(|runtime| FunctionRuntime {
+        class: container.labels.get(consts::LABEL_RUNTIME_CLASS).cloned(),
+        runtime_type: runtime.name.clone(),
+        snapshotter: container.snapshotter.clone(),
+    })
+}
+
+impl ContainerdProvider {
+    /// 按注解与命名空间允许的运行时类选择函数的运行时类
+    pub(crate) async fn select_runtime_class(
+        &self,
+        namespace: &str,
+        annotations: Option<&HashMap<String, String>>,
+    ) -> Result<String, String> {
+        let labels = backend()
+            .namespace_exist(namespace)
+            .await
+            .map_err(|e| e.to_string())?
+            .map(|ns| ns.labels)
+            .unwrap_or_default();
+        let requested = annotations
+            .and_then(|a| a.get(annotation::RUNTIME_CLASS))
+            .map(|class| class.trim());
+        RUNTIME_CLASSES.select(
+            requested,
+            runtime_class::allowed_classes(&labels).as_deref(),
+        )
+    }
+}
@@ -4,6 +4,7 @@ use gateway::{
 };
 
 use crate::{
+    consts,
     impls::{backend, cni::Endpoint, container::ContainerError},
     provider::ContainerdProvider,
 };
@@ -43,25 +44,39 @@ impl ContainerdProvider {
             }
         }
 
+        let available_replicas = self.available_replicas(&endpoint, replicas).await;
+
+        let runtime = super::runtime_class::stored_runtime(&container);
+        let deployment = self.stored_deployment(&endpoint);
         // 大部分字段并未实现，使用None填充
         let status = Status {
             function_name: container.id,
-            namespace: Some(endpoint.namespace),
-            image: container.image,
+            namespace: Some(endpoint.namespace.clone()),
+            image: container
+                .labels
+                .get(consts::LABEL_IMAGE)
+                .cloned()
+                .unwrap_or(container.image),
             env_process: None,
             env_vars: None,
             constraints: None,
             secrets: None,
-            labels: None,
-            annotations: None,
+            labels: deployment.as_ref().and_then(|d| d.labels.clone()),
+            annotations: deployment.and_then(|d| d.annotations),
             limits: None,
             requests: None,
             read_only_root_filesystem: false,
             invocation_count: None,
             replicas: Some(replicas),
-            available_replicas: Some(replicas),
+            available_replicas: Some(available_replicas),
             created_at: Some(created_at),
             usage: None,
+            network: self.network_status(&endpoint),
+            volumes: self.volume_mounts(&endpoint),
+            configs: self.config_mounts(&endpoint),
+            process_limits: super::limits::stored_limits(&container.labels),
+            runtime,
+            paused: self.is_paused(&endpoint),
         };
 
         Ok(status)
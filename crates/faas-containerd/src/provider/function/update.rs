@@ -1,17 +1,70 @@
 use gateway::{
     handlers::function::{DeleteError, DeployError, UpdateError},
-    types::function::{Deployment, Query},
+    types::{
+        function::{Deployment, Query, UpdateStrategy},
+        image::PullPolicy,
+        operation::OperationHandle,
+        probe::ReadinessProbe,
+    },
 };
 
-use crate::provider::ContainerdProvider;
+use super::resolve::Upstream;
+use crate::{
+    consts,
+    impls::{backend, cni::Endpoint, oci_image::ImageError},
+    provider::ContainerdProvider,
+};
+
+/// 记录各函数最近一次部署或更新的 `Deployment`，键为 `<namespace>/<function>`
+const DEPLOYMENTS_TREE: &str = "deployments";
+
+fn deployment_key(endpoint: &Endpoint) -> String {
+    format!("{}/{}", endpoint.namespace, endpoint.function_name)
+}
+
+fn update_error(e: DeployError) -> UpdateError {
+    match e {
+        DeployError::Invalid(e) => UpdateError::Invalid(e),
+        DeployError::Forbidden(e) => UpdateError::Forbidden(e),
+        DeployError::InternalError(e) => UpdateError::Internal(e),
+    }
+}
+
+fn internal(e: impl std::fmt::Display) -> UpdateError {
+    UpdateError::Internal(e.to_string())
+}
 
 impl ContainerdProvider {
-    pub(crate) async fn _update(&self, param: Deployment) -> Result<(), UpdateError> {
+    pub(crate) async fn _update(
+        &self,
+        param: Deployment,
+        operation: &OperationHandle,
+    ) -> Result<UpdateStrategy, UpdateError> {
         let function = Query {
             function_name: param.function_name.clone(),
             namespace: param.namespace.clone(),
         };
-        self._delete(function).await.map_err(|e| {
+        let endpoint = Endpoint::from(function);
+
+        // 没有记录的函数部署于记录之前，只能重新创建
+        if let Some(previous) = self.stored_deployment(&endpoint) {
+            let mut changes = param.runtime_changes(&previous);
+            if changes.is_empty() && self.image_moved(&endpoint, &param, operation).await? {
+                changes.push("image digest".to_string());
+            }
+            if changes.is_empty() {
+                self.update_in_place(&endpoint, &param).await?;
+                log::info!("Function {} was updated in place", endpoint);
+                return Ok(UpdateStrategy::InPlace);
+            }
+            log::info!(
+                "Recreating function {} because {} changed",
+                endpoint,
+                changes.join(", ")
+            );
+        }
+
+        self.teardown(&endpoint).await.map_err(|e| {
             log::error!("failed to delete function when update because {:?}", e);
             match e {
                 DeleteError::NotFound(e) => UpdateError::NotFound(e.to_string()),
@@ -19,14 +72,125 @@ impl ContainerdProvider {
                 _ => UpdateError::Internal(e.to_string()),
             }
         })?;
-        self._deploy(param).await.map_err(|e| {
+        self._deploy(param, operation).await.map_err(|e| {
             log::error!("failed to deploy function when update because {:?}", e);
-            match e {
-                DeployError::Invalid(e) => UpdateError::Invalid(e.to_string()),
-                DeployError::InternalError(e) => UpdateError::Internal(e.to_string()),
-            }
+            update_error(e)
         })?;
 
+        Ok(UpdateStrategy::Recreate)
+    }
+
+    /// 拉取策略为 Always 时重新解析镜像引用，tag 指向了新的 digest 时需要重新创建。
+    /// 镜像只拉取到缓存，不在函数的命名空间中解包
+    async fn image_moved(
+        &self,
+        endpoint: &Endpoint,
+        param: &Deployment,
+        operation: &OperationHandle,
+    ) -> Result<bool, UpdateError> {
+        if super::deploy::pull_policy(param).map_err(update_error)? != PullPolicy::Always {
+            return Ok(false);
+        }
+        let container = backend()
+            .load_container(endpoint)
+            .await
+            .map_err(|e| UpdateError::NotFound(e.to_string()))?;
+        let credentials = self
+            .registry_credentials(&endpoint.namespace)
+            .map_err(internal)?;
+        let digest = backend()
+            .resolve_digest(&param.image, credentials.as_ref(), operation)
+            .await
+            .map_err(|e| match e {
+                ImageError::ImageNotFound(_) | ImageError::Unauthorized(_) => {
+                    UpdateError::Invalid(e.to_string())
+                }
+                _ => internal(e),
+            })?;
+        Ok(container.labels.get(consts::LABEL_IMAGE_DIGEST) != Some(&digest))
+    }
+
+    /// 只有元数据与路由设置变化时保留容器，重新计算端口与就绪探测
+    async fn update_in_place(
+        &self,
+        endpoint: &Endpoint,
+        param: &Deployment,
+    ) -> Result<(), UpdateError> {
+        let container = backend()
+            .load_container(endpoint)
+            .await
+            .map_err(|e| UpdateError::NotFound(e.to_string()))?;
+        let port = match super::deploy::upstream_port_annotation(param).map_err(update_error)? {
+            Some(port) => port,
+            None => backend()
+                .runtime_config(&container.image, &endpoint.namespace)
+                .await
+                .map_err(internal)?
+                .exposed_tcp_port()
+                .unwrap_or(consts::DEFAULT_UPSTREAM_PORT),
+        };
+        let probe = ReadinessProbe::from_annotations(param.annotations.as_ref(), port)
+            .map_err(UpdateError::Invalid)?
+            .map(|probe| serde_json::to_string(&probe))
+            .transpose()
+            .map_err(internal)?;
+
+        if container.labels.get(consts::LABEL_READINESS_PROBE) != probe.as_ref() {
+            backend()
+                .set_container_label(endpoint, consts::LABEL_READINESS_PROBE, probe)
+                .await
+                .map_err(internal)?;
+        }
+        let key = endpoint.to_string();
+        if let Some(mut upstream) = self
+            .database
+            .get(&key)
+            .map_err(internal)?
+            .and_then(|value| Upstream::decode(&value))
+            && upstream.port != port
+        {
+            upstream.port = port;
+            self.database
+                .insert(&key, upstream.encode())
+                .map_err(internal)?;
+        }
+        // 探测任务在下次路由时按新的配置重新启动
+        self.stop_probe(endpoint);
+
+        let deployment = serde_json::to_vec(param).map_err(internal)?;
+        self.record_deployment(endpoint, &deployment);
         Ok(())
     }
+
+    fn deployments_tree(&self) -> Result<sled::Tree, sled::Error> {
+        self.database.open_tree(DEPLOYMENTS_TREE)
+    }
+
+    /// 记录 JSON 格式的 `Deployment`，失败时只影响之后的更新能否原地进行
+    pub(crate) fn record_deployment(&self, endpoint: &Endpoint, deployment: &[u8]) {
+        if let Err(e) = self
+            .deployments_tree()
+            .and_then(|tree| tree.insert(deployment_key(endpoint), deployment))
+        {
+            log::warn!("Failed to record deployment of {}: {}", endpoint, e);
+        }
+    }
+
+    pub(crate) fn stored_deployment(&self, endpoint: &Endpoint) -> Option<Deployment> {
+        let value = self
+            .deployments_tree()
+            .ok()?
+            .get(deployment_key(endpoint))
+            .ok()??;
+        serde_json::from_slice(&value).ok()
+    }
+
+    pub(crate) fn forget_deployment(&self, endpoint: &Endpoint) {
+        if let Err(e) = self
+            .deployments_tree()
+            .and_then(|tree| tree.remove(deployment_key(endpoint)))
+        {
+            log::warn!("Failed to forget deployment of {}: {}", endpoint, e);
+        }
+    }
 }
@@ -0,0 +1,308 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+use gateway::{
+    handlers::volume::VolumeError,
+    types::volume::{Volume, VolumeMount, valid_volume_name},
+};
+
+use crate::{
+    impls::{backend, cni::Endpoint, function::VolumeBind},
+    provider::ContainerdProvider,
+};
+
+/// 记录各函数挂载的卷，键为 `<namespace>/<function>`
+const VOLUME_MOUNTS_TREE: &str = "volume_mounts";
+
+/// 检查卷是否被挂载与记录挂载需要互斥，否则删除卷时可能漏掉正在部署的函数
+static VOLUME_LOCK: Mutex<()> = Mutex::new(());
+
+/// 由 provider 生成并挂载的路径，卷不能覆盖它们
+const RESERVED_PATHS: &[&str] = &[
+    "/proc",
+    "/dev",
+    "/sys",
+    "/etc/hosts",
+    "/etc/hostname",
+    "/etc/resolv.conf",
+];
+
+fn mount_key(endpoint: &Endpoint) -> String {
+    format!("{}/{}", endpoint.namespace, endpoint.function_name)
+}
+
+/// 挂载路径必须是不含 `.` 与 `..` 的绝对路径，且不能位于保留路径之中
+pub(super) fn validate_mount_path(path: &str) -> Result<(), String> {
+    let p = Path::new(path);
+    if !p.is_absolute() {
+        return Err(format!("mount path '{}' must be absolute", path));
+    }
+    if p.components().count() < 2
+        || p.components()
+            .skip(1)
+            .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return Err(format!("invalid mount path '{}'", path));
+    }
+    if let Some(reserved) = RESERVED_PATHS.iter().find(|r| p.starts_with(r)) {
+        return Err(format!("mount path '{}' conflicts with {}", path, reserved));
+    }
+    Ok(())
+}
+
+fn validate_mounts(mounts: &[VolumeMount]) -> Result<(), String> {
+    for (i, mount) in mounts.iter().enumerate() {
+        if !valid_volume_name(&mount.name) {
+            return Err(format!("invalid volume name '{}'", mount.name));
+        }
+        validate_mount_path(&mount.mount_path)?;
+        if mounts[..i]
+            .iter()
+            .any(|other| Path::new(&other.mount_path) == Path::new(&mount.mount_path))
+        {
+            return Err(format!("duplicate mount path '{}'", mount.mount_path));
+        }
+    }
+    Ok(())
+}
+
+/// 卷与配置的挂载路径不能相同
+pub(super) fn check_destinations(binds: &[VolumeBind]) -> Result<(), String> {
+    for (i, bind) in binds.iter().enumerate() {
+        if binds[..i]
+            .iter()
+            .any(|other| Path::new(&other.destination) == Path::new(&bind.destination))
+        {
+            return Err(format!("duplicate mount path '{}'", bind.destination));
+        }
+    }
+    Ok(())
+}
+
+impl ContainerdProvider {
+    fn volume_dir(&self, namespace: &str, name: &str) -> PathBuf {
+        self.data_dir.join("volumes").join(namespace).join(name)
+    }
+
+    fn volume_mounts_tree(&self) -> Result<sled::Tree, VolumeError> {
+        self.database
+            .open_tree(VOLUME_MOUNTS_TREE)
+            .map_err(|e| VolumeError::Internal(e.to_string()))
+    }
+
+    /// 命名空间中挂载了该卷的函数
+    fn volume_users(&self, namespace: &str, name: &str) -> Result<Vec<String>, VolumeError> {
+        let mut users = Vec::new();
+        for entry in self
+            .volume_mounts_tree()?
+            .scan_prefix(format!("{}/", namespace))
+        {
+            let (key, value) = entry.map_err(|e| VolumeError::Internal(e.to_string()))?;
+            let mounts: Vec<VolumeMount> = serde_json::from_slice(&value).unwrap_or_default();
+            if mounts.iter().any(|m| m.name == name) {
+                let key = String::from_utf8_lossy(&key);
+                users.push(key[namespace.len() + 1..].to_string());
+            }
+        }
+        Ok(users)
+    }
+
+    async fn check_volume_namespace(&self, namespace: &str) -> Result<(), VolumeError> {
+        let exist = backend()
+            .namespace_exist(namespace)
+            .await
+            .map_err(|e| VolumeError::Internal(e.to_string()))?;
+        if exist.is_none() {
+            return Err(VolumeError::NotFound(format!(
+                "namespace {} not found",
+                namespace
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn _create_volume(
+        &self,
+        namespace: String,
+        name: String,
+    ) -> Result<(), VolumeError> {
+        if !valid_volume_name(&name) {
+            return Err(VolumeError::Invalid(format!(
+                "invalid volume name '{}'",
+                name
+            )));
+        }
+        self.check_volume_namespace(&namespace).await?;
+        let dir = self.volume_dir(&namespace, &name);
+        std::fs::create_dir_all(dir.parent().unwrap())
+            .map_err(|e| VolumeError::Internal(e.to_string()))?;
+        std::fs::create_dir(&dir).map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => VolumeError::AlreadyExists(format!(
+                "volume {} already exists in namespace {}",
+                name, namespace
+            )),
+            _ => VolumeError::Internal(e.to_string()),
+        })?;
+        log::info!("Volume {} created in namespace {}", name, namespace);
+        Ok(())
+    }
+
+    pub(crate) async fn _list_volumes(
+        &self,
+        namespace: String,
+    ) -> Result<Vec<Volume>, VolumeError> {
+        self.check_volume_namespace(&namespace).await?;
+        let dir = self.data_dir.join("volumes").join(&namespace);
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(VolumeError::Internal(e.to_string())),
+        };
+        let mut volumes = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| VolumeError::Internal(e.to_string()))?;
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let created_at = entry
+                .metadata()
+                .and_then(|m| m.created().or_else(|_| m.modified()))
+                .ok()
+                .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339());
+            volumes.push(Volume {
+                mounted_by: self.volume_users(&namespace, &name)?,
+                name,
+                namespace: Some(namespace.clone()),
+                created_at,
+            });
+        }
+        volumes.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(volumes)
+    }
+
+    pub(crate) async fn _delete_volume(
+        &self,
+        namespace: String,
+        name: String,
+    ) -> Result<(), VolumeError> {
+        if !valid_volume_name(&name) {
+            return Err(VolumeError::Invalid(format!(
+                "invalid volume name '{}'",
+                name
+            )));
+        }
+        self.check_volume_namespace(&namespace).await?;
+        let dir = self.volume_dir(&namespace, &name);
+        let _lock = VOLUME_LOCK.lock().unwrap();
+        if !dir.is_dir() {
+            return Err(VolumeError::NotFound(format!(
+                "volume {} not found in namespace {}",
+                name, namespace
+            )));
+        }
+        let users = self.volume_users(&namespace, &name)?;
+        if !users.is_empty() {
+            return Err(VolumeError::InUse(format!(
+                "volume {} is mounted by {}",
+                name,
+                users.join(", ")
+            )));
+        }
+        std::fs::remove_dir_all(&dir).map_err(|e| VolumeError::Internal(e.to_string()))?;
+        log::info!("Volume {} deleted from namespace {}", name, namespace);
+        Ok(())
+    }
+
+    /// 检查部署引用的卷并记录挂载，返回容器中要绑定挂载的目录
+    pub(crate) fn mount_volumes(
+        &self,
+        endpoint: &Endpoint,
+        mounts: &[VolumeMount],
+    ) -> Result<Vec<VolumeBind>, VolumeError> {
+        validate_mounts(mounts).map_err(VolumeError::Invalid)?;
+        let _lock = VOLUME_LOCK.lock().unwrap();
+        let binds = mounts
+            .iter()
+            .map(|mount| {
+                let source = self.volume_dir(&endpoint.namespace, &mount.name);
+                if !source.is_dir() {
+                    return Err(VolumeError::Invalid(format!(
+                        "volume {} not found in namespace {}",
+                        mount.name, endpoint.namespace
+                    )));
+                }
+                Ok(VolumeBind {
+                    source,
+                    destination: mount.mount_path.clone(),
+                    read_only: mount.read_only,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let tree = self.volume_mounts_tree()?;
+        let key = mount_key(endpoint);
+        if mounts.is_empty() {
+            tree.remove(key)
+        } else {
+            let value =
+                serde_json::to_vec(mounts).map_err(|e| VolumeError::Internal(e.to_string()))?;
+            tree.insert(key, value)
+        }
+        .map_err(|e| VolumeError::Internal(e.to_string()))?;
+        Ok(binds)
+    }
+
+    pub(crate) fn unmount_volumes(&self, endpoint: &Endpoint) {
+        if let Err(e) = self.volume_mounts_tree().and_then(|tree| {
+            tree.remove(mount_key(endpoint))
+                .map_err(|e| VolumeError::Internal(e.to_string()))
+        }) {
+            log::warn!("Failed to forget volume mounts of {}: {}", endpoint, e);
+        }
+    }
+
+    /// 函数挂载的卷，没有挂载时返回 None
+    pub(crate) fn volume_mounts(&self, endpoint: &Endpoint) -> Option<Vec<VolumeMount>> {
+        let value = self
+            .volume_mounts_tree()
+            .ok()?
+            .get(mount_key(endpoint))
+            .ok()??;
+        serde_json::from_slice(&value).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(name: &str, path: &str) -> VolumeMount {
+        VolumeMount {
+            name: name.to_string(),
+            mount_path: path.to_string(),
+            read_only: false,
+        }
+    }
+
+    #[test]
+    fn test_validate_mounts() {
+        assert!(
+            validate_mounts(&[mount("cache", "/cache"), mount("models", "/opt/models")]).is_ok()
+        );
+
+        for path in [
+            "cache",
+            "/",
+            "/data/../etc",
+            "/proc/self",
+            "/etc/hosts",
+            "/dev",
+        ] {
+            assert!(validate_mount_path(path).is_err(), "{}", path);
+        }
+        assert!(validate_mount_path("/etc/hosts.d").is_ok());
+
+        assert!(validate_mounts(&[mount("a", "/data"), mount("b", "/data/")]).is_err());
+        assert!(validate_mounts(&[mount("../a", "/data")]).is_err());
+    }
+}
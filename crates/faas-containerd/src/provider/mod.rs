@@ -1,29 +1,65 @@
 pub mod function;
-use std::{collections::HashMap, path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
 
 use gateway::{
     handlers::{
-        function::{DeleteError, DeployError, ListError, ResolveError, UpdateError},
+        config_map::ConfigError,
+        exec::ExecError,
+        function::{
+            DeleteError, DeployError, LifecycleError, ListError, ResolveError, UpdateError,
+        },
+        image::ImageStoreError,
         namespace::NamespaceError,
+        registry::RegistryAuthError,
+        volume::VolumeError,
     },
     provider::Provider,
     types::{
-        function::{Deployment, Query, Status},
+        config_map::ConfigMap,
+        exec::{ExecRequest, ExecSession},
+        function::{Deployment, Query, Status, UpdateStrategy},
+        image::ImageSummary,
         namespace::Namespace,
+        operation::OperationHandle,
+        registry::RegistryAuth,
+        volume::Volume,
     },
 };
 
 pub struct ContainerdProvider {
     // pub ctr_instance_map: tokio::sync::Mutex<HashMap<Query, FunctionInstance>>,
     database: sled::Db,
+    /// 数据库与卷所在的目录
+    data_dir: PathBuf,
+    /// 各函数的就绪探测任务
+    probes: Mutex<HashMap<String, function::readiness::ProbeWorker>>,
+    /// 部署登记待固定的镜像时持有读锁，镜像回收持有写锁
+    image_gc_lock: tokio::sync::RwLock<()>,
+    /// 部署中已解析、尚未写入版本记录的镜像，键为命名空间与 digest，值为部署数
+    pending_pins: Mutex<HashMap<(String, String), usize>>,
 }
 
 impl ContainerdProvider {
     pub fn new<P: AsRef<Path>>(path: P) -> Arc<Self> {
-        Arc::new(ContainerdProvider {
+        let provider = Arc::new(ContainerdProvider {
             // ctr_instance_map: tokio::sync::Mutex::new(HashMap::new()),
-            database: sled::open(path).unwrap(),
-        })
+            database: sled::open(&path).unwrap(),
+            data_dir: path.as_ref().to_path_buf(),
+            probes: Mutex::new(HashMap::new()),
+            image_gc_lock: tokio::sync::RwLock::new(()),
+            pending_pins: Mutex::new(HashMap::new()),
+        });
+        // 恢复已部署函数的隔离规则
+        if let Err(e) = provider.apply_network_policies() {
+            log::error!("Failed to restore network policies: {}", e);
+        }
+        provider
     }
 }
 
@@ -32,8 +68,12 @@ impl Provider for ContainerdProvider {
         self._resolve(function).await
     }
 
-    async fn deploy(&self, param: Deployment) -> Result<(), DeployError> {
-        self._deploy(param).await
+    async fn deploy(
+        &self,
+        param: Deployment,
+        operation: OperationHandle,
+    ) -> Result<(), DeployError> {
+        self._deploy(param, &operation).await
     }
 
     async fn delete(&self, function: Query) -> Result<(), DeleteError> {
@@ -44,14 +84,39 @@ impl Provider for ContainerdProvider {
         self._list(namespace).await
     }
 
-    async fn update(&self, param: Deployment) -> Result<(), UpdateError> {
-        self._update(param).await
+    async fn update(
+        &self,
+        param: Deployment,
+        operation: OperationHandle,
+    ) -> Result<UpdateStrategy, UpdateError> {
+        self._update(param, &operation).await
     }
 
     async fn status(&self, function: Query) -> Result<Status, ResolveError> {
         self._status(function).await
     }
 
+    async fn pause(&self, function: Query) -> Result<(), LifecycleError> {
+        self._pause(function).await
+    }
+
+    async fn resume(&self, function: Query) -> Result<(), LifecycleError> {
+        self._resume(function).await
+    }
+
+    async fn restart(&self, function: Query) -> Result<(), LifecycleError> {
+        self._restart(function).await
+    }
+
+    async fn exec(
+        &self,
+        function: Query,
+        request: ExecRequest,
+        session: ExecSession,
+    ) -> Result<u32, ExecError> {
+        self._exec(function, request, session).await
+    }
+
     async fn create_namespace(
         &self,
         namespace: String,
@@ -79,4 +144,88 @@ impl Provider for ContainerdProvider {
     async fn namespace_list(&self) -> Result<Vec<Namespace>, NamespaceError> {
         self._namespace_list().await
     }
+
+    async fn set_registry_auth(
+        &self,
+        namespace: String,
+        auth: RegistryAuth,
+    ) -> Result<(), RegistryAuthError> {
+        self._set_registry_auth(namespace, auth).await
+    }
+
+    async fn get_registry_auth(&self, namespace: String) -> Result<Vec<String>, RegistryAuthError> {
+        self._get_registry_auth(namespace).await
+    }
+
+    async fn delete_registry_auth(&self, namespace: String) -> Result<(), RegistryAuthError> {
+        self._delete_registry_auth(namespace).await
+    }
+
+    async fn import_image(
+        &self,
+        namespace: String,
+        archive: tokio::sync::mpsc::Receiver<Bytes>,
+    ) -> Result<Vec<String>, ImageStoreError> {
+        self._import_image(namespace, archive).await
+    }
+
+    async fn list_images(&self, namespace: String) -> Result<Vec<ImageSummary>, ImageStoreError> {
+        self._list_images(namespace).await
+    }
+
+    async fn prune_images(
+        &self,
+        namespace: Option<String>,
+    ) -> Result<Vec<String>, ImageStoreError> {
+        self._prune_images(namespace).await
+    }
+
+    async fn create_config(&self, config: ConfigMap) -> Result<(), ConfigError> {
+        self._create_config(config).await
+    }
+
+    async fn update_config(&self, config: ConfigMap) -> Result<Vec<String>, ConfigError> {
+        self._update_config(config).await
+    }
+
+    async fn restart_config_users(
+        &self,
+        namespace: Option<String>,
+        name: String,
+        operation: OperationHandle,
+    ) -> Result<(), ConfigError> {
+        self._restart_config_users(namespace, name, operation).await
+    }
+
+    async fn get_config(
+        &self,
+        namespace: Option<String>,
+        name: String,
+    ) -> Result<ConfigMap, ConfigError> {
+        self._get_config(namespace, name).await
+    }
+
+    async fn list_configs(&self, namespace: Option<String>) -> Result<Vec<ConfigMap>, ConfigError> {
+        self._list_configs(namespace).await
+    }
+
+    async fn delete_config(
+        &self,
+        namespace: Option<String>,
+        name: String,
+    ) -> Result<(), ConfigError> {
+        self._delete_config(namespace, name).await
+    }
+
+    async fn create_volume(&self, namespace: String, name: String) -> Result<(), VolumeError> {
+        self._create_volume(namespace, name).await
+    }
+
+    async fn list_volumes(&self, namespace: String) -> Result<Vec<Volume>, VolumeError> {
+        self._list_volumes(namespace).await
+    }
+
+    async fn delete_volume(&self, namespace: String, name: String) -> Result<(), VolumeError> {
+        self._delete_volume(namespace, name).await
+    }
 }
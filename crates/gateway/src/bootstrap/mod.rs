@@ -4,7 +4,7 @@ use crate::{
     models::db,
     oauth::auth_handler,
     provider::Provider,
-    types::config::FaaSConfig,
+    types::{config::FaaSConfig, operation::OperationStore},
 };
 use actix_web::{
     App, HttpServer,
@@ -21,6 +21,7 @@ pub fn config_app<P: Provider>(
     provider: Arc<P>,
     db_pool: Pool<AsyncPgConnection>,
     faas_config: FaaSConfig,
+    operations: web::Data<OperationStore>,
 ) -> impl FnOnce(&mut ServiceConfig) {
     // let _registry = Registry::new();
     let provider = web::Data::from(provider);
@@ -33,6 +34,7 @@ pub fn config_app<P: Provider>(
             .app_data(provider)
             .app_data(web::Data::new(db_pool.clone()))
             .app_data(web::Data::new(faas_config.clone()))
+            .app_data(operations)
             .service(
                 web::scope("/auth")
                     .route(
@@ -55,6 +57,26 @@ pub fn config_app<P: Provider>(
                         web::resource("/function/{functionName}")
                             .route(web::get().to(handlers::function::status::<P>)),
                     )
+                    .service(
+                        web::resource("/function/{functionName}/exec")
+                            .route(web::get().to(handlers::exec::exec::<P>)),
+                    )
+                    .service(
+                        web::resource("/function/{functionName}/pause")
+                            .route(web::post().to(handlers::function::pause::<P>)),
+                    )
+                    .service(
+                        web::resource("/function/{functionName}/resume")
+                            .route(web::post().to(handlers::function::resume::<P>)),
+                    )
+                    .service(
+                        web::resource("/function/{functionName}/restart")
+                            .route(web::post().to(handlers::function::restart::<P>)),
+                    )
+                    .service(
+                        web::resource("/operations/{id}")
+                            .route(web::get().to(handlers::operation::get)),
+                    )
                     .service(
                         web::resource("/namespace/{namespace}")
                             .route(web::to(handlers::namespace::mut_namespace::<P>)),
@@ -62,6 +84,41 @@ pub fn config_app<P: Provider>(
                     .service(
                         web::resource("/namespaces")
                             .route(web::get().to(handlers::namespace::namespace_list::<P>)),
+                    )
+                    .service(
+                        web::resource("/images")
+                            .route(web::get().to(handlers::image::list::<P>))
+                            .route(web::post().to(handlers::image::import::<P>)),
+                    )
+                    .service(
+                        web::resource("/images/prune")
+                            .route(web::post().to(handlers::image::prune::<P>)),
+                    )
+                    .service(
+                        web::resource("/registry-auth/{namespace}")
+                            .route(web::get().to(handlers::registry::get_registry_auth::<P>))
+                            .route(web::put().to(handlers::registry::set_registry_auth::<P>))
+                            .route(web::delete().to(handlers::registry::delete_registry_auth::<P>)),
+                    )
+                    .service(
+                        web::resource("/volumes")
+                            .route(web::get().to(handlers::volume::list::<P>))
+                            .route(web::post().to(handlers::volume::create::<P>)),
+                    )
+                    .service(
+                        web::resource("/volumes/{name}")
+                            .route(web::delete().to(handlers::volume::delete::<P>)),
+                    )
+                    .service(
+                        web::resource("/configs")
+                            .route(web::get().to(handlers::config_map::list::<P>))
+                            .route(web::post().to(handlers::config_map::create::<P>))
+                            .route(web::put().to(handlers::config_map::update::<P>)),
+                    )
+                    .service(
+                        web::resource("/configs/{name}")
+                            .route(web::get().to(handlers::config_map::get::<P>))
+                            .route(web::delete().to(handlers::config_map::delete::<P>)),
                     ),
                 //         .service(
                 //             web::resource("/scale-function/{name}")
@@ -113,12 +170,15 @@ pub async fn serve<P: Provider>(provider: Arc<P>) -> std::io::Result<Server> {
     let port = config.tcp_port.unwrap_or(8080);
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let db_pool = db::create_pool(&database_url).await?;
+    // 操作记录需要在所有 worker 间共享
+    let operations = web::Data::new(OperationStore::default());
     // let pool = setup_test_db().await.expect("failed to set up test");
     let server = HttpServer::new(move || {
         App::new().configure(config_app(
             provider.clone(),
             db_pool.clone(),
             config.clone(),
+            operations.clone(),
         ))
     })
     .bind(("0.0.0.0", port))?
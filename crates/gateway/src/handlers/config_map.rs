@@ -0,0 +1,128 @@
+use crate::{
+    handlers::function::accepted_operation,
+    provider::Provider,
+    types::{
+        config_map::ConfigMap,
+        operation::{OperationKind, OperationStore},
+    },
+};
+use actix_http::StatusCode;
+use actix_web::{HttpResponse, ResponseError, web};
+use derive_more::Display;
+use serde::Deserialize;
+
+#[derive(Debug, Display)]
+pub enum ConfigError {
+    #[display("Invalid: {}", _0)]
+    Invalid(String),
+    #[display("AlreadyExists: {}", _0)]
+    AlreadyExists(String),
+    #[display("NotFound: {}", _0)]
+    NotFound(String),
+    /// The config is still referenced by a function
+    #[display("InUse: {}", _0)]
+    InUse(String),
+    #[display("Internal: {}", _0)]
+    Internal(String),
+}
+
+impl ResponseError for ConfigError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ConfigError::Invalid(_) => StatusCode::BAD_REQUEST,
+            ConfigError::AlreadyExists(_) => StatusCode::CONFLICT,
+            ConfigError::NotFound(_) => StatusCode::NOT_FOUND,
+            ConfigError::InUse(_) => StatusCode::CONFLICT,
+            ConfigError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConfigParam {
+    namespace: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateParam {
+    /// Restart the functions using the config one after another
+    #[serde(default)]
+    restart: bool,
+}
+
+pub async fn list<P: Provider>(
+    provider: web::Data<P>,
+    info: web::Query<ConfigParam>,
+) -> Result<HttpResponse, ConfigError> {
+    (*provider)
+        .list_configs(info.into_inner().namespace)
+        .await
+        .map(|configs| HttpResponse::Ok().json(configs))
+}
+
+pub async fn get<P: Provider>(
+    provider: web::Data<P>,
+    name: web::Path<String>,
+    info: web::Query<ConfigParam>,
+) -> Result<HttpResponse, ConfigError> {
+    (*provider)
+        .get_config(info.into_inner().namespace, name.into_inner())
+        .await
+        .map(|config| HttpResponse::Ok().json(config))
+}
+
+pub async fn create<P: Provider>(
+    provider: web::Data<P>,
+    info: web::Json<ConfigMap>,
+) -> Result<HttpResponse, ConfigError> {
+    let config = info.into_inner();
+    let name = config.name.clone();
+    (*provider)
+        .create_config(config)
+        .await
+        .map(|()| HttpResponse::Created().body(format!("config {} was created successfully", name)))
+}
+
+/// Files of running functions are refreshed in place. Responds with the affected
+/// functions, or with `?restart=true` with `202 Accepted` and the operation
+/// restarting them one at a time.
+pub async fn update<P: Provider>(
+    provider: web::Data<P>,
+    operations: web::Data<OperationStore>,
+    param: web::Query<UpdateParam>,
+    info: web::Json<ConfigMap>,
+) -> Result<HttpResponse, ConfigError> {
+    let config = info.into_inner();
+    let (name, namespace) = (config.name.clone(), config.namespace.clone());
+    let functions = (*provider).update_config(config).await?;
+    if !param.restart {
+        return Ok(HttpResponse::Ok().json(functions));
+    }
+    let operation = operations.start(OperationKind::Restart, name.clone(), namespace.clone());
+    log::info!(
+        "Restart of the functions using config {} started as operation {:?}",
+        name,
+        operation.get().map(|op| op.id)
+    );
+    let handle = operation.clone();
+    actix_web::rt::spawn(async move {
+        let result = (*provider)
+            .restart_config_users(namespace, name, handle.clone())
+            .await;
+        handle.finish(result.map_err(|e| e.to_string()));
+    });
+    Ok(accepted_operation(&operation))
+}
+
+/// Fails with `409 Conflict` while a function still references the config
+pub async fn delete<P: Provider>(
+    provider: web::Data<P>,
+    name: web::Path<String>,
+    info: web::Query<ConfigParam>,
+) -> Result<HttpResponse, ConfigError> {
+    let name = name.into_inner();
+    (*provider)
+        .delete_config(info.into_inner().namespace, name.clone())
+        .await
+        .map(|()| HttpResponse::Ok().body(format!("config {} was deleted successfully", name)))
+}
@@ -0,0 +1,225 @@
+use actix_http::{
+    StatusCode,
+    ws::{self, CloseCode, Codec, Frame, Message},
+};
+use actix_web::{
+    HttpRequest, HttpResponse, ResponseError,
+    body::BodyStream,
+    web::{self, Bytes, BytesMut},
+};
+use chrono::Utc;
+use derive_more::derive::Display;
+use futures_util::StreamExt;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    handlers::function::ResolveError,
+    oauth::auth_handler::{is_admin, user_id},
+    provider::Provider,
+    types::{
+        config::FaaSConfig,
+        exec::{
+            ExecAudit, ExecControl, ExecEvent, ExecRequest, ExecSession, TerminalSize,
+            parse_command,
+        },
+        function::Query,
+    },
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ExecParam {
+    namespace: Option<String>,
+    /// 以空白分隔的命令，未指定时使用 `EXEC_COMMAND`
+    command: Option<String>,
+    cols: Option<u16>,
+    rows: Option<u16>,
+}
+
+#[derive(Debug, Display)]
+pub enum ExecError {
+    #[display("Forbidden: {}", _0)]
+    Forbidden(String),
+    #[display("NotFound: {}", _0)]
+    NotFound(String),
+    /// The function cannot run a process now, e.g. it is paused or stopped
+    #[display("Conflict: {}", _0)]
+    Conflict(String),
+    #[display("Invalid: {}", _0)]
+    Invalid(String),
+    #[display("Internal: {}", _0)]
+    Internal(String),
+}
+
+impl ResponseError for ExecError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ExecError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ExecError::NotFound(_) => StatusCode::NOT_FOUND,
+            ExecError::Conflict(_) => StatusCode::CONFLICT,
+            ExecError::Invalid(_) => StatusCode::BAD_REQUEST,
+            ExecError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// 管理员通过 WebSocket 在函数中打开终端。二进制帧为终端的输入输出，
+/// 文本帧为 [`ExecControl`] 与 [`ExecEvent`]，每次会话的开始与结束都会写入审计日志
+pub async fn exec<P: Provider>(
+    req: HttpRequest,
+    payload: web::Payload,
+    provider: web::Data<P>,
+    config: web::Data<FaaSConfig>,
+    function_name: web::Path<String>,
+    param: web::Query<ExecParam>,
+) -> Result<HttpResponse, ExecError> {
+    if !is_admin(&req) {
+        return Err(ExecError::Forbidden("exec requires an admin".to_string()));
+    }
+    let param = param.into_inner();
+    let query = Query {
+        function_name: function_name.into_inner(),
+        namespace: param.namespace,
+    };
+    // 升级之前确认函数存在，升级之后的错误只能通过 WebSocket 返回
+    provider.status(query.clone()).await.map_err(|e| match e {
+        ResolveError::NotFound(e) => ExecError::NotFound(e),
+        e => ExecError::Internal(e.to_string()),
+    })?;
+    let mut response = ws::handshake(req.head()).map_err(|e| ExecError::Invalid(e.to_string()))?;
+
+    let default = TerminalSize::default();
+    let request = ExecRequest {
+        command: parse_command(param.command.as_deref(), &config.exec_command),
+        size: TerminalSize {
+            cols: param.cols.unwrap_or(default.cols),
+            rows: param.rows.unwrap_or(default.rows),
+        },
+    };
+    let mut audit = ExecAudit {
+        id: uuid::Uuid::new_v4(),
+        user: user_id(&req),
+        function_name: query.function_name.clone(),
+        namespace: query.namespace.clone(),
+        command: request.command.clone(),
+        peer: req
+            .connection_info()
+            .realip_remote_addr()
+            .map(str::to_string),
+        started_at: Utc::now(),
+        finished_at: None,
+        exit_code: None,
+        error: None,
+    };
+    // 写不进审计日志的会话不允许开始
+    config
+        .exec_audit
+        .record(&audit)
+        .map_err(|e| ExecError::Internal(format!("failed to write exec audit log: {}", e)))?;
+
+    let (stdin_tx, stdin) = mpsc::channel(16);
+    let (resize_tx, resize) = mpsc::channel(4);
+    let (output, mut output_rx) = mpsc::channel::<Bytes>(16);
+    let (frames, frames_rx) = mpsc::channel(16);
+    let session = ExecSession {
+        stdin,
+        resize,
+        output,
+    };
+
+    actix_web::rt::spawn(read_frames(payload, stdin_tx, resize_tx, frames.clone()));
+    let exec_audit = config.exec_audit.clone();
+    actix_web::rt::spawn(async move {
+        let forward = async {
+            while let Some(data) = output_rx.recv().await {
+                if frames.send(Message::Binary(data)).await.is_err() {
+                    break;
+                }
+            }
+        };
+        let (result, ()) = tokio::join!(provider.exec(query, request, session), forward);
+
+        let event = match &result {
+            Ok(code) => ExecEvent::Exit { code: *code },
+            Err(e) => ExecEvent::Error {
+                message: e.to_string(),
+            },
+        };
+        if let Ok(event) = serde_json::to_string(&event) {
+            let _ = frames.send(Message::Text(event.into())).await;
+        }
+        let _ = frames
+            .send(Message::Close(Some(CloseCode::Normal.into())))
+            .await;
+
+        audit.finished_at = Some(Utc::now());
+        match result {
+            Ok(code) => audit.exit_code = Some(code),
+            Err(e) => audit.error = Some(e.to_string()),
+        }
+        if let Err(e) = exec_audit.record(&audit) {
+            log::error!("Failed to write exec audit log: {}", e);
+        }
+    });
+
+    let body = futures_util::stream::unfold(
+        (frames_rx, Codec::new(), false),
+        |(mut rx, mut codec, closed)| async move {
+            if closed {
+                return None;
+            }
+            let message = rx.recv().await?;
+            let closed = matches!(message, Message::Close(_));
+            let mut buf = BytesMut::new();
+            let item = codec.encode(message, &mut buf).map(|()| buf.freeze());
+            Some((item, (rx, codec, closed)))
+        },
+    );
+    response
+        .message_body(BodyStream::new(body))
+        .map(|res| HttpResponse::from(res).map_into_boxed_body())
+        .map_err(|e| ExecError::Internal(e.to_string()))
+}
+
+/// 解码客户端的帧，客户端断开时丢弃 `stdin`，provider 随后结束进程
+async fn read_frames(
+    mut payload: web::Payload,
+    stdin: mpsc::Sender<Bytes>,
+    resize: mpsc::Sender<TerminalSize>,
+    frames: mpsc::Sender<Message>,
+) {
+    let mut codec = Codec::new();
+    let mut buf = BytesMut::new();
+    while let Some(Ok(chunk)) = payload.next().await {
+        buf.extend_from_slice(&chunk);
+        loop {
+            let frame = match codec.decode(&mut buf) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(e) => {
+                    log::warn!("Invalid exec frame: {}", e);
+                    return;
+                }
+            };
+            match frame {
+                Frame::Binary(data) => {
+                    if stdin.send(data).await.is_err() {
+                        return;
+                    }
+                }
+                Frame::Text(text) => match serde_json::from_slice::<ExecControl>(&text) {
+                    Ok(ExecControl::Resize(size)) => {
+                        let _ = resize.send(size).await;
+                    }
+                    Err(e) => log::warn!("Invalid exec control message: {}", e),
+                },
+                Frame::Ping(data) => {
+                    let _ = frames.send(Message::Pong(data)).await;
+                }
+                Frame::Close(_) => return,
+                Frame::Pong(_) | Frame::Continuation(_) => {}
+            }
+        }
+    }
+}
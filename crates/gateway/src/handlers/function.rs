@@ -1,37 +1,143 @@
+use crate::oauth::auth_handler::is_admin;
 use crate::provider::Provider;
-use crate::types::function::{Delete, Deployment, Query};
+use crate::types::{
+    config::FaaSConfig,
+    function::{Delete, Deployment, Query},
+    operation::{OperationHandle, OperationKind, OperationStore},
+    security::SecurityOptions,
+};
 use actix_http::StatusCode;
 use actix_web::ResponseError;
-use actix_web::{HttpResponse, web};
+use actix_web::http::header::{ContentType, RETRY_AFTER};
+use actix_web::{HttpRequest, HttpResponse, web};
 use derive_more::derive::Display;
 use serde::Deserialize;
 
+/// `Retry-After` seconds sent to callers of a paused function
+pub const PAUSED_RETRY_AFTER_SECS: u64 = 10;
+
+#[derive(Debug, Deserialize)]
+pub struct DeployParam {
+    /// 为真时立即返回操作 ID，通过 `/system/operations/{id}` 查询进度
+    #[serde(rename = "async", default)]
+    is_async: bool,
+}
+
 // 参考响应状态 https://github.com/openfaas/faas/blob/7803ea1861f2a22adcbcfa8c79ed539bc6506d5b/api-docs/spec.openapi.yml#L121C1-L140C45
 // 请求体反序列化失败，自动返回400错误
 pub async fn deploy<P: Provider>(
+    req: HttpRequest,
     provider: web::Data<P>,
+    config: web::Data<FaaSConfig>,
+    operations: web::Data<OperationStore>,
+    param: web::Query<DeployParam>,
     info: web::Json<Deployment>,
 ) -> Result<HttpResponse, DeployError> {
-    let function_name = info.0.function_name.clone();
-    (*provider).deploy(info.0).await.map(|()| {
-        HttpResponse::Accepted().body(format!(
-            "function {} was created successfully",
-            function_name
-        ))
-    })
+    let deployment = info.into_inner();
+    deployment.validate_names().map_err(DeployError::Invalid)?;
+    check_security(&req, &deployment, &config)?;
+    let function_name = deployment.function_name.clone();
+    if param.is_async {
+        let operation = start_operation(&operations, OperationKind::Deploy, &deployment);
+        let handle = operation.clone();
+        actix_web::rt::spawn(async move {
+            let result = (*provider).deploy(deployment, handle.clone()).await;
+            handle.finish(result.map_err(|e| e.to_string()));
+        });
+        return Ok(accepted_operation(&operation));
+    }
+    (*provider)
+        .deploy(deployment, OperationHandle::detached())
+        .await
+        .map(|()| {
+            HttpResponse::Accepted().body(format!(
+                "function {} was created successfully",
+                function_name
+            ))
+        })
 }
 
 pub async fn update<P: Provider>(
+    req: HttpRequest,
     provider: web::Data<P>,
+    config: web::Data<FaaSConfig>,
+    operations: web::Data<OperationStore>,
+    param: web::Query<DeployParam>,
     info: web::Json<Deployment>,
 ) -> Result<HttpResponse, UpdateError> {
-    let function_name = info.0.function_name.clone();
-    (*provider).update(info.0).await.map(|()| {
-        HttpResponse::Accepted().body(format!(
-            "function {} was updated successfully",
-            function_name
-        ))
-    })
+    let deployment = info.into_inner();
+    check_security(&req, &deployment, &config).map_err(|e| match e {
+        DeployError::Forbidden(e) => UpdateError::Forbidden(e),
+        DeployError::Invalid(e) => UpdateError::Invalid(e),
+        DeployError::InternalError(e) => UpdateError::Internal(e),
+    })?;
+    let function_name = deployment.function_name.clone();
+    if param.is_async {
+        let operation = start_operation(&operations, OperationKind::Update, &deployment);
+        let handle = operation.clone();
+        actix_web::rt::spawn(async move {
+            let result = (*provider).update(deployment, handle.clone()).await;
+            handle.finish(
+                result
+                    .map(|strategy| handle.set_strategy(strategy))
+                    .map_err(|e| e.to_string()),
+            );
+        });
+        return Ok(accepted_operation(&operation));
+    }
+    (*provider)
+        .update(deployment, OperationHandle::detached())
+        .await
+        .map(|strategy| {
+            HttpResponse::Accepted().body(format!("function {} was {}", function_name, strategy))
+        })
+}
+
+fn start_operation(
+    operations: &OperationStore,
+    kind: OperationKind,
+    deployment: &Deployment,
+) -> OperationHandle {
+    let operation = operations.start(
+        kind,
+        deployment.function_name.clone(),
+        deployment.namespace.clone(),
+    );
+    log::info!(
+        "{} of function {} started as operation {:?}",
+        kind,
+        deployment.function_name,
+        operation.get().map(|op| op.id)
+    );
+    operation
+}
+
+pub(crate) fn accepted_operation(operation: &OperationHandle) -> HttpResponse {
+    let snapshot = operation.get();
+    let location = snapshot
+        .as_ref()
+        .map(|op| format!("/system/operations/{}", op.id))
+        .unwrap_or_default();
+    HttpResponse::Accepted()
+        .insert_header((actix_web::http::header::LOCATION, location))
+        .json(snapshot)
+}
+
+/// 非管理员请求的 capabilities 与 seccomp 配置必须在管理员的策略之内
+fn check_security(
+    req: &HttpRequest,
+    deployment: &Deployment,
+    config: &FaaSConfig,
+) -> Result<(), DeployError> {
+    let options = SecurityOptions::from_annotations(deployment.annotations.as_ref())
+        .map_err(DeployError::Invalid)?;
+    if is_admin(req) {
+        return Ok(());
+    }
+    config
+        .security_policy
+        .check(&options)
+        .map_err(DeployError::Forbidden)
 }
 
 pub async fn delete<P: Provider>(
@@ -84,12 +190,63 @@ pub async fn status<P: Provider>(
     Ok(HttpResponse::Ok().json(status))
 }
 
+fn lifecycle_query(function_name: web::Path<String>, info: web::Query<StatusParam>) -> Query {
+    Query {
+        function_name: function_name.into_inner(),
+        namespace: info.into_inner().namespace,
+    }
+}
+
+/// 冻结函数的进程，恢复前对它的调用返回 503
+pub async fn pause<P: Provider>(
+    provider: web::Data<P>,
+    function_name: web::Path<String>,
+    info: web::Query<StatusParam>,
+) -> Result<HttpResponse, LifecycleError> {
+    let query = lifecycle_query(function_name, info);
+    let function_name = query.function_name.clone();
+    (*provider)
+        .pause(query)
+        .await
+        .map(|()| HttpResponse::Ok().body(format!("function {} was paused", function_name)))
+}
+
+pub async fn resume<P: Provider>(
+    provider: web::Data<P>,
+    function_name: web::Path<String>,
+    info: web::Query<StatusParam>,
+) -> Result<HttpResponse, LifecycleError> {
+    let query = lifecycle_query(function_name, info);
+    let function_name = query.function_name.clone();
+    (*provider)
+        .resume(query)
+        .await
+        .map(|()| HttpResponse::Ok().body(format!("function {} was resumed", function_name)))
+}
+
+/// 在原有的容器中重新启动函数，函数重新就绪后返回
+pub async fn restart<P: Provider>(
+    provider: web::Data<P>,
+    function_name: web::Path<String>,
+    info: web::Query<StatusParam>,
+) -> Result<HttpResponse, LifecycleError> {
+    let query = lifecycle_query(function_name, info);
+    let function_name = query.function_name.clone();
+    (*provider)
+        .restart(query)
+        .await
+        .map(|()| HttpResponse::Ok().body(format!("function {} was restarted", function_name)))
+}
+
 // TODO: 为 Errors 添加错误信息
 
 #[derive(Debug, Display)]
 pub enum DeployError {
     #[display("Invalid: {}", _0)]
     Invalid(String),
+    /// The deployment asks for something the user is not allowed to
+    #[display("Forbidden: {}", _0)]
+    Forbidden(String),
     #[display("Internal: {}", _0)]
     InternalError(String),
 }
@@ -108,12 +265,29 @@ pub enum DeleteError {
 pub enum ResolveError {
     #[display("NotFound: {}", _0)]
     NotFound(String),
+    /// The function exists but no replica has passed its readiness probe
+    #[display("NotReady: {}", _0)]
+    NotReady(String),
+    /// The function is paused, callers should retry after it is resumed
+    #[display("Paused: {}", _0)]
+    Paused(String),
     #[display("Invalid: {}", _0)]
     Invalid(String),
     #[display("Internal: {}", _0)]
     Internal(String),
 }
 
+#[derive(Debug, Display)]
+pub enum LifecycleError {
+    #[display("NotFound: {}", _0)]
+    NotFound(String),
+    /// The function is not in a state the action applies to, e.g. resuming a running function
+    #[display("Conflict: {}", _0)]
+    Conflict(String),
+    #[display("Internal: {}", _0)]
+    Internal(String),
+}
+
 #[derive(Debug, Display)]
 pub enum ListError {
     #[display("Internal: {}", _0)]
@@ -126,6 +300,8 @@ pub enum ListError {
 pub enum UpdateError {
     #[display("Invalid: {}", _0)]
     Invalid(String),
+    #[display("Forbidden: {}", _0)]
+    Forbidden(String),
     #[display("Internal: {}", _0)]
     Internal(String),
     #[display("NotFound: {}", _0)]
@@ -136,6 +312,7 @@ impl ResponseError for DeployError {
     fn status_code(&self) -> StatusCode {
         match self {
             DeployError::Invalid(_) => StatusCode::BAD_REQUEST,
+            DeployError::Forbidden(_) => StatusCode::FORBIDDEN,
             DeployError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -155,10 +332,30 @@ impl ResponseError for ResolveError {
     fn status_code(&self) -> StatusCode {
         match self {
             ResolveError::NotFound(_) => StatusCode::NOT_FOUND,
+            ResolveError::NotReady(_) | ResolveError::Paused(_) => StatusCode::SERVICE_UNAVAILABLE,
             ResolveError::Invalid(_) => StatusCode::BAD_REQUEST,
             ResolveError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut res = HttpResponse::build(self.status_code());
+        res.insert_header(ContentType::plaintext());
+        if let ResolveError::Paused(_) = self {
+            res.insert_header((RETRY_AFTER, PAUSED_RETRY_AFTER_SECS.to_string()));
+        }
+        res.body(self.to_string())
+    }
+}
+
+impl ResponseError for LifecycleError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            LifecycleError::NotFound(_) => StatusCode::NOT_FOUND,
+            LifecycleError::Conflict(_) => StatusCode::CONFLICT,
+            LifecycleError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
 }
 
 impl ResponseError for ListError {
@@ -174,6 +371,7 @@ impl ResponseError for UpdateError {
     fn status_code(&self) -> StatusCode {
         match self {
             UpdateError::Invalid(_) => StatusCode::BAD_REQUEST,
+            UpdateError::Forbidden(_) => StatusCode::FORBIDDEN,
             UpdateError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
             UpdateError::NotFound(_) => StatusCode::NOT_FOUND,
         }
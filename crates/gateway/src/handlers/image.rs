@@ -0,0 +1,94 @@
+use crate::oauth::auth_handler::is_admin;
+use crate::provider::Provider;
+use actix_http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, ResponseError, web};
+use derive_more::Display;
+use futures_util::StreamExt;
+use serde::Deserialize;
+
+#[derive(Debug, Display)]
+pub enum ImageStoreError {
+    #[display("Invalid: {}", _0)]
+    Invalid(String),
+    #[display("Forbidden: {}", _0)]
+    Forbidden(String),
+    #[display("NotFound: {}", _0)]
+    NotFound(String),
+    #[display("Internal: {}", _0)]
+    Internal(String),
+}
+
+impl ResponseError for ImageStoreError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ImageStoreError::Invalid(_) => StatusCode::BAD_REQUEST,
+            ImageStoreError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ImageStoreError::NotFound(_) => StatusCode::NOT_FOUND,
+            ImageStoreError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImageParam {
+    namespace: String,
+}
+
+/// 上传 OCI layout 或 `docker save` 格式的镜像归档，边接收边转发给 provider，
+/// 返回导入的镜像名
+pub async fn import<P: Provider>(
+    provider: web::Data<P>,
+    info: web::Query<ImageParam>,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, ImageStoreError> {
+    let namespace = info.into_inner().namespace;
+    let (tx, rx) = tokio::sync::mpsc::channel(16);
+    let upload = async move {
+        while let Some(chunk) = payload.next().await {
+            let chunk = chunk.map_err(|e| {
+                ImageStoreError::Invalid(format!("failed to read image archive: {}", e))
+            })?;
+            if tx.send(chunk).await.is_err() {
+                // provider stopped reading, its result tells why
+                break;
+            }
+        }
+        Ok(())
+    };
+    // an upload failure drops the import, which cancels the transfer in the provider
+    let ((), names) = futures_util::try_join!(upload, (*provider).import_image(namespace, rx))?;
+    Ok(HttpResponse::Created().json(names))
+}
+
+pub async fn list<P: Provider>(
+    provider: web::Data<P>,
+    info: web::Query<ImageParam>,
+) -> Result<HttpResponse, ImageStoreError> {
+    (*provider)
+        .list_images(info.into_inner().namespace)
+        .await
+        .map(|images| HttpResponse::Ok().json(images))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PruneParam {
+    namespace: Option<String>,
+}
+
+/// 删除未被任何函数（含可回滚版本）使用的镜像，不指定命名空间时清理全部命名空间。
+/// 只允许管理员调用
+pub async fn prune<P: Provider>(
+    req: HttpRequest,
+    provider: web::Data<P>,
+    info: web::Query<PruneParam>,
+) -> Result<HttpResponse, ImageStoreError> {
+    if !is_admin(&req) {
+        return Err(ImageStoreError::Forbidden(
+            "pruning images requires an admin".to_string(),
+        ));
+    }
+    (*provider)
+        .prune_images(info.into_inner().namespace)
+        .await
+        .map(|removed| HttpResponse::Ok().json(removed))
+}
@@ -1,6 +1,12 @@
+pub mod config_map;
+pub mod exec;
 pub mod function;
+pub mod image;
 pub mod namespace;
+pub mod operation;
 pub mod proxy;
+pub mod registry;
+pub mod volume;
 
 #[derive(Debug, thiserror::Error)]
 pub struct FaasError {
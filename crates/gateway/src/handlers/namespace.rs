@@ -1,12 +1,19 @@
-use crate::{provider::Provider, types::namespace::Namespace};
+use crate::{
+    oauth::auth_handler::is_admin,
+    provider::Provider,
+    types::namespace::{Namespace, keep_admin_labels},
+};
 use actix_http::{Method, StatusCode};
 use actix_web::{HttpRequest, HttpResponse, ResponseError, web};
 use derive_more::Display;
+use std::collections::HashMap;
 
 #[derive(Debug, Display)]
 pub enum NamespaceError {
     #[display("Invalid: {}", _0)]
     Invalid(String),
+    #[display("Forbidden: {}", _0)]
+    Forbidden(String),
     #[display("AlreadyExists: {}", _0)]
     AlreadyExists(String),
     #[display("NotFound: {}", _0)]
@@ -21,6 +28,7 @@ impl ResponseError for NamespaceError {
     fn status_code(&self) -> StatusCode {
         match self {
             NamespaceError::Invalid(_) => StatusCode::BAD_REQUEST,
+            NamespaceError::Forbidden(_) => StatusCode::FORBIDDEN,
             NamespaceError::AlreadyExists(_) => StatusCode::BAD_REQUEST,
             NamespaceError::NotFound(_) => StatusCode::NOT_FOUND,
             NamespaceError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
@@ -39,7 +47,7 @@ pub async fn mut_namespace<P: Provider>(
         return Err(NamespaceError::Invalid("namespace is required".to_string()));
     }
     let namespace = namespace.unwrap();
-    let labels;
+    let mut labels;
     match *req.method() {
         Method::POST => {
             match info {
@@ -52,6 +60,10 @@ pub async fn mut_namespace<P: Provider>(
                     ));
                 }
             }
+            if !is_admin(&req) {
+                keep_admin_labels(&HashMap::new(), labels.clone())
+                    .map_err(NamespaceError::Forbidden)?;
+            }
             (*provider)
                 .create_namespace(namespace.to_string(), labels)
                 .await
@@ -78,6 +90,12 @@ pub async fn mut_namespace<P: Provider>(
                     ));
                 }
             }
+            // 更新会替换全部标签，非管理员省略的管理员标签沿用当前的值
+            if !is_admin(&req) {
+                let current = (*provider).get_namespace(namespace.to_string()).await?;
+                labels = keep_admin_labels(&current.labels, labels)
+                    .map_err(NamespaceError::Forbidden)?;
+            }
             (*provider)
                 .update_namespace(namespace.to_string(), labels)
                 .await
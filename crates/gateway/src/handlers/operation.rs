@@ -0,0 +1,29 @@
+use crate::types::operation::OperationStore;
+use actix_http::StatusCode;
+use actix_web::{HttpResponse, ResponseError, web};
+use derive_more::Display;
+
+#[derive(Debug, Display)]
+pub enum OperationError {
+    #[display("NotFound: {}", _0)]
+    NotFound(String),
+}
+
+impl ResponseError for OperationError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            OperationError::NotFound(_) => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+pub async fn get(
+    operations: web::Data<OperationStore>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, OperationError> {
+    let id = id.into_inner();
+    operations
+        .get(&id)
+        .map(|op| HttpResponse::Ok().json(op))
+        .ok_or_else(|| OperationError::NotFound(format!("operation {} not found", id)))
+}
@@ -1,9 +1,16 @@
 use std::str::FromStr;
 
 use actix_http::Method;
-use actix_web::{HttpRequest, HttpResponse, error::ErrorMethodNotAllowed, web};
+use actix_web::{
+    HttpRequest, HttpResponse,
+    error::{ErrorMethodNotAllowed, ErrorServiceUnavailable},
+    web,
+};
 
-use crate::{provider::Provider, proxy::proxy_handler::proxy_request, types::function::Query};
+use crate::{
+    handlers::function::ResolveError, provider::Provider, proxy::proxy_handler::proxy_request,
+    types::function::Query,
+};
 
 pub const PROXY_DISPATCH_PATH: &str = "/{any:.+}";
 
@@ -58,10 +65,12 @@ pub async fn proxy<P: Provider>(
         | Method::PATCH
         | Method::HEAD
         | Method::OPTIONS => {
-            let upstream = provider
-                .resolve(function)
-                .await
-                .map_err(|e| ErrorMethodNotAllowed(format!("Invalid function name {e}")))?;
+            let upstream = provider.resolve(function).await.map_err(|e| match e {
+                ResolveError::NotReady(_) => ErrorServiceUnavailable(e.to_string()),
+                // 携带 Retry-After，调用方无需等待被冻结的进程
+                ResolveError::Paused(_) => e.into(),
+                _ => ErrorMethodNotAllowed(format!("Invalid function name {e}")),
+            })?;
             log::trace!("upstream: {:?}", upstream);
             proxy_request(&req, payload, upstream, &meta.path).await
         }
@@ -0,0 +1,68 @@
+use crate::{provider::Provider, types::registry::RegistryAuth};
+use actix_http::StatusCode;
+use actix_web::{HttpResponse, ResponseError, web};
+use derive_more::Display;
+
+#[derive(Debug, Display)]
+pub enum RegistryAuthError {
+    #[display("Invalid: {}", _0)]
+    Invalid(String),
+    #[display("NotFound: {}", _0)]
+    NotFound(String),
+    #[display("Internal: {}", _0)]
+    Internal(String),
+}
+
+impl ResponseError for RegistryAuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RegistryAuthError::Invalid(_) => StatusCode::BAD_REQUEST,
+            RegistryAuthError::NotFound(_) => StatusCode::NOT_FOUND,
+            RegistryAuthError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+pub async fn set_registry_auth<P: Provider>(
+    provider: web::Data<P>,
+    namespace: web::Path<String>,
+    info: web::Json<RegistryAuth>,
+) -> Result<HttpResponse, RegistryAuthError> {
+    let namespace = namespace.into_inner();
+    (*provider)
+        .set_registry_auth(namespace.clone(), info.0)
+        .await
+        .map(|()| {
+            HttpResponse::Accepted().body(format!(
+                "registry auth of namespace {} was updated successfully",
+                namespace
+            ))
+        })
+}
+
+/// Only the registry hosts are returned, credentials never leave the provider
+pub async fn get_registry_auth<P: Provider>(
+    provider: web::Data<P>,
+    namespace: web::Path<String>,
+) -> Result<HttpResponse, RegistryAuthError> {
+    (*provider)
+        .get_registry_auth(namespace.into_inner())
+        .await
+        .map(|registries| HttpResponse::Ok().json(registries))
+}
+
+pub async fn delete_registry_auth<P: Provider>(
+    provider: web::Data<P>,
+    namespace: web::Path<String>,
+) -> Result<HttpResponse, RegistryAuthError> {
+    let namespace = namespace.into_inner();
+    (*provider)
+        .delete_registry_auth(namespace.clone())
+        .await
+        .map(|()| {
+            HttpResponse::Ok().body(format!(
+                "registry auth of namespace {} was deleted successfully",
+                namespace
+            ))
+        })
+}
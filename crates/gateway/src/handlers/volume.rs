@@ -0,0 +1,88 @@
+use crate::provider::Provider;
+use actix_http::StatusCode;
+use actix_web::{HttpResponse, ResponseError, web};
+use derive_more::Display;
+use serde::Deserialize;
+
+#[derive(Debug, Display)]
+pub enum VolumeError {
+    #[display("Invalid: {}", _0)]
+    Invalid(String),
+    #[display("AlreadyExists: {}", _0)]
+    AlreadyExists(String),
+    #[display("NotFound: {}", _0)]
+    NotFound(String),
+    /// The volume is still mounted by a function
+    #[display("InUse: {}", _0)]
+    InUse(String),
+    #[display("Internal: {}", _0)]
+    Internal(String),
+}
+
+impl ResponseError for VolumeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            VolumeError::Invalid(_) => StatusCode::BAD_REQUEST,
+            VolumeError::AlreadyExists(_) => StatusCode::CONFLICT,
+            VolumeError::NotFound(_) => StatusCode::NOT_FOUND,
+            VolumeError::InUse(_) => StatusCode::CONFLICT,
+            VolumeError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VolumeParam {
+    namespace: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateVolume {
+    name: String,
+    namespace: String,
+}
+
+pub async fn list<P: Provider>(
+    provider: web::Data<P>,
+    info: web::Query<VolumeParam>,
+) -> Result<HttpResponse, VolumeError> {
+    (*provider)
+        .list_volumes(info.into_inner().namespace)
+        .await
+        .map(|volumes| HttpResponse::Ok().json(volumes))
+}
+
+pub async fn create<P: Provider>(
+    provider: web::Data<P>,
+    info: web::Json<CreateVolume>,
+) -> Result<HttpResponse, VolumeError> {
+    let CreateVolume { name, namespace } = info.into_inner();
+    (*provider)
+        .create_volume(namespace.clone(), name.clone())
+        .await
+        .map(|()| {
+            HttpResponse::Created().body(format!(
+                "volume {} was created in namespace {} successfully",
+                name, namespace
+            ))
+        })
+}
+
+/// Fails with `409 Conflict` while a function still mounts the volume
+pub async fn delete<P: Provider>(
+    provider: web::Data<P>,
+    name: web::Path<String>,
+    info: web::Query<VolumeParam>,
+) -> Result<HttpResponse, VolumeError> {
+    let name = name.into_inner();
+    let namespace = info.into_inner().namespace;
+    (*provider)
+        .delete_volume(namespace.clone(), name.clone())
+        .await
+        .map(|()| {
+            HttpResponse::Ok().body(format!(
+                "volume {} was deleted from namespace {} successfully",
+                name, namespace
+            ))
+        })
+}
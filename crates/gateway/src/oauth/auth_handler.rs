@@ -1,16 +1,17 @@
 use crate::models::error::DbError;
 use crate::oauth::error::AuthError;
-use crate::oauth::jwt_utils::{generate_access_token, validate_access_token};
+use crate::oauth::jwt_utils::{AccessTokenClaims, generate_access_token, validate_access_token};
 use crate::oauth::services::{self, UserService};
 use crate::types::config::FaaSConfig; // 确保 JwtConfig 被正确导入
 use actix_web::HttpMessage;
 use actix_web::error::ErrorUnauthorized;
 use actix_web::{Error, dev::ServiceRequest};
-use actix_web::{HttpResponse, web};
+use actix_web::{HttpRequest, HttpResponse, web};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
 use diesel_async::AsyncPgConnection;
 use diesel_async::pooled_connection::bb8::Pool;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 #[derive(Deserialize, Serialize, Debug, Clone)]
 //注册结构体
 pub struct RegisterPayload {
@@ -85,7 +86,8 @@ pub async fn login_handler(
         Ok(user) => {
             if services::verify_password(&user.password_hash, &payload.password)? {
                 log::debug!("Password verified for user: {}", user.username);
-                let token = generate_access_token(user.uid, &config.jwt_config)?;
+                let admin = config.admin_users.contains(&user.username);
+                let token = generate_access_token(user.uid, admin, &config.jwt_config)?;
                 log::info!("Token generated for user: {}", user.username);
                 Ok(HttpResponse::Ok().json(LoginResponse {
                     token,
@@ -111,6 +113,20 @@ pub async fn login_handler(
     }
 }
 
+/// 请求是否来自管理员，令牌中的权限在签发时确定
+pub fn is_admin(req: &HttpRequest) -> bool {
+    req.extensions()
+        .get::<AccessTokenClaims>()
+        .is_some_and(|claims| claims.admin)
+}
+
+/// 令牌签发给的用户
+pub fn user_id(req: &HttpRequest) -> Option<Uuid> {
+    req.extensions()
+        .get::<AccessTokenClaims>()
+        .map(|claims| claims.sub)
+}
+
 pub async fn protected_endpoint(
     req: ServiceRequest,
     credentials: BearerAuth,
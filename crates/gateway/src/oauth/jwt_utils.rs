@@ -11,15 +11,23 @@ pub struct AccessTokenClaims {
     pub sub: Uuid,  // users.user_id,use uuid as user identifier
     pub exp: usize, // Expiration time (timestamp),jwtconfig.access_token_ttl_seconds
     pub iat: usize, // Issued at (timestamp）
+    /// 签发时用户是否在 ADMIN_USERS 中
+    #[serde(default)]
+    pub admin: bool,
 }
 
-pub fn generate_access_token(user_id: Uuid, jwt_config: &JwtConfig) -> Result<String, AuthError> {
+pub fn generate_access_token(
+    user_id: Uuid,
+    admin: bool,
+    jwt_config: &JwtConfig,
+) -> Result<String, AuthError> {
     let now = Utc::now();
     let expiration = now + Duration::seconds(jwt_config.access_token_ttl_seconds);
     let claims = AccessTokenClaims {
         sub: user_id,
         exp: expiration.timestamp() as usize,
         iat: now.timestamp() as usize,
+        admin,
     };
     //The default algorithm is HS256
     encode(
@@ -70,12 +78,14 @@ mod tests {
         };
 
         let user_id = Uuid::new_v4();
-        let token = generate_access_token(user_id, &jwt_config).expect("Failed to generate token");
+        let token =
+            generate_access_token(user_id, true, &jwt_config).expect("Failed to generate token");
 
         let claims = validate_access_token(&token, &jwt_config).expect("Failed to validate token");
         print!("\nGenerated Token: {}\n", token);
         print!("claims:{:?}", claims);
         assert_eq!(claims.sub, user_id);
+        assert!(claims.admin);
         assert!(claims.exp > claims.iat); // 过期时间应该大于签发时间
     }
 
@@ -88,7 +98,8 @@ mod tests {
         };
 
         let user_id = Uuid::new_v4();
-        let token = generate_access_token(user_id, &jwt_config).expect("Failed to generate token");
+        let token =
+            generate_access_token(user_id, false, &jwt_config).expect("Failed to generate token");
 
         let result = validate_access_token(&token, &jwt_config);
         assert!(matches!(result, Err(AuthError::TokenExpired)));
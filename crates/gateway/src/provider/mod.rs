@@ -1,13 +1,28 @@
 use std::collections::HashMap;
 
+use actix_web::web::Bytes;
+
 use crate::{
     handlers::{
-        function::{DeleteError, DeployError, ListError, ResolveError, UpdateError},
+        config_map::ConfigError,
+        exec::ExecError,
+        function::{
+            DeleteError, DeployError, LifecycleError, ListError, ResolveError, UpdateError,
+        },
+        image::ImageStoreError,
         namespace::NamespaceError,
+        registry::RegistryAuthError,
+        volume::VolumeError,
     },
     types::{
-        function::{Deployment, Query, Status},
+        config_map::ConfigMap,
+        exec::{ExecRequest, ExecSession},
+        function::{Deployment, Query, Status, UpdateStrategy},
+        image::ImageSummary,
         namespace::Namespace,
+        operation::OperationHandle,
+        registry::RegistryAuth,
+        volume::Volume,
     },
 };
 
@@ -26,17 +41,20 @@ pub trait Provider: Send + Sync + 'static {
         namespace: String,
     ) -> impl std::future::Future<Output = Result<Vec<Status>, ListError>> + Send;
 
-    /// Deploy a new function
+    /// Deploy a new function, reporting its phases to `operation`
     fn deploy(
         &self,
         param: Deployment,
+        operation: OperationHandle,
     ) -> impl std::future::Future<Output = Result<(), DeployError>> + Send;
 
-    /// Update a function spec
+    /// Update a function spec, reporting its phases to `operation`. Returns
+    /// whether the function was updated in place or recreated
     fn update(
         &self,
         param: Deployment,
-    ) -> impl std::future::Future<Output = Result<(), UpdateError>> + Send;
+        operation: OperationHandle,
+    ) -> impl std::future::Future<Output = Result<UpdateStrategy, UpdateError>> + Send;
 
     /// Delete a function
     fn delete(
@@ -51,6 +69,34 @@ pub trait Provider: Send + Sync + 'static {
         function: Query,
     ) -> impl std::future::Future<Output = Result<Status, ResolveError>> + Send;
 
+    /// Freeze the processes of a function without losing their state
+    fn pause(
+        &self,
+        function: Query,
+    ) -> impl std::future::Future<Output = Result<(), LifecycleError>> + Send;
+
+    /// Thaw a paused function
+    fn resume(
+        &self,
+        function: Query,
+    ) -> impl std::future::Future<Output = Result<(), LifecycleError>> + Send;
+
+    /// Stop the processes of a function and start them again in the same
+    /// container, returns once the function is ready
+    fn restart(
+        &self,
+        function: Query,
+    ) -> impl std::future::Future<Output = Result<(), LifecycleError>> + Send;
+
+    /// Run a process with a terminal in a function and relay the streams of
+    /// `session` until it exits, returns its exit code
+    fn exec(
+        &self,
+        function: Query,
+        request: ExecRequest,
+        session: ExecSession,
+    ) -> impl std::future::Future<Output = Result<u32, ExecError>> + Send;
+
     fn create_namespace(
         &self,
         namespace: String,
@@ -76,4 +122,110 @@ pub trait Provider: Send + Sync + 'static {
     fn namespace_list(
         &self,
     ) -> impl std::future::Future<Output = Result<Vec<Namespace>, NamespaceError>> + Send;
+
+    // `/system/registry-auth/{namespace}` endpoint
+
+    /// Store the registry credentials used when pulling images into a namespace
+    fn set_registry_auth(
+        &self,
+        namespace: String,
+        auth: RegistryAuth,
+    ) -> impl std::future::Future<Output = Result<(), RegistryAuthError>> + Send;
+
+    /// List the registries a namespace holds credentials for
+    fn get_registry_auth(
+        &self,
+        namespace: String,
+    ) -> impl std::future::Future<Output = Result<Vec<String>, RegistryAuthError>> + Send;
+
+    fn delete_registry_auth(
+        &self,
+        namespace: String,
+    ) -> impl std::future::Future<Output = Result<(), RegistryAuthError>> + Send;
+
+    // `/system/images` endpoint
+
+    /// Import an OCI layout or `docker save` archive, streamed in chunks.
+    /// Returns the names of the imported images
+    fn import_image(
+        &self,
+        namespace: String,
+        archive: tokio::sync::mpsc::Receiver<Bytes>,
+    ) -> impl std::future::Future<Output = Result<Vec<String>, ImageStoreError>> + Send;
+
+    /// List the images present in a namespace
+    fn list_images(
+        &self,
+        namespace: String,
+    ) -> impl std::future::Future<Output = Result<Vec<ImageSummary>, ImageStoreError>> + Send;
+
+    /// Remove images no function uses, in one namespace or in all of them,
+    /// returns the names of the removed images
+    fn prune_images(
+        &self,
+        namespace: Option<String>,
+    ) -> impl std::future::Future<Output = Result<Vec<String>, ImageStoreError>> + Send;
+
+    // `/system/configs` endpoint
+
+    /// Store a new config, a missing namespace means the default one
+    fn create_config(
+        &self,
+        config: ConfigMap,
+    ) -> impl std::future::Future<Output = Result<(), ConfigError>> + Send;
+
+    /// Replace the contents of a config and refresh the files of the functions
+    /// using it. Returns the functions using the config
+    fn update_config(
+        &self,
+        config: ConfigMap,
+    ) -> impl std::future::Future<Output = Result<Vec<String>, ConfigError>> + Send;
+
+    /// Restart the functions using a config one at a time, reporting to `operation`
+    fn restart_config_users(
+        &self,
+        namespace: Option<String>,
+        name: String,
+        operation: OperationHandle,
+    ) -> impl std::future::Future<Output = Result<(), ConfigError>> + Send;
+
+    fn get_config(
+        &self,
+        namespace: Option<String>,
+        name: String,
+    ) -> impl std::future::Future<Output = Result<ConfigMap, ConfigError>> + Send;
+
+    fn list_configs(
+        &self,
+        namespace: Option<String>,
+    ) -> impl std::future::Future<Output = Result<Vec<ConfigMap>, ConfigError>> + Send;
+
+    /// Delete a config, refused while a function references it
+    fn delete_config(
+        &self,
+        namespace: Option<String>,
+        name: String,
+    ) -> impl std::future::Future<Output = Result<(), ConfigError>> + Send;
+
+    // `/system/volumes` endpoint
+
+    /// Create an empty volume in a namespace
+    fn create_volume(
+        &self,
+        namespace: String,
+        name: String,
+    ) -> impl std::future::Future<Output = Result<(), VolumeError>> + Send;
+
+    /// List the volumes of a namespace and the functions mounting them
+    fn list_volumes(
+        &self,
+        namespace: String,
+    ) -> impl std::future::Future<Output = Result<Vec<Volume>, VolumeError>> + Send;
+
+    /// Delete a volume and its data, refused while a function mounts it
+    fn delete_volume(
+        &self,
+        namespace: String,
+        name: String,
+    ) -> impl std::future::Future<Output = Result<(), VolumeError>> + Send;
 }
@@ -0,0 +1,82 @@
+//! Annotation keys of a [`Deployment`](super::function::Deployment) understood by the provider
+
+/// Image pull policy of the function, see [`PullPolicy`](super::image::PullPolicy)
+pub const PULL_POLICY: &str = "faasrs.io/image-pull-policy";
+
+/// Port the function serves HTTP on, defaults to the single TCP port exposed by
+/// the image and then to 8080
+pub const PORT: &str = "faasrs.io/port";
+
+/// Readiness probe of the function: `http`, `tcp`, `exec` or `none`. Defaults to
+/// `http` when `faasrs.io/readiness-path` is set and to `tcp` otherwise,
+/// see [`ReadinessProbe`](super::probe::ReadinessProbe)
+pub const READINESS_PROBE: &str = "faasrs.io/readiness-probe";
+/// Path requested by an `http` probe, defaults to the watchdog's `/_/health`
+/// when the probe is `http` without a path
+pub const READINESS_PATH: &str = "faasrs.io/readiness-path";
+/// Port checked by an `http` or `tcp` probe, defaults to the upstream port
+pub const READINESS_PORT: &str = "faasrs.io/readiness-port";
+/// Whitespace separated command run by an `exec` probe
+pub const READINESS_COMMAND: &str = "faasrs.io/readiness-command";
+/// Seconds between two probes
+pub const READINESS_PERIOD: &str = "faasrs.io/readiness-period";
+/// Seconds before a probe is considered failed
+pub const READINESS_TIMEOUT: &str = "faasrs.io/readiness-timeout";
+/// Consecutive failures before a ready replica stops receiving traffic
+pub const READINESS_FAILURE_THRESHOLD: &str = "faasrs.io/readiness-failure-threshold";
+
+/// `true` drops all traffic of the function leaving the function network
+pub const EGRESS_DISABLED: &str = "faasrs.io/egress-disabled";
+/// Comma separated CIDRs the function may reach outside the function network,
+/// applied on top of the namespace's `faasrs.io/egress-allow` label
+pub const EGRESS_ALLOW: &str = "faasrs.io/egress-allow";
+/// Rate limit of the traffic received by the function in bit/s, `k`, `M` and `G`
+/// suffixes are accepted, requires the `bandwidth` CNI plugin
+pub const INGRESS_BANDWIDTH: &str = "faasrs.io/ingress-bandwidth";
+/// Rate limit of the traffic sent by the function, same format as `INGRESS_BANDWIDTH`
+pub const EGRESS_BANDWIDTH: &str = "faasrs.io/egress-bandwidth";
+
+/// Comma separated `<name>:<ip>` entries added to the function's `/etc/hosts`
+pub const EXTRA_HOSTS: &str = "faasrs.io/extra-hosts";
+
+/// User the function runs as, `user`, `uid`, `user:group` or `uid:gid`, overrides the image's `User`
+pub const USER: &str = "faasrs.io/user";
+/// `true` runs the function in a user namespace that maps root to an unprivileged host range
+pub const USER_NAMESPACE: &str = "faasrs.io/user-namespace";
+
+/// Comma separated capabilities added to the default set, e.g. `NET_ADMIN,SYS_PTRACE`
+pub const CAP_ADD: &str = "faasrs.io/cap-add";
+/// Comma separated capabilities removed from the default set, `ALL` removes every capability
+pub const CAP_DROP: &str = "faasrs.io/cap-drop";
+/// `default`, `unconfined` or the name of a seccomp profile installed on the host
+pub const SECCOMP_PROFILE: &str = "faasrs.io/seccomp-profile";
+
+/// Soft and hard open files limit of the function, at most the namespace maximum
+pub const NOFILE: &str = "faasrs.io/nofile";
+/// Maximum number of processes and threads in the function
+pub const PIDS_LIMIT: &str = "faasrs.io/pids-limit";
+/// Size of the function's `/dev/shm`, e.g. `256m` or `1Gi`
+pub const SHM_SIZE: &str = "faasrs.io/shm-size";
+
+/// Runtime class the function runs with, one registered by the administrator
+pub const RUNTIME_CLASS: &str = "faasrs.io/runtime-class";
+
+/// Annotations that only affect how the image is fetched, how the gateway routes
+/// to the function and how it is probed, changing them updates a function in place
+pub const IN_PLACE: &[&str] = &[
+    PULL_POLICY,
+    PORT,
+    READINESS_PROBE,
+    READINESS_PATH,
+    READINESS_PORT,
+    READINESS_COMMAND,
+    READINESS_PERIOD,
+    READINESS_TIMEOUT,
+    READINESS_FAILURE_THRESHOLD,
+];
+
+/// Whether a change of the annotation only takes effect in a new container. Other
+/// annotations than the provider's are metadata and never do
+pub fn requires_recreate(key: &str) -> bool {
+    key.starts_with("faasrs.io/") && !IN_PLACE.contains(&key)
+}
@@ -1,5 +1,12 @@
+use std::collections::HashSet;
 use std::time::Duration;
 
+use super::{
+    exec::{DEFAULT_EXEC_COMMAND, ExecAuditLog, parse_command},
+    image::PullPolicy,
+    security::SecurityPolicy,
+};
+
 const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(10);
 const DEFAULT_MAX_IDLE_CONNS: usize = 1024;
 
@@ -20,6 +27,14 @@ pub struct FaaSConfig {
     pub max_idle_conns: usize,
     pub max_idle_conns_per_host: usize,
     pub jwt_config: JwtConfig,
+    /// Users with admin rights, given by name in `ADMIN_USERS`
+    pub admin_users: HashSet<String>,
+    /// Limits on the security options of functions deployed by non-admin users
+    pub security_policy: SecurityPolicy,
+    /// Command of exec sessions that do not give one, `EXEC_COMMAND`
+    pub exec_command: Vec<String>,
+    /// Where exec sessions are recorded, `EXEC_AUDIT_LOG`
+    pub exec_audit: ExecAuditLog,
 }
 
 impl Default for FaaSConfig {
@@ -39,6 +54,16 @@ impl FaaSConfig {
             .unwrap_or_else(|_| "604800".to_string()) // 默认7天
             .parse::<i64>()
             .expect("REFRESH_TOKEN_TTL_SECONDS must be an integer");
+        // 只在启动时校验，默认的拉取策略由 provider 在拉取时读取
+        PullPolicy::from_env()
+            .expect("IMAGE_PULL_POLICY must be one of Always, IfNotPresent, Never");
+        let admin_users = std::env::var("ADMIN_USERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|user| !user.is_empty())
+            .map(str::to_string)
+            .collect();
         Self {
             tcp_port: None,
             read_timeout: Duration::from_secs(10),
@@ -53,6 +78,13 @@ impl FaaSConfig {
                 access_token_ttl_seconds,
                 refresh_token_ttl_seconds,
             },
+            admin_users,
+            security_policy: SecurityPolicy::from_env(),
+            exec_command: parse_command(
+                std::env::var("EXEC_COMMAND").ok().as_deref(),
+                &[DEFAULT_EXEC_COMMAND.to_string()],
+            ),
+            exec_audit: ExecAuditLog::from_env(),
         }
     }
     pub fn get_read_timeout(&self) -> Duration {
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::volume::valid_volume_name;
+
+/// Directory configs are projected under when a mount gives no path
+pub const DEFAULT_CONFIG_MOUNT_DIR: &str = "/var/faasrs/configs";
+
+/// Upper bound of the total size of a config's contents
+pub const MAX_CONFIG_SIZE: usize = 1024 * 1024;
+
+/// Non-sensitive configuration of a namespace, each key becomes a read-only
+/// file in the functions referencing it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigMap {
+    pub name: String,
+
+    pub namespace: Option<String>,
+
+    /// File name -> file content
+    #[serde(default)]
+    pub data: HashMap<String, String>,
+}
+
+/// Reference to a config from a function's `configs`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigMount {
+    /// Name of a config in the function's namespace
+    pub name: String,
+
+    /// Absolute directory the files appear in, defaults to
+    /// `/var/faasrs/configs/<name>`
+    pub mount_path: Option<String>,
+}
+
+impl ConfigMount {
+    pub fn mount_path(&self) -> String {
+        self.mount_path
+            .clone()
+            .unwrap_or_else(|| format!("{}/{}", DEFAULT_CONFIG_MOUNT_DIR, self.name))
+    }
+}
+
+/// Keys are plain file names: letters, digits, `-`, `_` and `.`, not starting
+/// with `..`, which is left to the provider's temporary files
+pub fn valid_config_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= 253
+        && key != "."
+        && !key.starts_with("..")
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+}
+
+impl ConfigMap {
+    /// Names follow the same rules as volume names
+    pub fn validate(&self) -> Result<(), String> {
+        if !valid_volume_name(&self.name) {
+            return Err(format!("invalid config name '{}'", self.name));
+        }
+        if let Some(key) = self.data.keys().find(|key| !valid_config_key(key)) {
+            return Err(format!("invalid config key '{}'", key));
+        }
+        let size: usize = self.data.iter().map(|(k, v)| k.len() + v.len()).sum();
+        if size > MAX_CONFIG_SIZE {
+            return Err(format!(
+                "config {} is {} bytes, at most {} bytes are allowed",
+                self.name, size, MAX_CONFIG_SIZE
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_map() {
+        let mut config: ConfigMap = serde_json::from_str(
+            r#"{"name": "flags", "data": {"flags.json": "{}", "template_v2.txt": "hi"}}"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        config.data.insert("../passwd".to_string(), String::new());
+        assert!(config.validate().is_err());
+        config.data.clear();
+        config
+            .data
+            .insert("big".to_string(), "x".repeat(MAX_CONFIG_SIZE));
+        assert!(config.validate().is_err());
+
+        let mount = ConfigMount {
+            name: "flags".to_string(),
+            mount_path: None,
+        };
+        assert_eq!(mount.mount_path(), "/var/faasrs/configs/flags");
+    }
+}
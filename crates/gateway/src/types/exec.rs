@@ -0,0 +1,203 @@
+use std::{
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use actix_web::web::Bytes;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Command of exec sessions that do not give one
+pub const DEFAULT_EXEC_COMMAND: &str = "/bin/sh";
+/// Where exec sessions are recorded when `EXEC_AUDIT_LOG` is not set
+pub const DEFAULT_EXEC_AUDIT_LOG: &str = "/var/log/faasrs/exec-audit.log";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalSize {
+    pub cols: u16,
+    pub rows: u16,
+}
+
+impl Default for TerminalSize {
+    fn default() -> Self {
+        TerminalSize { cols: 80, rows: 24 }
+    }
+}
+
+/// Control messages a client sends as text frames, binary frames are the terminal's input
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ExecControl {
+    Resize(TerminalSize),
+}
+
+/// Events sent to the client as text frames, binary frames are the terminal's output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ExecEvent {
+    /// The process exited, the connection is closed afterwards
+    Exit { code: u32 },
+    /// The session failed, the connection is closed afterwards
+    Error { message: String },
+}
+
+/// A process to run in a function with a terminal
+#[derive(Debug, Clone)]
+pub struct ExecRequest {
+    pub command: Vec<String>,
+    pub size: TerminalSize,
+}
+
+/// Streams of an exec session between the gateway and the provider. The provider
+/// kills the process once `stdin` is closed, i.e. the client went away
+#[derive(Debug)]
+pub struct ExecSession {
+    pub stdin: mpsc::Receiver<Bytes>,
+    pub resize: mpsc::Receiver<TerminalSize>,
+    pub output: mpsc::Sender<Bytes>,
+}
+
+/// Who ran what in which function and how it ended
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecAudit {
+    pub id: Uuid,
+    /// Id of the user the access token was issued to
+    pub user: Option<Uuid>,
+    pub function_name: String,
+    pub namespace: Option<String>,
+    pub command: Vec<String>,
+    /// Address the session was opened from
+    pub peer: Option<String>,
+    pub started_at: DateTime<Utc>,
+    /// Not set in the record written when the session starts
+    pub finished_at: Option<DateTime<Utc>>,
+    pub exit_code: Option<u32>,
+    pub error: Option<String>,
+}
+
+/// Append-only log of exec sessions, one JSON record per line. Every session is
+/// recorded when it starts and again when it ends
+#[derive(Debug, Clone)]
+pub struct ExecAuditLog {
+    path: PathBuf,
+    lock: Arc<Mutex<()>>,
+}
+
+impl ExecAuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        ExecAuditLog {
+            path: path.into(),
+            lock: Arc::default(),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::var("EXEC_AUDIT_LOG").unwrap_or_else(|_| DEFAULT_EXEC_AUDIT_LOG.to_string()),
+        )
+    }
+
+    pub fn record(&self, audit: &ExecAudit) -> std::io::Result<()> {
+        log::info!(
+            target: "audit",
+            "exec {} by {:?} in {}.{}: {:?}, exit {:?}",
+            audit.id,
+            audit.user,
+            audit.function_name,
+            audit.namespace.as_deref().unwrap_or_default(),
+            audit.command,
+            audit.exit_code
+        );
+        let mut line = serde_json::to_vec(audit)?;
+        line.push(b'\n');
+        let _guard = self.lock.lock().unwrap();
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)
+    }
+}
+
+/// Whitespace separated command, the default one when empty
+pub fn parse_command(command: Option<&str>, default: &[String]) -> Vec<String> {
+    let args: Vec<String> = command
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    if args.is_empty() {
+        default.to_vec()
+    } else {
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exec_messages() {
+        let control: ExecControl =
+            serde_json::from_str(r#"{"type":"resize","cols":120,"rows":40}"#).unwrap();
+        assert_eq!(
+            control,
+            ExecControl::Resize(TerminalSize {
+                cols: 120,
+                rows: 40
+            })
+        );
+        assert_eq!(
+            serde_json::to_string(&ExecEvent::Exit { code: 130 }).unwrap(),
+            r#"{"type":"exit","code":130}"#
+        );
+
+        let default = vec![DEFAULT_EXEC_COMMAND.to_string()];
+        assert_eq!(parse_command(None, &default), default);
+        assert_eq!(parse_command(Some("  "), &default), default);
+        assert_eq!(
+            parse_command(Some("ps aux"), &default),
+            vec!["ps".to_string(), "aux".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_exec_audit_log() {
+        let dir = std::env::temp_dir().join(format!("faasrs-exec-audit-{}", Uuid::new_v4()));
+        let log = ExecAuditLog::new(dir.join("audit.log"));
+        let mut audit = ExecAudit {
+            id: Uuid::new_v4(),
+            user: Some(Uuid::new_v4()),
+            function_name: "echo".to_string(),
+            namespace: None,
+            command: vec!["/bin/sh".to_string()],
+            peer: None,
+            started_at: Utc::now(),
+            finished_at: None,
+            exit_code: None,
+            error: None,
+        };
+        log.record(&audit).unwrap();
+        audit.finished_at = Some(Utc::now());
+        audit.exit_code = Some(0);
+        log.record(&audit).unwrap();
+
+        let content = std::fs::read_to_string(dir.join("audit.log")).unwrap();
+        let records: Vec<ExecAudit> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].exit_code, Some(0));
+        assert!(records[0].finished_at.is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,9 +1,15 @@
 // https://github.com/openfaas/faas/blob/7803ea1861f2a22adcbcfa8c79ed539bc6506d5b/api-docs/spec.openapi.yml
 
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    str::FromStr,
+};
 
 use serde::{Deserialize, Serialize};
 
+use super::{annotation, config_map::ConfigMount, limits::ProcessLimits, volume::VolumeMount};
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Deployment {
@@ -47,6 +53,65 @@ pub struct Deployment {
     /// mount-point.
     #[serde(default = "default_read_only_root_filesystem")]
     pub read_only_root_filesystem: bool,
+
+    /// Volumes of the function's namespace mounted into the function
+    pub volumes: Option<Vec<VolumeMount>>,
+
+    /// Configs of the function's namespace projected into the function as files
+    pub configs: Option<Vec<ConfigMount>>,
+}
+
+impl Deployment {
+    /// Fields and annotations changed since `previous` that only take effect in a
+    /// new container. Labels and the other annotations are metadata, an update
+    /// touching nothing else is applied in place
+    pub fn runtime_changes(&self, previous: &Deployment) -> Vec<String> {
+        let fields = |deployment: &Deployment| match serde_json::to_value(deployment) {
+            Ok(serde_json::Value::Object(mut fields)) => {
+                fields.remove("labels");
+                fields.remove("annotations");
+                fields
+            }
+            _ => serde_json::Map::new(),
+        };
+        let (new, old) = (fields(self), fields(previous));
+        let mut changes: BTreeSet<String> = new
+            .keys()
+            .chain(old.keys())
+            .filter(|key| new.get(*key) != old.get(*key))
+            .cloned()
+            .collect();
+
+        let empty = HashMap::new();
+        let new = self.annotations.as_ref().unwrap_or(&empty);
+        let old = previous.annotations.as_ref().unwrap_or(&empty);
+        changes.extend(
+            new.keys()
+                .chain(old.keys())
+                .filter(|key| annotation::requires_recreate(key) && new.get(*key) != old.get(*key))
+                .cloned(),
+        );
+        changes.into_iter().collect()
+    }
+}
+
+/// How an update was applied to a function
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateStrategy {
+    /// Only metadata or routing settings changed, the running container was kept
+    InPlace,
+    /// Runtime settings changed, the function was recreated
+    Recreate,
+}
+
+impl fmt::Display for UpdateStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateStrategy::InPlace => write!(f, "updated in place"),
+            UpdateStrategy::Recreate => write!(f, "recreated"),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -123,6 +188,87 @@ pub struct Status {
 
     /// Usage statistics for the function
     pub usage: Option<Usage>,
+
+    /// Network controls in effect for the function
+    pub network: Option<NetworkStatus>,
+
+    /// Volumes mounted into the function
+    pub volumes: Option<Vec<VolumeMount>>,
+
+    /// Configs projected into the function
+    pub configs: Option<Vec<ConfigMount>>,
+
+    /// Open files, pids and `/dev/shm` limits the function runs with
+    pub process_limits: Option<ProcessLimits>,
+
+    /// Container runtime the function runs with
+    pub runtime: Option<FunctionRuntime>,
+
+    /// Whether the function's processes are frozen by a pause
+    #[serde(default)]
+    pub paused: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionRuntime {
+    /// Runtime class chosen at deployment, not set for functions deployed before runtime classes
+    pub class: Option<String>,
+
+    /// containerd runtime type, e.g. `io.containerd.runc.v2`
+    pub runtime_type: String,
+
+    /// Snapshotter holding the function's root filesystem, e.g. `overlayfs`
+    pub snapshotter: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkStatus {
+    /// Traffic leaving the function network is dropped
+    pub egress_disabled: bool,
+
+    /// CIDRs allowed by the function's annotation, the allow list of its namespace applies as well
+    pub egress_allow: Vec<String>,
+
+    /// Rate limit of the traffic received by the function in bit/s
+    pub ingress_bandwidth: Option<u64>,
+
+    /// Rate limit of the traffic sent by the function in bit/s
+    pub egress_bandwidth: Option<u64>,
+}
+
+/// Longest DNS label
+const MAX_NAME_LEN: usize = 63;
+
+/// Function and namespace names must be DNS labels, they end up in hostnames,
+/// DNS names and paths on the host
+pub fn validate_name(kind: &str, name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        && !name.starts_with('-')
+        && !name.ends_with('-');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid {} name '{}', expected at most {} lowercase letters, digits or '-' \
+             not starting or ending with '-'",
+            kind, name, MAX_NAME_LEN
+        ))
+    }
+}
+
+impl Deployment {
+    pub fn validate_names(&self) -> Result<(), String> {
+        validate_name("function", &self.function_name)?;
+        self.namespace
+            .as_deref()
+            .map_or(Ok(()), |namespace| validate_name("namespace", namespace))
+    }
 }
 
 #[derive(Eq, Hash, PartialEq, Clone, Debug)]
@@ -164,3 +310,62 @@ pub struct Delete {
 const fn default_read_only_root_filesystem() -> bool {
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deployment(annotations: &[(&str, &str)]) -> Deployment {
+        serde_json::from_value(serde_json::json!({
+            "functionName": "echo",
+            "image": "ghcr.io/openfaas/alpine:latest",
+            "envVars": {"fprocess": "cat"},
+            "labels": {"team": "a"},
+            "annotations": annotations.iter().cloned().collect::<HashMap<_, _>>(),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("function", "echo-2").is_ok());
+        assert!(validate_name("function", &"a".repeat(MAX_NAME_LEN)).is_ok());
+        for name in [
+            "",
+            "../../../../etc",
+            "a/b",
+            "a.b",
+            "Echo",
+            "-echo",
+            "echo-",
+            "echo_2",
+        ] {
+            assert!(validate_name("function", name).is_err(), "{}", name);
+        }
+        assert!(validate_name("function", &"a".repeat(MAX_NAME_LEN + 1)).is_err());
+
+        let mut escaping = deployment(&[]);
+        escaping.namespace = Some("..".to_string());
+        assert!(escaping.validate_names().is_err());
+    }
+
+    #[test]
+    fn test_runtime_changes() {
+        let previous = deployment(&[(annotation::CAP_ADD, "NET_RAW")]);
+
+        let mut metadata = deployment(&[
+            (annotation::CAP_ADD, "NET_RAW"),
+            (annotation::READINESS_PERIOD, "5"),
+            ("com.example/owner", "b"),
+        ]);
+        metadata.labels = Some(HashMap::from([("team".to_string(), "b".to_string())]));
+        assert!(metadata.runtime_changes(&previous).is_empty());
+
+        let mut runtime = deployment(&[]);
+        runtime.image = "ghcr.io/openfaas/alpine:3.20".to_string();
+        assert_eq!(
+            runtime.runtime_changes(&previous),
+            vec![annotation::CAP_ADD, "image"]
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use std::str::FromStr;
+
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+
+/// When the provider should fetch the image of a function from its registry
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Display)]
+pub enum PullPolicy {
+    /// Always resolve the reference against the registry
+    #[default]
+    Always,
+    /// Only pull when the image is missing from the local store
+    IfNotPresent,
+    /// Never contact the registry, the image must already be present
+    Never,
+}
+
+impl FromStr for PullPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Always" => Ok(PullPolicy::Always),
+            "IfNotPresent" => Ok(PullPolicy::IfNotPresent),
+            "Never" => Ok(PullPolicy::Never),
+            _ => Err(format!(
+                "invalid pull policy '{}', expected one of Always, IfNotPresent, Never",
+                s
+            )),
+        }
+    }
+}
+
+impl PullPolicy {
+    /// Pull policy of functions which do not specify one by annotation,
+    /// `IMAGE_PULL_POLICY`, `Always` when unset
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("IMAGE_PULL_POLICY") {
+            Ok(policy) => policy.parse(),
+            Err(_) => Ok(PullPolicy::default()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageSummary {
+    /// Image reference, e.g. `docker.io/library/alpine:3.20`
+    pub name: String,
+
+    /// Digest of the manifest or index the reference points to
+    pub digest: String,
+
+    /// The time the image was created in the provider's image store
+    pub created_at: Option<String>,
+
+    /// Bytes of the manifest, config and layers for the current platform
+    pub size: Option<i64>,
+
+    /// Functions deployed from this image, including retained rollback revisions
+    pub functions: Vec<String>,
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{annotation, namespace};
+
+/// Open files limit of functions that neither set one nor have a namespace default
+pub const DEFAULT_NOFILE: u64 = 1024;
+/// Upper bound of the open files limit, the kernel's default `fs.nr_open`
+pub const MAX_NOFILE: u64 = 1 << 20;
+/// Size of `/dev/shm` of functions that neither set one nor have a namespace default
+pub const DEFAULT_SHM_SIZE: u64 = 64 << 20;
+
+/// Process limits a function runs with, reported in its status
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessLimits {
+    /// Soft and hard `RLIMIT_NOFILE`
+    pub nofile: u64,
+
+    /// Maximum number of processes and threads, unlimited when not set
+    pub pids_limit: Option<u64>,
+
+    /// Size of `/dev/shm` in bytes
+    pub shm_size: u64,
+}
+
+impl Default for ProcessLimits {
+    fn default() -> Self {
+        ProcessLimits {
+            nofile: DEFAULT_NOFILE,
+            pids_limit: None,
+            shm_size: DEFAULT_SHM_SIZE,
+        }
+    }
+}
+
+/// Size in bytes with an optional binary suffix, `k`, `m` and `g` as in
+/// `docker run --shm-size` or `Ki`, `Mi` and `Gi`
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let trimmed = value.trim();
+    let lower = trimmed.to_ascii_lowercase();
+    let number = lower.strip_suffix('i').unwrap_or(&lower);
+    let (number, unit) = match number.char_indices().last() {
+        Some((i, 'k')) => (&number[..i], 1u64 << 10),
+        Some((i, 'm')) => (&number[..i], 1 << 20),
+        Some((i, 'g')) => (&number[..i], 1 << 30),
+        _ if number.len() == lower.len() => (number, 1),
+        _ => return Err(format!("invalid size '{}'", trimmed)),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .filter(|size| *size > 0)
+        .ok_or_else(|| format!("invalid size '{}'", trimmed))
+}
+
+fn parse_count(value: &str, max: u64) -> Result<u64, String> {
+    value
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|n| (1..=max).contains(n))
+        .ok_or_else(|| format!("invalid value '{}', expected 1 to {}", value.trim(), max))
+}
+
+/// Defaults and maxima of the process limits of a namespace, given by its labels
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NamespaceLimits {
+    pub default_nofile: Option<u64>,
+    pub max_nofile: Option<u64>,
+    pub default_pids_limit: Option<u64>,
+    pub max_pids_limit: Option<u64>,
+    pub default_shm_size: Option<u64>,
+    pub max_shm_size: Option<u64>,
+}
+
+impl NamespaceLimits {
+    pub fn from_labels(labels: &HashMap<String, String>) -> Result<Self, String> {
+        let get = |key: &str, parse: fn(&str) -> Result<u64, String>| {
+            labels
+                .get(key)
+                .map(|v| parse(v).map_err(|e| format!("{}: {}", key, e)))
+                .transpose()
+        };
+        let nofile = |v: &str| parse_count(v, MAX_NOFILE);
+        let pids = |v: &str| parse_count(v, i64::MAX as u64);
+        Ok(NamespaceLimits {
+            default_nofile: get(namespace::LABEL_DEFAULT_NOFILE, nofile)?,
+            max_nofile: get(namespace::LABEL_MAX_NOFILE, nofile)?,
+            default_pids_limit: get(namespace::LABEL_DEFAULT_PIDS_LIMIT, pids)?,
+            max_pids_limit: get(namespace::LABEL_MAX_PIDS_LIMIT, pids)?,
+            default_shm_size: get(namespace::LABEL_DEFAULT_SHM_SIZE, parse_size)?,
+            max_shm_size: get(namespace::LABEL_MAX_SHM_SIZE, parse_size)?,
+        })
+    }
+}
+
+/// A value set on the function must not exceed the namespace maximum, defaults
+/// are lowered to it
+fn resolve(
+    name: &str,
+    explicit: Option<u64>,
+    default: Option<u64>,
+    max: Option<u64>,
+) -> Result<Option<u64>, String> {
+    if let (Some(value), Some(max)) = (explicit, max)
+        && value > max
+    {
+        return Err(format!(
+            "{} {} exceeds the namespace maximum {}",
+            name, value, max
+        ));
+    }
+    Ok(match (explicit.or(default), max) {
+        (Some(value), Some(max)) => Some(value.min(max)),
+        (value, max) => value.or(max),
+    })
+}
+
+impl ProcessLimits {
+    /// Limits of a function from its annotations and the limits of its namespace
+    pub fn resolve(
+        annotations: Option<&HashMap<String, String>>,
+        namespace: &NamespaceLimits,
+    ) -> Result<Self, String> {
+        let get = |key: &str, parse: &dyn Fn(&str) -> Result<u64, String>| {
+            annotations
+                .and_then(|a| a.get(key))
+                .map(|v| parse(v).map_err(|e| format!("{}: {}", key, e)))
+                .transpose()
+        };
+        let nofile = get(annotation::NOFILE, &|v| parse_count(v, MAX_NOFILE))?;
+        let pids_limit = get(annotation::PIDS_LIMIT, &|v| parse_count(v, i64::MAX as u64))?;
+        let shm_size = get(annotation::SHM_SIZE, &parse_size)?;
+        Ok(ProcessLimits {
+            nofile: resolve(
+                "nofile",
+                nofile,
+                namespace.default_nofile.or(Some(DEFAULT_NOFILE)),
+                namespace.max_nofile,
+            )?
+            .unwrap_or(DEFAULT_NOFILE),
+            pids_limit: resolve(
+                "pids limit",
+                pids_limit,
+                namespace.default_pids_limit,
+                namespace.max_pids_limit,
+            )?,
+            shm_size: resolve(
+                "shm size",
+                shm_size,
+                namespace.default_shm_size.or(Some(DEFAULT_SHM_SIZE)),
+                namespace.max_shm_size,
+            )?
+            .unwrap_or(DEFAULT_SHM_SIZE),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("64m").unwrap(), 64 << 20);
+        assert_eq!(parse_size("2Gi").unwrap(), 2 << 30);
+        assert_eq!(parse_size("512K").unwrap(), 512 << 10);
+        for invalid in ["", "0", "1.5g", "64mb", "-1", "i"] {
+            assert!(parse_size(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_resolve_limits() {
+        assert_eq!(
+            ProcessLimits::resolve(None, &NamespaceLimits::default()).unwrap(),
+            ProcessLimits::default()
+        );
+
+        let labels = HashMap::from([
+            (
+                namespace::LABEL_DEFAULT_NOFILE.to_string(),
+                "4096".to_string(),
+            ),
+            (namespace::LABEL_MAX_NOFILE.to_string(), "65536".to_string()),
+            (
+                namespace::LABEL_MAX_PIDS_LIMIT.to_string(),
+                "512".to_string(),
+            ),
+            (namespace::LABEL_MAX_SHM_SIZE.to_string(), "32m".to_string()),
+        ]);
+        let ns = NamespaceLimits::from_labels(&labels).unwrap();
+        let limits = ProcessLimits::resolve(None, &ns).unwrap();
+        assert_eq!(limits.nofile, 4096);
+        assert_eq!(limits.pids_limit, Some(512));
+        assert_eq!(limits.shm_size, 32 << 20);
+
+        let annotations = HashMap::from([
+            (annotation::NOFILE.to_string(), "65536".to_string()),
+            (annotation::PIDS_LIMIT.to_string(), "100".to_string()),
+        ]);
+        let limits = ProcessLimits::resolve(Some(&annotations), &ns).unwrap();
+        assert_eq!(limits.nofile, 65536);
+        assert_eq!(limits.pids_limit, Some(100));
+
+        let annotations = HashMap::from([(annotation::SHM_SIZE.to_string(), "1g".to_string())]);
+        assert!(ProcessLimits::resolve(Some(&annotations), &ns).is_err());
+        let annotations = HashMap::from([(annotation::NOFILE.to_string(), "0".to_string())]);
+        assert!(ProcessLimits::resolve(Some(&annotations), &NamespaceLimits::default()).is_err());
+
+        let labels = HashMap::from([(
+            namespace::LABEL_MAX_NOFILE.to_string(),
+            "99999999".to_string(),
+        )]);
+        assert!(NamespaceLimits::from_labels(&labels).is_err());
+    }
+}
@@ -1,3 +1,13 @@
+pub mod annotation;
 pub mod config;
+pub mod config_map;
+pub mod exec;
 pub mod function;
+pub mod image;
+pub mod limits;
 pub mod namespace;
+pub mod operation;
+pub mod probe;
+pub mod registry;
+pub mod security;
+pub mod volume;
@@ -6,3 +6,93 @@ pub struct Namespace {
     pub name: Option<String>,
     pub labels: HashMap<String, String>,
 }
+
+/// Prefix of the namespace labels that constrain its functions: isolation, limits
+/// and runtime classes. Only admins may set or change them, the tenants of the
+/// namespace are the ones they restrict
+pub const ADMIN_LABEL_PREFIX: &str = "faasrs.io/";
+
+pub fn is_admin_label(key: &str) -> bool {
+    key.starts_with(ADMIN_LABEL_PREFIX)
+}
+
+/// Labels a non-admin write results in: the admin labels of `current` are kept
+/// as they are, an error names the first admin label `requested` sets or changes
+pub fn keep_admin_labels(
+    current: &HashMap<String, String>,
+    mut requested: HashMap<String, String>,
+) -> Result<HashMap<String, String>, String> {
+    if let Some(key) = requested
+        .iter()
+        .find(|(key, value)| is_admin_label(key) && current.get(*key) != Some(*value))
+        .map(|(key, _)| key)
+    {
+        return Err(format!("only admins may set namespace label {}", key));
+    }
+    for (key, value) in current {
+        if is_admin_label(key) {
+            requested.insert(key.clone(), value.clone());
+        }
+    }
+    Ok(requested)
+}
+
+/// Label of a namespace choosing which peers may reach its functions directly:
+/// `namespace` (default) allows functions of the same namespace, `gateway` allows
+/// only the gateway and `open` disables isolation. The internal DNS resolves names
+/// across namespaces only when both are `open`
+pub const LABEL_NETWORK_INGRESS: &str = "faasrs.io/network-ingress";
+/// Comma separated CIDRs the functions of a namespace may reach outside the
+/// function network, everything else is dropped when set
+pub const LABEL_EGRESS_ALLOW: &str = "faasrs.io/egress-allow";
+/// Comma separated CIDRs the functions of a namespace must not reach
+pub const LABEL_EGRESS_DENY: &str = "faasrs.io/egress-deny";
+
+/// Open files limit of functions in the namespace that do not set `faasrs.io/nofile`
+pub const LABEL_DEFAULT_NOFILE: &str = "faasrs.io/default-nofile";
+/// Largest open files limit functions in the namespace may use
+pub const LABEL_MAX_NOFILE: &str = "faasrs.io/max-nofile";
+/// Pids limit of functions in the namespace that do not set `faasrs.io/pids-limit`
+pub const LABEL_DEFAULT_PIDS_LIMIT: &str = "faasrs.io/default-pids-limit";
+/// Largest pids limit functions in the namespace may use, also applied to
+/// functions without a limit
+pub const LABEL_MAX_PIDS_LIMIT: &str = "faasrs.io/max-pids-limit";
+/// `/dev/shm` size of functions in the namespace that do not set `faasrs.io/shm-size`
+pub const LABEL_DEFAULT_SHM_SIZE: &str = "faasrs.io/default-shm-size";
+/// Largest `/dev/shm` size functions in the namespace may use
+pub const LABEL_MAX_SHM_SIZE: &str = "faasrs.io/max-shm-size";
+
+/// Comma separated runtime classes functions in the namespace may use, functions
+/// without a `faasrs.io/runtime-class` annotation get the first one unless the
+/// default class is listed
+pub const LABEL_RUNTIME_CLASSES: &str = "faasrs.io/runtime-classes";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keep_admin_labels() {
+        let current = HashMap::from([
+            (LABEL_RUNTIME_CLASSES.to_string(), "gvisor".to_string()),
+            ("team".to_string(), "a".to_string()),
+        ]);
+
+        // 省略的管理员标签保持不变，普通标签照常替换
+        let labels = keep_admin_labels(
+            &current,
+            HashMap::from([("team".to_string(), "b".to_string())]),
+        )
+        .unwrap();
+        assert_eq!(labels.get(LABEL_RUNTIME_CLASSES).unwrap(), "gvisor");
+        assert_eq!(labels.get("team").unwrap(), "b");
+
+        // 原样回传不算修改
+        assert!(keep_admin_labels(&current, current.clone()).is_ok());
+
+        let relabel = HashMap::from([(LABEL_RUNTIME_CLASSES.to_string(), "runc".to_string())]);
+        assert!(keep_admin_labels(&current, relabel).is_err());
+        let raise = HashMap::from([(LABEL_MAX_PIDS_LIMIT.to_string(), "100000".to_string())]);
+        assert!(keep_admin_labels(&HashMap::new(), raise).is_err());
+    }
+}
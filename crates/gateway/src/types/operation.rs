@@ -0,0 +1,187 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use derive_more::Display;
+use serde::Serialize;
+
+use super::function::UpdateStrategy;
+
+/// Finished operations are kept this long for clients to poll their result
+const FINISHED_OPERATION_TTL: Duration = Duration::hours(1);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Display)]
+#[serde(rename_all = "camelCase")]
+pub enum OperationKind {
+    Deploy,
+    Update,
+    /// Rolling restart of the functions using a config, named after the config
+    Restart,
+}
+
+/// Phases of a deploy or update, in the order they are entered
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Display)]
+#[serde(rename_all = "camelCase")]
+pub enum Phase {
+    Pending,
+    Pulling,
+    Creating,
+    Networking,
+    Starting,
+    Ready,
+    Failed,
+}
+
+impl Phase {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Phase::Ready | Phase::Failed)
+    }
+}
+
+/// Bytes fetched so far out of the bytes known to be needed,
+/// `total` grows while manifests are resolved
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PullProgress {
+    pub bytes: i64,
+    pub total: i64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Operation {
+    pub id: String,
+    pub kind: OperationKind,
+    pub function_name: String,
+    pub namespace: Option<String>,
+    pub phase: Phase,
+    /// Image download progress, present once pulling has started
+    pub progress: Option<PullProgress>,
+    /// Why the operation failed, only set in the `failed` phase
+    pub error: Option<String>,
+    /// How an update was applied, set once it succeeded
+    pub strategy: Option<UpdateStrategy>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Handed to the provider to report the progress of an operation.
+/// A detached handle is used by synchronous requests and records nothing.
+#[derive(Debug, Clone, Default)]
+pub struct OperationHandle(Option<Arc<RwLock<Operation>>>);
+
+impl OperationHandle {
+    pub fn detached() -> Self {
+        Self(None)
+    }
+
+    pub fn set_phase(&self, phase: Phase) {
+        self.update(|op| op.phase = phase);
+    }
+
+    pub fn set_progress(&self, progress: PullProgress) {
+        self.update(|op| op.progress = Some(progress));
+    }
+
+    pub fn set_strategy(&self, strategy: UpdateStrategy) {
+        self.update(|op| op.strategy = Some(strategy));
+    }
+
+    pub fn finish(&self, result: Result<(), String>) {
+        self.update(|op| match result {
+            Ok(()) => op.phase = Phase::Ready,
+            Err(e) => {
+                op.phase = Phase::Failed;
+                op.error = Some(e);
+            }
+        });
+    }
+
+    /// Snapshot of the operation, `None` for a detached handle
+    pub fn get(&self) -> Option<Operation> {
+        self.0.as_ref().map(|op| op.read().unwrap().clone())
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Operation)) {
+        if let Some(op) = &self.0 {
+            let mut op = op.write().unwrap();
+            f(&mut op);
+            op.updated_at = Utc::now();
+        }
+    }
+}
+
+/// In-memory registry of the asynchronous operations of this gateway
+#[derive(Debug, Default)]
+pub struct OperationStore {
+    operations: RwLock<HashMap<String, Arc<RwLock<Operation>>>>,
+}
+
+impl OperationStore {
+    pub fn start(
+        &self,
+        kind: OperationKind,
+        function_name: String,
+        namespace: Option<String>,
+    ) -> OperationHandle {
+        let now = Utc::now();
+        let id = uuid::Uuid::new_v4().to_string();
+        let op = Arc::new(RwLock::new(Operation {
+            id: id.clone(),
+            kind,
+            function_name,
+            namespace,
+            phase: Phase::Pending,
+            progress: None,
+            error: None,
+            strategy: None,
+            created_at: now,
+            updated_at: now,
+        }));
+
+        let mut operations = self.operations.write().unwrap();
+        operations.retain(|_, op| {
+            let op = op.read().unwrap();
+            !op.phase.is_finished() || now - op.updated_at < FINISHED_OPERATION_TTL
+        });
+        operations.insert(id, op.clone());
+        OperationHandle(Some(op))
+    }
+
+    pub fn get(&self, id: &str) -> Option<Operation> {
+        self.operations
+            .read()
+            .unwrap()
+            .get(id)
+            .map(|op| op.read().unwrap().clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operation_lifecycle() {
+        let store = OperationStore::default();
+        let handle = store.start(OperationKind::Deploy, "echo".to_string(), None);
+        let id = handle.get().unwrap().id;
+        assert_eq!(store.get(&id).unwrap().phase, Phase::Pending);
+
+        handle.set_phase(Phase::Pulling);
+        handle.set_progress(PullProgress {
+            bytes: 10,
+            total: 100,
+        });
+        handle.finish(Err("pull failed".to_string()));
+        let op = store.get(&id).unwrap();
+        assert_eq!(op.phase, Phase::Failed);
+        assert_eq!(op.progress.unwrap().bytes, 10);
+        assert_eq!(op.error.as_deref(), Some("pull failed"));
+
+        let detached = OperationHandle::detached();
+        detached.set_phase(Phase::Ready);
+        assert!(detached.get().is_none());
+    }
+}
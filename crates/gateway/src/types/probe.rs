@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::annotation;
+
+/// Health endpoint served by the OpenFaaS watchdogs
+pub const DEFAULT_PROBE_PATH: &str = "/_/health";
+const DEFAULT_PERIOD_SECONDS: u64 = 2;
+const DEFAULT_TIMEOUT_SECONDS: u64 = 1;
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+/// How a replica is checked
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ProbeHandler {
+    /// A `GET` on `path` answered with a 2xx or 3xx status
+    Http { path: String, port: u16 },
+    /// A TCP connection to `port` is accepted
+    Tcp { port: u16 },
+    /// `command` exits with 0 inside the container
+    Exec { command: Vec<String> },
+}
+
+/// Readiness probe of a function, a replica receives traffic only after passing it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadinessProbe {
+    pub handler: ProbeHandler,
+    pub period_seconds: u64,
+    pub timeout_seconds: u64,
+    /// Consecutive failures before a ready replica is taken out of routing
+    pub failure_threshold: u32,
+}
+
+impl ReadinessProbe {
+    /// Build the probe from the `faasrs.io/readiness-*` annotations,
+    /// returns `None` when probing is disabled with `none`. Without annotations
+    /// a TCP connect is used, only watchdog images are known to serve `/_/health`.
+    /// `default_port` is the port the function serves on
+    pub fn from_annotations(
+        annotations: Option<&HashMap<String, String>>,
+        default_port: u16,
+    ) -> Result<Option<Self>, String> {
+        let get = |key: &str| annotations.and_then(|a| a.get(key)).map(String::as_str);
+        fn parse<T: std::str::FromStr>(
+            key: &str,
+            value: Option<&str>,
+            default: T,
+        ) -> Result<T, String> {
+            value.map_or(Ok(default), |v| {
+                v.parse()
+                    .map_err(|_| format!("invalid value '{}' of annotation {}", v, key))
+            })
+        }
+        let port = parse(
+            annotation::READINESS_PORT,
+            get(annotation::READINESS_PORT),
+            default_port,
+        )?;
+
+        let default = if get(annotation::READINESS_PATH).is_some() {
+            "http"
+        } else {
+            "tcp"
+        };
+        let handler = match get(annotation::READINESS_PROBE).unwrap_or(default) {
+            "none" => return Ok(None),
+            "http" => ProbeHandler::Http {
+                path: get(annotation::READINESS_PATH)
+                    .unwrap_or(DEFAULT_PROBE_PATH)
+                    .to_string(),
+                port,
+            },
+            "tcp" => ProbeHandler::Tcp { port },
+            "exec" => {
+                let command: Vec<String> = get(annotation::READINESS_COMMAND)
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect();
+                if command.is_empty() {
+                    return Err(format!(
+                        "annotation {} is required by an exec probe",
+                        annotation::READINESS_COMMAND
+                    ));
+                }
+                ProbeHandler::Exec { command }
+            }
+            other => {
+                return Err(format!(
+                    "invalid readiness probe '{}', expected one of http, tcp, exec, none",
+                    other
+                ));
+            }
+        };
+
+        let probe = ReadinessProbe {
+            handler,
+            period_seconds: parse(
+                annotation::READINESS_PERIOD,
+                get(annotation::READINESS_PERIOD),
+                DEFAULT_PERIOD_SECONDS,
+            )?,
+            timeout_seconds: parse(
+                annotation::READINESS_TIMEOUT,
+                get(annotation::READINESS_TIMEOUT),
+                DEFAULT_TIMEOUT_SECONDS,
+            )?,
+            failure_threshold: parse(
+                annotation::READINESS_FAILURE_THRESHOLD,
+                get(annotation::READINESS_FAILURE_THRESHOLD),
+                DEFAULT_FAILURE_THRESHOLD,
+            )?,
+        };
+        if probe.period_seconds == 0 || probe.timeout_seconds == 0 {
+            return Err("readiness probe period and timeout must be positive".to_string());
+        }
+        Ok(Some(probe))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_from_annotations() {
+        let probe = ReadinessProbe::from_annotations(None, 3000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(probe.handler, ProbeHandler::Tcp { port: 3000 });
+
+        let http = HashMap::from([(annotation::READINESS_PATH.to_string(), "/ready".to_string())]);
+        let probe = ReadinessProbe::from_annotations(Some(&http), 3000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            probe.handler,
+            ProbeHandler::Http {
+                path: "/ready".to_string(),
+                port: 3000
+            }
+        );
+        let watchdog =
+            HashMap::from([(annotation::READINESS_PROBE.to_string(), "http".to_string())]);
+        let probe = ReadinessProbe::from_annotations(Some(&watchdog), 8080)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            probe.handler,
+            ProbeHandler::Http {
+                path: DEFAULT_PROBE_PATH.to_string(),
+                port: 8080
+            }
+        );
+
+        let annotations = HashMap::from([
+            (annotation::READINESS_PROBE.to_string(), "exec".to_string()),
+            (
+                annotation::READINESS_COMMAND.to_string(),
+                "cat /tmp/.lock".to_string(),
+            ),
+            (annotation::READINESS_PERIOD.to_string(), "5".to_string()),
+        ]);
+        let probe = ReadinessProbe::from_annotations(Some(&annotations), 8080)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            probe.handler,
+            ProbeHandler::Exec {
+                command: vec!["cat".to_string(), "/tmp/.lock".to_string()]
+            }
+        );
+        assert_eq!(probe.period_seconds, 5);
+
+        let disabled =
+            HashMap::from([(annotation::READINESS_PROBE.to_string(), "none".to_string())]);
+        assert!(
+            ReadinessProbe::from_annotations(Some(&disabled), 8080)
+                .unwrap()
+                .is_none()
+        );
+
+        let invalid = HashMap::from([(annotation::READINESS_PORT.to_string(), "http".to_string())]);
+        assert!(ReadinessProbe::from_annotations(Some(&invalid), 8080).is_err());
+    }
+}
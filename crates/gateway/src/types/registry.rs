@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Registry credentials of a namespace, either in docker `config.json` style
+/// or as a plain `registry -> credential` map. Both can be given at once,
+/// entries in `registries` take precedence.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryAuth {
+    /// Content of a docker `config.json`, only the `auths` section is used
+    pub docker_config: Option<DockerConfig>,
+
+    /// Credentials keyed by registry host, e.g. `registry.example.com`
+    pub registries: Option<HashMap<String, RegistryCredential>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DockerConfig {
+    #[serde(default)]
+    pub auths: HashMap<String, DockerAuthEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DockerAuthEntry {
+    /// base64 encoded `username:password`
+    pub auth: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[serde(rename = "identitytoken")]
+    pub identity_token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RegistryCredential {
+    pub username: String,
+    /// Password or access token of the user
+    #[serde(alias = "password")]
+    pub token: String,
+}
+
+impl RegistryAuth {
+    /// Flatten both forms into a `host -> credential` map, hosts are normalized
+    /// by [`normalize_registry_host`]
+    pub fn credentials(&self) -> Result<HashMap<String, RegistryCredential>, String> {
+        let mut creds = HashMap::new();
+        if let Some(config) = &self.docker_config {
+            for (host, entry) in &config.auths {
+                creds.insert(normalize_registry_host(host), entry.credential(host)?);
+            }
+        }
+        if let Some(registries) = &self.registries {
+            for (host, cred) in registries {
+                creds.insert(normalize_registry_host(host), cred.clone());
+            }
+        }
+        if creds.is_empty() {
+            return Err("no registry credential provided".to_string());
+        }
+        Ok(creds)
+    }
+}
+
+impl DockerAuthEntry {
+    fn credential(&self, host: &str) -> Result<RegistryCredential, String> {
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            return Ok(RegistryCredential {
+                username: username.clone(),
+                token: password.clone(),
+            });
+        }
+        if let Some(token) = &self.identity_token {
+            return Ok(RegistryCredential {
+                username: self.username.clone().unwrap_or_default(),
+                token: token.clone(),
+            });
+        }
+        let auth = self
+            .auth
+            .as_ref()
+            .ok_or(format!("no credential found for registry {}", host))?;
+        let decoded = base64::decode(auth)
+            .ok()
+            .and_then(|raw| String::from_utf8(raw).ok())
+            .ok_or(format!("invalid auth field for registry {}", host))?;
+        let (username, token) = decoded
+            .split_once(':')
+            .ok_or(format!("invalid auth field for registry {}", host))?;
+        Ok(RegistryCredential {
+            username: username.to_string(),
+            token: token.to_string(),
+        })
+    }
+}
+
+/// Strip scheme and path, and fold the docker hub aliases into `docker.io`
+pub fn normalize_registry_host(host: &str) -> String {
+    let host = host
+        .trim_start_matches("https://")
+        .trim_start_matches("http://");
+    let host = host.split('/').next().unwrap_or(host);
+    match host {
+        "index.docker.io" | "registry-1.docker.io" | "registry.hub.docker.com" => {
+            "docker.io".to_string()
+        }
+        _ => host.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_docker_config_credentials() {
+        let auth: RegistryAuth = serde_json::from_str(
+            r#"{
+                "dockerConfig": {
+                    "auths": {
+                        "https://index.docker.io/v1/": { "auth": "dXNlcjpzZWNyZXQ=" },
+                        "ghcr.io": { "username": "bot", "password": "pat" }
+                    }
+                },
+                "registries": {
+                    "registry.example.com": { "username": "ci", "token": "t0k3n" }
+                }
+            }"#,
+        )
+        .unwrap();
+        let creds = auth.credentials().unwrap();
+        assert_eq!(creds.len(), 3);
+        assert_eq!(creds["docker.io"].username, "user");
+        assert_eq!(creds["docker.io"].token, "secret");
+        assert_eq!(creds["ghcr.io"].token, "pat");
+        assert_eq!(creds["registry.example.com"].token, "t0k3n");
+    }
+
+    #[test]
+    fn test_empty_credentials() {
+        assert!(RegistryAuth::default().credentials().is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use super::annotation;
+
+/// Seccomp profile a function runs with, given by the `faasrs.io/seccomp-profile` annotation
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum SeccompProfile {
+    /// The provider's built-in profile
+    #[default]
+    Default,
+    /// No seccomp filtering
+    Unconfined,
+    /// A custom profile installed on the host by an administrator
+    Named(String),
+}
+
+impl FromStr for SeccompProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "" | "default" => Ok(SeccompProfile::Default),
+            "unconfined" => Ok(SeccompProfile::Unconfined),
+            name if valid_profile_name(name) => Ok(SeccompProfile::Named(name.to_string())),
+            name => Err(format!("invalid seccomp profile name '{}'", name)),
+        }
+    }
+}
+
+impl fmt::Display for SeccompProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeccompProfile::Default => write!(f, "default"),
+            SeccompProfile::Unconfined => write!(f, "unconfined"),
+            SeccompProfile::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Profile names are file names on the host, so only lowercase letters, digits,
+/// `-`, `_` and `.` are accepted and the name cannot start with `.`
+fn valid_profile_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 63
+        && !name.starts_with('.')
+        && name.chars().all(|c| {
+            c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_' || c == '.'
+        })
+}
+
+/// Capability names are accepted with or without the `CAP_` prefix and in any
+/// case, and normalized to the upper case name without prefix, e.g. `NET_ADMIN`
+pub fn normalize_capability(name: &str) -> Result<String, String> {
+    let upper = name.trim().to_ascii_uppercase();
+    let cap = upper.strip_prefix("CAP_").unwrap_or(&upper);
+    if cap.is_empty() || !cap.chars().all(|c| c.is_ascii_uppercase() || c == '_') {
+        return Err(format!("invalid capability '{}'", name));
+    }
+    Ok(cap.to_string())
+}
+
+/// Security settings a deployment asks for through annotations
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SecurityOptions {
+    /// Capabilities added to the default set, `ALL` adds every capability
+    pub cap_add: Vec<String>,
+    /// Capabilities removed from the default set, `ALL` removes every capability
+    pub cap_drop: Vec<String>,
+    pub seccomp: SeccompProfile,
+}
+
+impl SecurityOptions {
+    pub fn from_annotations(annotations: Option<&HashMap<String, String>>) -> Result<Self, String> {
+        let caps = |key: &str| -> Result<Vec<String>, String> {
+            let Some(list) = annotations.and_then(|a| a.get(key)) else {
+                return Ok(Vec::new());
+            };
+            let mut caps = list
+                .split(',')
+                .filter(|cap| !cap.trim().is_empty())
+                .map(normalize_capability)
+                .collect::<Result<Vec<_>, _>>()?;
+            caps.sort();
+            caps.dedup();
+            Ok(caps)
+        };
+        let seccomp = annotations
+            .and_then(|a| a.get(annotation::SECCOMP_PROFILE))
+            .map(|profile| profile.parse())
+            .transpose()?
+            .unwrap_or_default();
+        Ok(SecurityOptions {
+            cap_add: caps(annotation::CAP_ADD)?,
+            cap_drop: caps(annotation::CAP_DROP)?,
+            seccomp,
+        })
+    }
+}
+
+/// Limits the administrator puts on the security options of non-admin users.
+/// Dropping capabilities and the default seccomp profile are always allowed
+#[derive(Debug, Clone, Default)]
+pub struct SecurityPolicy {
+    /// Capabilities non-admin users may add, `SECURITY_ALLOWED_CAPABILITIES`
+    pub allowed_capabilities: HashSet<String>,
+    /// Named seccomp profiles non-admin users may use, `SECURITY_ALLOWED_SECCOMP_PROFILES`
+    pub allowed_seccomp_profiles: HashSet<String>,
+    /// Whether non-admin users may run functions unconfined, `SECURITY_ALLOW_UNCONFINED`
+    pub allow_unconfined: bool,
+}
+
+impl SecurityPolicy {
+    pub fn from_env() -> Self {
+        let list = |key: &str| -> Vec<String> {
+            std::env::var(key)
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        let allowed_capabilities = list("SECURITY_ALLOWED_CAPABILITIES")
+            .iter()
+            .map(|cap| normalize_capability(cap))
+            .collect::<Result<_, _>>()
+            .expect("SECURITY_ALLOWED_CAPABILITIES must be a list of capability names");
+        let allow_unconfined = std::env::var("SECURITY_ALLOW_UNCONFINED")
+            .map(|v| {
+                v.parse::<bool>()
+                    .expect("SECURITY_ALLOW_UNCONFINED must be true or false")
+            })
+            .unwrap_or(false);
+        SecurityPolicy {
+            allowed_capabilities,
+            allowed_seccomp_profiles: list("SECURITY_ALLOWED_SECCOMP_PROFILES")
+                .into_iter()
+                .collect(),
+            allow_unconfined,
+        }
+    }
+
+    /// Checks the options of a non-admin user against the policy
+    pub fn check(&self, options: &SecurityOptions) -> Result<(), String> {
+        if let Some(cap) = options
+            .cap_add
+            .iter()
+            .find(|cap| !self.allowed_capabilities.contains(*cap))
+        {
+            return Err(format!("adding capability {} requires an admin", cap));
+        }
+        match &options.seccomp {
+            SeccompProfile::Default => Ok(()),
+            SeccompProfile::Unconfined if self.allow_unconfined => Ok(()),
+            SeccompProfile::Named(name) if self.allowed_seccomp_profiles.contains(name) => Ok(()),
+            profile => Err(format!("seccomp profile '{}' requires an admin", profile)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_security_options() {
+        let annotations = HashMap::from([
+            (
+                annotation::CAP_ADD.to_string(),
+                "cap_net_admin, NET_RAW".to_string(),
+            ),
+            (annotation::CAP_DROP.to_string(), "all".to_string()),
+            (
+                annotation::SECCOMP_PROFILE.to_string(),
+                "strict-v1".to_string(),
+            ),
+        ]);
+        let options = SecurityOptions::from_annotations(Some(&annotations)).unwrap();
+        assert_eq!(options.cap_add, vec!["NET_ADMIN", "NET_RAW"]);
+        assert_eq!(options.cap_drop, vec!["ALL"]);
+        assert_eq!(
+            options.seccomp,
+            SeccompProfile::Named("strict-v1".to_string())
+        );
+        assert_eq!(
+            SecurityOptions::from_annotations(None).unwrap(),
+            SecurityOptions::default()
+        );
+        assert!("../etc/passwd".parse::<SeccompProfile>().is_err());
+        assert!(normalize_capability("NET-ADMIN").is_err());
+
+        let policy = SecurityPolicy {
+            allowed_capabilities: HashSet::from(["NET_RAW".to_string()]),
+            ..Default::default()
+        };
+        assert!(policy.check(&options).is_err());
+        let options = SecurityOptions {
+            cap_add: vec!["NET_RAW".to_string()],
+            cap_drop: vec!["ALL".to_string()],
+            seccomp: SeccompProfile::Default,
+        };
+        assert!(policy.check(&options).is_ok());
+        let unconfined = SecurityOptions {
+            seccomp: SeccompProfile::Unconfined,
+            ..Default::default()
+        };
+        assert!(policy.check(&unconfined).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+/// A named host directory of a namespace that functions can mount
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Volume {
+    pub name: String,
+
+    pub namespace: Option<String>,
+
+    /// Functions currently mounting the volume
+    #[serde(default)]
+    pub mounted_by: Vec<String>,
+
+    /// RFC 3339 time the volume was created at
+    pub created_at: Option<String>,
+}
+
+/// Mount of a volume into a function, given in the function's `volumes`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeMount {
+    /// Name of a volume in the function's namespace
+    pub name: String,
+
+    /// Absolute path of the mount inside the function
+    pub mount_path: String,
+
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// Whether `name` can be used as a volume name: lowercase letters, digits and
+/// `-`, starting and ending with a letter or digit, at most 63 characters
+pub fn valid_volume_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 63
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_volume_mount() {
+        let mount: VolumeMount =
+            serde_json::from_str(r#"{"name": "models", "mountPath": "/models"}"#).unwrap();
+        assert!(!mount.read_only);
+        assert!(valid_volume_name("models-v2"));
+        assert!(!valid_volume_name("../etc"));
+        assert!(!valid_volume_name("Models"));
+        assert!(!valid_volume_name("-models"));
+    }
+}